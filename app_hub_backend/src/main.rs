@@ -1,7 +1,15 @@
-use backend::app_image_installer::install_app_image;
+use backend::app_image_info::app_image_info;
+use backend::app_image_installer::{install_app_image, install_app_images};
 use backend::app_image_uninstaller::uninstall_app_image;
 use backend::app_image_updater::app_image_update;
+use backend::app_image_versions::{activate_app_version, remove_app_version};
 use clap::Parser;
+use common_utils::download_helpers::{
+    download_app_image, remove_download, DownloadOptions, DEFAULT_MAX_DOWNLOAD_SIZE,
+};
+use common_utils::install_transaction::{recover_transactions, transactions_dir};
+use common_utils::models::existing_app_policy::ExistingAppPolicy;
+use common_utils::models::install_scope::InstallScope;
 use log::{error, info};
 use serde::Serialize;
 use std::path::PathBuf;
@@ -11,8 +19,15 @@ use std::path::PathBuf;
 enum Action {
     #[default]
     Install,
+    /// Install several AppImages under a single authorization, reporting the outcome of each one
+    InstallBatch,
     Uninstall,
     Update,
+    Info,
+    /// Make a version installed side by side the one the desktop entry launches
+    Activate,
+    /// Remove a version installed side by side
+    RemoveVersion,
 }
 
 #[derive(Parser, Debug)]
@@ -22,6 +37,24 @@ struct Args {
     #[arg(short, long)]
     file_path: Option<String>,
 
+    /// AppImage file paths to install in one batch
+    #[arg(long, num_args = 1..)]
+    file_paths: Vec<String>,
+
+    /// HTTP(S) URL of the AppImage to download and install, instead of a file path
+    /// Interrupted downloads of the same URL are resumed.
+    /// Only for the user scope, nothing is downloaded with root privileges
+    #[arg(long, conflicts_with = "file_path")]
+    url: Option<String>,
+
+    /// Expected SHA-256 digest of the downloaded AppImage, hex encoded
+    #[arg(long, requires = "url")]
+    sha256: Option<String>,
+
+    /// Largest AppImage to download, in bytes
+    #[arg(long, requires = "url", default_value_t = DEFAULT_MAX_DOWNLOAD_SIZE)]
+    max_download_size: u64,
+
     /// Installation directory
     /// The directory where the AppImage will be installed
     #[arg(short, long)]
@@ -33,22 +66,44 @@ struct Args {
         long,
         default_value_t,
         value_enum,
-        requires_if("install", "file_path"),
         requires_if("install", "install_dir"),
-        requires_if("uninstall", "uninstall_app_name"),
+        requires_if("install-batch", "file_paths"),
+        requires_if("install-batch", "install_dir"),
+        requires_if("uninstall", "app_id"),
         requires_if("update", "new_install_dir"),
-        requires_if("update", "old_install_dir")
+        requires_if("update", "old_install_dir"),
+        requires_if("activate", "app_id"),
+        requires_if("activate", "app_version"),
+        requires_if("remove-version", "app_id"),
+        requires_if("remove-version", "app_version")
     )]
     action: Action,
 
-    /// App name to uninstall
-    #[arg(short, long)]
-    uninstall_app_name: Option<String>,
+    /// Id of the app to uninstall or show the information of, as recorded in the install database
+    #[arg(long)]
+    app_id: Option<String>,
+
+    /// Version of the app to activate or remove, as shown by the info action
+    #[arg(long)]
+    app_version: Option<String>,
+
+    /// App name to show the information of, when its id is not known
+    #[arg(long)]
+    app_name: Option<String>,
 
     /// No sandbox flag
     #[arg(short, long)]
     no_sandbox: Option<bool>,
 
+    /// Create portable home and config directories next to the installed AppImage
+    /// The AppImage runtime keeps the data of the app in them instead of the home directory
+    #[arg(long)]
+    portable: Option<bool>,
+
+    /// Delete the portable home and config directories of the uninstalled app or removed version
+    #[arg(long)]
+    remove_portable_data: Option<bool>,
+
     /// New app images directory path
     /// The new path to the directory where the AppImages are stored
     #[arg(long)]
@@ -58,6 +113,40 @@ struct Args {
     /// The old path to the directory where the AppImages are stored
     #[arg(long)]
     old_install_dir: Option<String>,
+
+    /// Installation scope, `user` or `system`
+    /// User installations go to the home directory, system ones need root privileges
+    #[arg(long, default_value = "system", value_parser = parse_scope)]
+    scope: InstallScope,
+
+    /// What to do when the app is already installed, `upgrade`, `downgrade` or `side-by-side`
+    /// An upgrade refuses older versions, a downgrade accepts them, side-by-side keeps both
+    #[arg(long, default_value = "upgrade", value_parser = parse_existing)]
+    existing: ExistingAppPolicy,
+}
+
+fn parse_scope(raw: &str) -> Result<InstallScope, String> {
+    InstallScope::parse(raw).ok_or(format!("Invalid scope: {}", raw))
+}
+
+fn parse_existing(raw: &str) -> Result<ExistingAppPolicy, String> {
+    ExistingAppPolicy::parse(raw).ok_or(format!("Invalid existing app policy: {}", raw))
+}
+
+/// Download the AppImage into the staging directory of the scope, logging the progress
+fn download(url: &str, scope: InstallScope, options: &DownloadOptions) -> Result<PathBuf, String> {
+    let download_dir = scope.download_dir()?;
+    let mut last_percent = None;
+    download_app_image(url, &download_dir, options, |downloaded, total| {
+        let Some(total) = total.filter(|total| *total > 0) else {
+            return;
+        };
+        let percent = downloaded * 100 / total;
+        if last_percent != Some(percent) {
+            info!("Downloaded {}% of {} bytes", percent, total);
+            last_percent = Some(percent);
+        }
+    })
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -68,38 +157,110 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("Starting AppHub backend");
     let args = Args::parse();
 
+    // roll back the installations interrupted by a crash
+    if let Ok(journal_dir) = transactions_dir(args.scope) {
+        let recovered = recover_transactions(&journal_dir);
+        if recovered > 0 {
+            info!("Rolled back {} interrupted installations", recovered);
+        }
+    }
+
     match args.action {
         Action::Install => {
             info!("Received install action");
             // read required arguments
-            let file_path = args.file_path.as_ref().ok_or("file_path is required")?;
             let installation_dir = args.install_dir.as_ref().ok_or("install_dir is required")?;
             let no_sandbox = args.no_sandbox.unwrap_or(false);
+            let portable = args.portable.unwrap_or(false);
+
+            // download the AppImage into the staging directory first
+            let file_path = match (&args.file_path, &args.url) {
+                (Some(file_path), _) => PathBuf::from(file_path),
+                (None, Some(_)) if args.scope.needs_privileges() => {
+                    return Err("AppImages are not downloaded with root privileges, \
+                        download the AppImage first and install the file"
+                        .into());
+                }
+                (None, Some(url)) => {
+                    let options = DownloadOptions {
+                        max_size: args.max_download_size,
+                        sha256: args.sha256.clone(),
+                    };
+                    match download(url, args.scope, &options) {
+                        Ok(file_path) => file_path,
+                        Err(e) => {
+                            error!("Failed to download AppImage: {}", e);
+                            return Err("Failed to download AppImage".into());
+                        }
+                    }
+                }
+                (None, None) => return Err("file_path or url is required".into()),
+            };
 
             // install the AppImage
-            if let Err(e) = install_app_image(
-                PathBuf::from(file_path.clone()),
+            let result = install_app_image(
+                file_path.clone(),
                 PathBuf::from(installation_dir.clone()),
                 no_sandbox,
-            ) {
+                portable,
+                args.scope,
+                args.existing,
+            );
+            if args.url.is_some() && result.is_ok() {
+                // the staged download is not needed anymore
+                if let Err(e) = remove_download(&file_path) {
+                    error!("{}", e);
+                }
+            }
+            if let Err(e) = result {
                 error!("Failed to install AppImage: {}", e);
                 return Err("Failed to install AppImage".into());
             }
-            info!("Installing AppImage: {}", file_path);
+            info!("Installing AppImage: {:?}", file_path);
+        }
+        Action::InstallBatch => {
+            info!("Received batch install action");
+            // read required arguments
+            let installation_dir = args.install_dir.as_ref().ok_or("install_dir is required")?;
+            let no_sandbox = args.no_sandbox.unwrap_or(false);
+            let portable = args.portable.unwrap_or(false);
+
+            // install the AppImages, each one on its own line of the output
+            let outcomes = install_app_images(
+                args.file_paths.iter().map(PathBuf::from).collect(),
+                PathBuf::from(installation_dir.clone()),
+                no_sandbox,
+                portable,
+                args.scope,
+                args.existing,
+                |outcome| println!("{}", outcome),
+            );
+
+            let failed = outcomes
+                .iter()
+                .filter(|outcome| !outcome.is_success())
+                .count();
+            if failed > 0 {
+                error!(
+                    "Failed to install {} of {} AppImages",
+                    failed,
+                    outcomes.len()
+                );
+                return Err("Failed to install AppImages".into());
+            }
+            info!("Installed {} AppImages", outcomes.len());
         }
         Action::Uninstall => {
             // read required arguments
-            let app_name = args
-                .uninstall_app_name
-                .as_ref()
-                .ok_or("uninstall_app_name is required")?;
+            let app_id = args.app_id.as_ref().ok_or("app_id is required")?;
 
             // uninstall the AppImage
-            if let Err(e) = uninstall_app_image(app_name.clone()) {
+            let remove_portable_data = args.remove_portable_data.unwrap_or(false);
+            if let Err(e) = uninstall_app_image(app_id.clone(), args.scope, remove_portable_data) {
                 error!("Failed to uninstall AppImage: {}", e);
                 return Err("Failed to uninstall AppImage".into());
             }
-            info!("Uninstalling AppImage with app name: {}", app_name);
+            info!("Uninstalling AppImage with app id: {}", app_id);
         }
         Action::Update => {
             // read required arguments
@@ -117,7 +278,51 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 new_install_dir
             );
 
-            app_image_update(old_install_dir.clone(), new_install_dir.clone())?;
+            app_image_update(old_install_dir.clone(), new_install_dir.clone(), args.scope)?;
+        }
+        Action::Info => {
+            // read required arguments
+            let app = args
+                .app_id
+                .as_ref()
+                .or(args.app_name.as_ref())
+                .ok_or("app_id or app_name is required")?;
+
+            match app_image_info(app.clone()) {
+                Ok(app_info) => println!("{}", app_info),
+                Err(e) => {
+                    error!("Failed to read AppImage information: {}", e);
+                    return Err("Failed to read AppImage information".into());
+                }
+            }
+        }
+        Action::Activate => {
+            // read required arguments
+            let app_id = args.app_id.as_ref().ok_or("app_id is required")?;
+            let app_version = args.app_version.as_ref().ok_or("app_version is required")?;
+
+            if let Err(e) = activate_app_version(app_id.clone(), app_version.clone(), args.scope) {
+                error!("Failed to activate version: {}", e);
+                return Err("Failed to activate version".into());
+            }
+            info!("Activated version {} of app {}", app_version, app_id);
+        }
+        Action::RemoveVersion => {
+            // read required arguments
+            let app_id = args.app_id.as_ref().ok_or("app_id is required")?;
+            let app_version = args.app_version.as_ref().ok_or("app_version is required")?;
+
+            let remove_portable_data = args.remove_portable_data.unwrap_or(false);
+            if let Err(e) = remove_app_version(
+                app_id.clone(),
+                app_version.clone(),
+                args.scope,
+                remove_portable_data,
+            ) {
+                error!("Failed to remove version: {}", e);
+                return Err("Failed to remove version".into());
+            }
+            info!("Removed version {} of app {}", app_version, app_id);
         }
     }
 
@@ -125,3 +330,39 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, clap::Error> {
+        let uninstall = [
+            "app_hub_backend",
+            "--action",
+            "uninstall",
+            "--app-id",
+            "org.example.App",
+        ];
+        Args::try_parse_from(uninstall.iter().chain(args))
+    }
+
+    #[test]
+    fn test_parse_scope() {
+        assert_eq!(parse(&[]).unwrap().scope, InstallScope::System);
+        assert_eq!(
+            parse(&["--scope", "user"]).unwrap().scope,
+            InstallScope::User
+        );
+        assert!(parse(&["--scope", "everyone"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_existing() {
+        assert_eq!(parse(&[]).unwrap().existing, ExistingAppPolicy::Upgrade);
+        for policy in ExistingAppPolicy::ALL {
+            let args = parse(&["--existing", &policy.to_string()]).unwrap();
+            assert_eq!(args.existing, policy);
+        }
+        assert!(parse(&["--existing", "replace"]).is_err());
+    }
+}
@@ -0,0 +1,87 @@
+use common_utils::app_images_helpers::read_all_app;
+use common_utils::models::installed_app::InstalledApp;
+use log::info;
+
+/// Find an installed app by id or name and describe it in a human readable form
+pub fn app_image_info(app: String) -> Result<String, String> {
+    info!(
+        "Reading information of AppImage with app id or name: {}",
+        app
+    );
+
+    let installed_app = read_all_app()?
+        .into_iter()
+        .find(|installed_app| installed_app.id == app || installed_app.name == app)
+        .ok_or(format!("App not found: {}", app))?;
+
+    Ok(format_app_info(&installed_app))
+}
+
+fn format_app_info(app: &InstalledApp) -> String {
+    let mut lines = vec![format!("Name: {}", app.name)];
+    lines.push(format!("Id: {}", app.id));
+    lines.push(format!("Path: {}", app.app_path));
+    lines.push(format!("Command: {}", app.exec));
+    if let Some(version) = &app.version {
+        lines.push(format!("Version: {}", version));
+    }
+    for version in &app.other_versions {
+        lines.push(format!(
+            "Other version: {} ({})",
+            version.label(),
+            version.app_image_path.display()
+        ));
+    }
+    for dir in &app.portable_dirs {
+        lines.push(format!("Portable directory: {}", dir.display()));
+    }
+    if let Some(categories) = &app.categories {
+        lines.push(format!("Categories: {}", categories));
+    }
+    if let Some(update_info) = &app.update_info {
+        lines.push(format!("Updates: {}", update_info.source()));
+    }
+    if let Some(signature) = &app.signature {
+        lines.push(format!("Signature: {}", signature));
+    }
+    if !app.mime_types.is_empty() {
+        lines.push(format!("MIME types: {}", app.mime_types.join(", ")));
+    }
+    for action in &app.actions {
+        lines.push(format!("Action: {} ({})", action.name, action.id));
+    }
+
+    if let Some(metadata) = &app.metadata {
+        if let Some(id) = &metadata.id {
+            lines.push(format!("AppStream id: {}", id));
+        }
+        if let Some(summary) = &metadata.summary {
+            lines.push(format!("Summary: {}", summary));
+        }
+        if let Some(license) = &metadata.project_license {
+            lines.push(format!("License: {}", license));
+        }
+        if let Some(homepage) = &metadata.homepage {
+            lines.push(format!("Homepage: {}", homepage));
+        }
+        for screenshot in &metadata.screenshots {
+            lines.push(format!("Screenshot: {}", screenshot.image));
+        }
+        if let Some(description) = &metadata.description {
+            lines.push(String::new());
+            lines.push(description.clone());
+        }
+        if !metadata.releases.is_empty() {
+            lines.push(String::new());
+            lines.push("Releases:".to_string());
+            for release in &metadata.releases {
+                match &release.date {
+                    Some(date) => lines.push(format!("  {} ({})", release.version, date)),
+                    None => lines.push(format!("  {}", release.version)),
+                }
+            }
+        }
+    }
+
+    lines.join("\n")
+}
@@ -1,31 +1,122 @@
-use common_utils::app_images_helpers::{app_image_extract_squashroot, choose_icon};
+use common_utils::app_id_helpers::{
+    desktop_file_name, installed_name, version_label, versioned_app_image_name,
+};
+use common_utils::app_image_reader::AppImageReader;
+use common_utils::app_images_helpers::{
+    app_image_extract_squashroot, find_metainfo_file, identify_app_image, install_icons,
+    install_metainfo, install_mime_packages, remove_icon, remove_metainfo, remove_mime_packages,
+};
+use common_utils::desktop_file::DesktopFile;
 use common_utils::desktop_file_builder::DesktopFileBuilder;
-use common_utils::desktop_file_helpers::find_desktop_file_location;
+use common_utils::desktop_file_helpers::{find_desktop_file_location, keep_customizations};
+use common_utils::desktop_file_validator::{
+    fix_desktop_file, has_errors, validate_desktop_file, Severity,
+};
 use common_utils::file_system_helpers::{
     add_executable_permission, find_desktop_file_in_dir, get_file_name,
 };
-use log::info;
-use std::fs::File;
-use std::io::Write;
+use common_utils::icon_helpers::{find_icon_theme_location, update_icon_cache};
+use common_utils::install_database::InstallDatabase;
+use common_utils::install_transaction::{transactions_dir, InstallTransaction};
+use common_utils::mime_helpers::{
+    find_mime_location, update_desktop_database, update_mime_database,
+};
+use common_utils::models::app_identity::AppIdentity;
+use common_utils::models::app_stream_metadata::AppStreamMetadata;
+use common_utils::models::exec_command::ExecCommand;
+use common_utils::models::existing_app_policy::ExistingAppPolicy;
+use common_utils::models::install_outcome::InstallOutcome;
+use common_utils::models::install_record::InstallRecord;
+use common_utils::models::install_scope::InstallScope;
+use common_utils::portable_helpers::{create_portable_dirs, move_portable_dirs};
+use common_utils::signature_helpers::{keyring_dirs, verify_app_image_signature};
+use common_utils::version_helpers::compare_versions;
+use log::{error, info, warn};
+use std::cmp::Ordering;
+use std::fs;
 use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub fn install_app_image(
     file_path: PathBuf,
     installation_dir: PathBuf,
     no_sandbox: bool,
+    portable: bool,
+    scope: InstallScope,
+    existing: ExistingAppPolicy,
 ) -> Result<(), String> {
     info!("##### REQUESTED TO INSTALL APP ####");
     info!("# File path: {:?}", &file_path);
     info!("# No sandbox: {:?}", &no_sandbox);
+    info!("# Portable: {:?}", &portable);
+    info!("# Scope: {}", &scope);
+    info!("# Existing app: {}", &existing);
     info!("#################################");
 
-    // Add executable permission to the AppImage
-    add_executable_permission(&file_path);
+    // the directories would belong to root, the apps of the users could not write in them
+    if portable && scope.needs_privileges() {
+        return Err("Portable directories are only created for the current user".into());
+    }
+
+    let mut installation_file_name = get_file_name(&file_path)?;
 
-    let installation_file_name = get_file_name(&file_path)?;
+    // every file is staged in the transaction, an error at any step rolls back the previous ones
+    let mut transaction = InstallTransaction::begin(&transactions_dir(scope)?)?;
 
-    // extract squashrootfs from AppImage
-    let squashroot_path = app_image_extract_squashroot(&file_path)?;
+    // extract metadata from AppImage, without executing it, into a private temporary directory
+    // which its guard removes even when the installation fails
+    let squashroot = app_image_extract_squashroot(&file_path)?;
+    let squashroot_path = squashroot.path().to_path_buf();
+    transaction.add_scratch_dir(&squashroot_path)?;
+
+    // read the update information embedded in the AppImage
+    let mut app_image_reader = AppImageReader::open(&file_path)?;
+    let update_info = app_image_reader.update_info()?;
+    match &update_info {
+        Some(update_info) => info!("Update information: {}", update_info),
+        None => info!("The AppImage has no update information"),
+    }
+
+    // verify the embedded signature against the AppHub keyring
+    let signature = verify_app_image_signature(&app_image_reader, &keyring_dirs())?;
+    info!("Signature status: {}", signature);
+
+    // identify the app, the installed version of the same app is replaced or kept next to the new one
+    let identity = identify_app_image(&mut app_image_reader)?;
+    info!("App id: {}, version: {:?}", identity.id, identity.version);
+    let mut database = InstallDatabase::open(scope)?;
+    let id = identity.id.clone();
+    let previous = database.find_installed(&identity).cloned();
+    let side_by_side = previous.is_some() && existing == ExistingAppPolicy::SideBySide;
+    if let Some(installed) = &previous {
+        if side_by_side {
+            let label = version_label(identity.version.as_deref(), &identity.sha256);
+            if installed
+                .versions()
+                .iter()
+                .any(|version| version.label() == label)
+            {
+                return Err(format!("{} {} is already installed", installed.name, label));
+            }
+            info!("Installing {} {} side by side", installed.name, label);
+        } else {
+            check_version_change(installed, &identity, existing)?;
+        }
+    }
+
+    // the new version goes where the previous one was installed, with its options
+    let installation_dir = match previous
+        .as_ref()
+        .and_then(|previous| previous.app_image_path.parent())
+    {
+        Some(previous_dir) => previous_dir.to_path_buf(),
+        None => installation_dir,
+    };
+    let no_sandbox = no_sandbox
+        || previous
+            .as_ref()
+            .is_some_and(|previous| previous.no_sandbox);
+    let portable = portable || previous.as_ref().is_some_and(|previous| previous.portable);
 
     // check installation dir exist
     if !installation_dir.exists() {
@@ -44,32 +135,175 @@ pub fn install_app_image(
         }
     };
 
-    let mut desktop_builder =
-        match DesktopFileBuilder::from_desktop_entry_path(&desktop_file_path, false) {
-            Ok(db) => db,
-            Err(err) => {
-                return Err(err.to_string());
+    // validate the desktop file: refuse broken entries, fix the fixable ones
+    let mut desktop_file = DesktopFile::from_path(&desktop_file_path)?;
+    let issues = validate_desktop_file(&desktop_file);
+    for issue in &issues {
+        match issue.severity {
+            Severity::Error => error!("Desktop file {}", issue),
+            Severity::Warning => info!("Desktop file {}", issue),
+        }
+    }
+    if has_errors(&issues) {
+        let errors: Vec<String> = issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .map(|issue| issue.to_string())
+            .collect();
+        return Err(format!("Invalid desktop file:\n{}", errors.join("\n")));
+    }
+    let fixed = fix_desktop_file(&mut desktop_file, &issues);
+    if fixed > 0 {
+        info!("Fixed {} problems of the desktop file", fixed);
+    }
+
+    // keep what the user changed in the desktop entry of the previous version
+    if let Some(entry_path) = previous
+        .as_ref()
+        .and_then(|previous| previous.desktop_entries.first())
+    {
+        match DesktopFile::from_path(entry_path) {
+            Ok(installed) => {
+                let kept = keep_customizations(&installed, &mut desktop_file);
+                info!(
+                    "Kept {} customizations of the installed desktop entry",
+                    kept
+                );
             }
-        };
+            Err(err) => warn!("Failed to read the installed desktop entry: {}", err),
+        }
+    }
+
+    let mut desktop_builder = match DesktopFileBuilder::from_desktop_file(desktop_file, false) {
+        Ok(db) => db,
+        Err(err) => {
+            return Err(err.to_string());
+        }
+    };
+
+    let app_name = match desktop_builder.name() {
+        None => {
+            return Err("Failed to get app name".to_string());
+        }
+        Some(name) => name,
+    };
 
-    // copy icons to icons directory
+    // install icons into the hicolor icon theme, under a name unique to the app
     info!("Installing icons...");
-    let icon_path = match choose_icon(&squashroot_path, &installation_dir) {
-        Ok(path) => path,
+    let theme_dir = find_icon_theme_location(scope)?;
+    let theme_icon_name = installed_name(&id);
+    let icon_paths = match install_icons(
+        &squashroot_path,
+        &theme_dir,
+        desktop_builder.icon(),
+        &theme_icon_name,
+        &mut transaction,
+    ) {
+        Ok(paths) => paths,
         Err(err) => {
             return Err(err.to_string());
         }
     };
 
-    // set icon path
-    desktop_builder.set_icon(icon_path.to_string_lossy().to_string());
+    // set icon theme name and remember the installed files
+    desktop_builder.set_icon(theme_icon_name.clone());
+    desktop_builder.set_icons(
+        icon_paths
+            .iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect(),
+    );
 
-    // set builder properties
-    desktop_builder.set_exec(format!(
-        "{}{}",
-        installation_dir.to_string_lossy(),
-        installation_file_name
-    ));
+    // copy the AppStream metainfo file to the metainfo directory,
+    // only the installed file is recorded, never a path named by the AppImage
+    let mut installed_metainfo = None;
+    if let Some(metainfo_path) = find_metainfo_file(&squashroot_path) {
+        match AppStreamMetadata::from_path(&metainfo_path) {
+            Ok(metadata) => {
+                info!("AppStream component: {:?}", metadata.id);
+                let installed_metainfo_path =
+                    install_metainfo(&metainfo_path, &installation_dir, &mut transaction)?;
+                desktop_builder.set_metainfo(installed_metainfo_path.to_string_lossy().to_string());
+                installed_metainfo = Some(installed_metainfo_path);
+            }
+            Err(err) => {
+                error!("Ignoring invalid metainfo file: {}", err);
+            }
+        }
+    } else {
+        info!("The AppImage has no AppStream metainfo");
+    }
+
+    // install the MIME types shipped in the AppImage, under the same name as the icons
+    let mime_dir = find_mime_location(scope)?;
+    let mime_package_paths = install_mime_packages(
+        &squashroot_path,
+        &mime_dir,
+        &theme_icon_name,
+        &mut transaction,
+    )?;
+    if !mime_package_paths.is_empty() {
+        desktop_builder.set_mime_packages(
+            mime_package_paths
+                .iter()
+                .map(|path| path.to_string_lossy().to_string())
+                .collect(),
+        );
+    }
+    let has_mime_types = !desktop_builder.mime_types().is_empty();
+
+    // Stage the AppImage in the installation directory, with the executable permission.
+    // Versions installed side by side get versioned file names and no other AppImage is overwritten
+    if side_by_side {
+        installation_file_name =
+            versioned_app_image_name(&id, identity.version.as_deref(), &identity.sha256);
+    }
+    let replaced_app_image = previous
+        .as_ref()
+        .filter(|_| !side_by_side)
+        .map(|previous| previous.app_image_path.clone());
+    let is_taken = |path: &PathBuf| {
+        Some(path) != replaced_app_image.as_ref()
+            && database.apps.iter().any(|record| {
+                record
+                    .versions()
+                    .iter()
+                    .any(|version| &version.app_image_path == path)
+            })
+    };
+    if is_taken(&installation_dir.join(&installation_file_name)) {
+        installation_file_name =
+            versioned_app_image_name(&id, identity.version.as_deref(), &identity.sha256);
+        if is_taken(&installation_dir.join(&installation_file_name)) {
+            return Err(format!(
+                "{:?} belongs to another installed app",
+                installation_dir.join(&installation_file_name)
+            ));
+        }
+    }
+    let installation_path = installation_dir.join(&installation_file_name);
+    let staged_app_image = transaction.stage_copy(&file_path, &installation_path)?;
+    add_executable_permission(&staged_app_image);
+    let installation_program = installation_path.to_string_lossy().to_string();
+
+    // point the desktop actions to the installed AppImage, keeping their arguments
+    for action in desktop_builder.actions() {
+        if let Some(exec) = action.exec {
+            let mut command = ExecCommand::parse(&exec)?.with_program(installation_program.clone());
+            if no_sandbox {
+                command.add_argument("--no-sandbox");
+            }
+            info!("Action {}: {}", action.id, command);
+            desktop_builder.set_action_exec(&action.id, command.to_string());
+        }
+    }
+
+    // set builder properties, the AppImage replaces the program of the original command
+    let command = match desktop_builder.exec() {
+        Some(exec) => ExecCommand::parse(&exec)?.with_program(installation_program),
+        None => ExecCommand::new(installation_program),
+    };
+    desktop_builder.set_exec(command.to_string());
 
     desktop_builder.set_path(installation_dir.to_string_lossy().to_string());
 
@@ -77,19 +311,16 @@ pub fn install_app_image(
         desktop_builder.set_no_sandbox(true);
     }
 
-    // write desktop file to /usr/share/applications
-    let desktop_files_system_location = find_desktop_file_location()?;
-    let app_name = match desktop_builder.name() {
-        None => {
-            return Err("Failed to get app name".to_string());
-        }
-        Some(name) => name,
-    };
-    let desktop_entry_path = format!(
-        "{}/{}.desktop",
-        desktop_files_system_location.to_string_lossy().to_string(),
-        app_name
-    );
+    if let Some(update_info) = update_info {
+        desktop_builder.set_update_information(update_info.to_string());
+    }
+
+    desktop_builder.set_signature(signature.to_string());
+
+    // write desktop file to /usr/share/applications or $XDG_DATA_HOME/applications,
+    // last so it is moved into place after the files it refers to
+    let desktop_files_system_location = find_desktop_file_location(scope)?;
+    let desktop_entry_path = desktop_files_system_location.join(desktop_file_name(&id));
 
     let desktop_file_content = match desktop_builder.generate_content_string() {
         Ok(content) => content,
@@ -97,22 +328,209 @@ pub fn install_app_image(
     };
 
     info!("Writing .desktop file to: {:?}", desktop_entry_path);
-    let mut file =
-        File::create(desktop_entry_path).map_err(|e| "Failed to create .desktop file")?;
-    file.write_all(desktop_file_content.as_bytes())
-        .map_err(|_| "Failed to write .desktop file")?;
-
-    // Copy the AppImage to the installation directory
-    let installation_path = format!(
-        "{}/{}",
-        installation_dir.to_string_lossy(),
-        installation_file_name
-    );
-    std::fs::copy(&file_path, &installation_path)
-        .map_err(|_| "Failed to copy AppImage to installation dir")?;
+    transaction.stage_write(&desktop_entry_path, desktop_file_content.as_bytes())?;
 
-    // remove squashrootfs directory
-    std::fs::remove_dir_all(&squashroot_path).map_err(|_| "Failed to remove squashroot dir")?;
+    // record the installed files in the database, committed along with them.
+    // The other versions are kept, with the previous active one when installing side by side
+    let mut other_versions = Vec::new();
+    if let Some(previous) = &previous {
+        if side_by_side {
+            other_versions.push(previous.active_version());
+        }
+        other_versions.extend(previous.other_versions.iter().cloned());
+    }
+    let record = InstallRecord {
+        id,
+        name: app_name.clone(),
+        version: identity.version.clone(),
+        scope,
+        app_image_path: installation_path.clone(),
+        sha256: Some(identity.sha256.clone()),
+        desktop_entries: vec![desktop_entry_path],
+        icon_paths,
+        metainfo_path: installed_metainfo,
+        mime_packages: mime_package_paths.clone(),
+        source_path: file_path.canonicalize().ok(),
+        installed_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default(),
+        signature: Some(signature),
+        no_sandbox,
+        portable,
+        other_versions,
+    };
+    if let Some(previous) = &previous {
+        // an app adopted under its legacy id now has its stable id
+        database.remove(&previous.id);
+    }
+    database.upsert(record.clone());
+    transaction.stage_write(
+        &InstallDatabase::path(scope)?,
+        database.to_json()?.as_bytes(),
+    )?;
+
+    // move every file into place, the squashroot directory is removed
+    transaction.commit()?;
+
+    // the files of the previous version which the new one did not replace are left over
+    if let Some(previous) = &previous {
+        remove_replaced_files(previous, &record);
+    }
+
+    // The AppImage runtime keeps the data of the app next to it, the data of the replaced
+    // AppImage follows the new one. The installation is committed, so failures are only logged
+    if let Some(replaced_app_image) = &replaced_app_image {
+        if replaced_app_image != &installation_path {
+            if let Err(err) = move_portable_dirs(replaced_app_image, &installation_path) {
+                error!("Failed to move the portable directories: {}", err);
+            }
+        }
+    }
+    if portable {
+        if let Err(err) = create_portable_dirs(&installation_path) {
+            error!("Failed to create the portable directories: {}", err);
+        }
+    }
+
+    // refresh the caches of the installed files
+    update_icon_cache(&theme_dir);
+    if !mime_package_paths.is_empty() {
+        update_mime_database(&mime_dir);
+    }
+    // register the file associations of the entry
+    if has_mime_types || previous.is_some() {
+        update_desktop_database(&desktop_files_system_location);
+    }
 
     Ok(())
 }
+
+/// Install several AppImages in one run of the backend, so a single authorization covers them all.
+/// A failed installation, rolled back by its transaction, does not stop the next ones.
+/// Each outcome is reported as soon as it is known
+pub fn install_app_images(
+    file_paths: Vec<PathBuf>,
+    installation_dir: PathBuf,
+    no_sandbox: bool,
+    portable: bool,
+    scope: InstallScope,
+    existing: ExistingAppPolicy,
+    mut report: impl FnMut(&InstallOutcome),
+) -> Vec<InstallOutcome> {
+    info!("Installing {} AppImages", file_paths.len());
+    file_paths
+        .into_iter()
+        .map(|file_path| {
+            let result = install_app_image(
+                file_path.clone(),
+                installation_dir.clone(),
+                no_sandbox,
+                portable,
+                scope,
+                existing,
+            );
+            if let Err(e) = &result {
+                error!("Failed to install {:?}: {}", file_path, e);
+            }
+            let outcome = InstallOutcome {
+                file_path,
+                error: result.err(),
+            };
+            report(&outcome);
+            outcome
+        })
+        .collect()
+}
+
+/// Refuse to replace an installed app with an older version, unless downgrades are allowed
+fn check_version_change(
+    installed: &InstallRecord,
+    identity: &AppIdentity,
+    existing: ExistingAppPolicy,
+) -> Result<(), String> {
+    let (installed_version, version) = match (&installed.version, &identity.version) {
+        (Some(installed_version), Some(version)) => (installed_version, version),
+        _ => {
+            info!("Replacing {} of unknown version", installed.name);
+            return Ok(());
+        }
+    };
+
+    match compare_versions(version, installed_version) {
+        Ordering::Greater => info!(
+            "Upgrading {} from {} to {}",
+            installed.name, installed_version, version
+        ),
+        Ordering::Equal => info!("Reinstalling {} {}", installed.name, version),
+        Ordering::Less if existing == ExistingAppPolicy::Downgrade => {
+            info!(
+                "Downgrading {} from {} to {}",
+                installed.name, installed_version, version
+            )
+        }
+        Ordering::Less => {
+            return Err(format!(
+                "{} {} is installed, refusing to downgrade it to {}",
+                installed.name, installed_version, version
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Remove the files of the previous version of an app which are not part of the new one
+fn remove_replaced_files(previous: &InstallRecord, current: &InstallRecord) {
+    let kept = current
+        .versions()
+        .iter()
+        .any(|version| version.app_image_path == previous.app_image_path);
+    if !kept && previous.app_image_path.exists() {
+        if let Err(err) = fs::remove_file(&previous.app_image_path) {
+            error!("Failed to remove the previous AppImage: {}", err);
+        }
+    }
+
+    for desktop_entry in &previous.desktop_entries {
+        if !current.desktop_entries.contains(desktop_entry) && desktop_entry.exists() {
+            if let Err(err) = fs::remove_file(desktop_entry) {
+                error!("Failed to remove the previous desktop entry: {}", err);
+            }
+        }
+    }
+
+    match find_icon_theme_location(previous.scope) {
+        Ok(theme_dir) => {
+            for icon_path in &previous.icon_paths {
+                if !current.icon_paths.contains(icon_path) {
+                    if let Err(err) = remove_icon(icon_path, &theme_dir) {
+                        error!("Failed to remove the previous icon: {}", err);
+                    }
+                }
+            }
+        }
+        Err(err) => error!("Failed to remove the previous icons: {}", err),
+    }
+
+    if let (Some(metainfo_path), Some(installation_dir)) =
+        (&previous.metainfo_path, previous.app_image_path.parent())
+    {
+        if current.metainfo_path.as_ref() != Some(metainfo_path) {
+            if let Err(err) = remove_metainfo(metainfo_path, installation_dir) {
+                error!("Failed to remove the previous metainfo file: {}", err);
+            }
+        }
+    }
+
+    let mime_packages: Vec<PathBuf> = previous
+        .mime_packages
+        .iter()
+        .filter(|mime_package| !current.mime_packages.contains(mime_package))
+        .cloned()
+        .collect();
+    if !mime_packages.is_empty() {
+        if let Err(err) = remove_mime_packages(&mime_packages, previous.scope) {
+            error!("Failed to remove the previous MIME packages: {}", err);
+        }
+    }
+}
@@ -1,44 +1,91 @@
-use common_utils::app_images_helpers::remove_icon;
-use common_utils::desktop_file_helpers::{delete_desktop_file_by_name, find_desktop_entry};
-use common_utils::file_system_helpers::rm_file;
-use log::{error, info};
-use std::path::PathBuf;
-
-pub fn uninstall_app_image(app_name: String) -> Result<(), String> {
-    info!("Uninstalling AppImage with app name: {}", app_name);
-
-    let desktop_entry = match find_desktop_entry(app_name.clone()) {
-        Ok(path) => path,
-        Err(err) => {
-            return Err(err);
+use common_utils::app_images_helpers::{remove_icons, remove_metainfo, remove_mime_packages};
+use common_utils::desktop_file_helpers::find_desktop_file_location;
+use common_utils::install_database::InstallDatabase;
+use common_utils::mime_helpers::update_desktop_database;
+use common_utils::models::install_scope::InstallScope;
+use common_utils::portable_helpers::{existing_portable_dirs, remove_portable_dirs};
+use log::{error, info, warn};
+use std::fs;
+
+pub fn uninstall_app_image(
+    app_id: String,
+    scope: InstallScope,
+    remove_portable_data: bool,
+) -> Result<(), String> {
+    info!("Uninstalling AppImage with app id: {} ({})", app_id, scope);
+
+    let mut database = InstallDatabase::open(scope)?;
+    let record = match database.get(&app_id) {
+        Some(record) => record.clone(),
+        None => {
+            return Err(format!("App not found: {}", app_id));
         }
     };
 
-    let exec = &desktop_entry
-        .exec
-        .replace("--no-sandbox", "")
-        .trim()
-        .to_string();
-
-    // Remove the AppImage
-    if let Err(err) = rm_file(exec) {
-        error!(
-            "Failed to remove app image file at {}: {}",
-            desktop_entry.exec, err
-        );
-        return Err("Failed to remove AppImage".into());
+    // Remove the desktop entries first: when this fails the app is left complete and recorded,
+    // and its uninstallation can be run again
+    for desktop_entry in &record.desktop_entries {
+        if desktop_entry.exists() {
+            if let Err(err) = fs::remove_file(desktop_entry) {
+                error!("Failed to remove desktop entry: {}", err);
+                return Err("Failed to remove desktop entry".into());
+            }
+        }
     }
 
-    // Remove the desktop entry
-    if let Err(err) = delete_desktop_file_by_name(&app_name) {
-        error!("Failed to remove desktop entry: {}", err);
-        return Err("Failed to remove desktop entry".into());
+    // Drop the file associations of the removed entries
+    if let Ok(applications_dir) = find_desktop_file_location(scope) {
+        update_desktop_database(&applications_dir);
+    }
+
+    // Remove the AppImages of every installed version
+    for version in record.versions() {
+        if version.app_image_path.exists() {
+            if let Err(err) = fs::remove_file(&version.app_image_path) {
+                error!(
+                    "Failed to remove app image file at {:?}: {}",
+                    version.app_image_path, err
+                );
+                return Err("Failed to remove AppImage".into());
+            }
+        } else {
+            warn!("AppImage does not exist: {:?}", version.app_image_path);
+        }
+
+        // The portable home and config directories hold the data of the app
+        if remove_portable_data {
+            if let Err(err) = remove_portable_dirs(&version.app_image_path) {
+                error!("Failed to remove portable directories: {}", err);
+            }
+        } else {
+            for dir in existing_portable_dirs(&version.app_image_path) {
+                info!("Keeping portable directory {:?}", dir);
+            }
+        }
     }
 
-    // Remove icons
-    if let Err(err) = remove_icon(&PathBuf::from(&desktop_entry.icon)) {
+    // Remove the icons installed in the icon theme
+    if let Err(err) = remove_icons(&record.icon_paths, scope) {
         error!("Failed to remove icons: {}", err);
     }
 
+    // Remove the AppStream metainfo file
+    if let (Some(metainfo), Some(installation_dir)) =
+        (&record.metainfo_path, record.app_image_path.parent())
+    {
+        if let Err(err) = remove_metainfo(metainfo, installation_dir) {
+            error!("Failed to remove metainfo file: {}", err);
+        }
+    }
+
+    // Remove the MIME types registered by the app
+    if let Err(err) = remove_mime_packages(&record.mime_packages, scope) {
+        error!("Failed to remove MIME packages: {}", err);
+    }
+
+    // Forget the app
+    database.remove(&app_id);
+    database.save(&InstallDatabase::path(scope)?)?;
+
     Ok(())
 }
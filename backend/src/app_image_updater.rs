@@ -1,16 +1,19 @@
 use common_utils::desktop_file_builder::DesktopFileBuilder;
-use common_utils::desktop_file_helpers::find_desktop_entries_by_exec_contains;
 use common_utils::file_system_helpers::copy_dir_all;
-use log::{error, info, warn};
-use std::fs::{remove_dir_all, File};
-use std::io::Write;
-use std::path::PathBuf;
+use common_utils::install_database::InstallDatabase;
+use common_utils::models::exec_command::ExecCommand;
+use common_utils::models::install_scope::InstallScope;
+use common_utils::portable_helpers::move_portable_dirs;
+use log::{error, info};
+use std::fs::{self, remove_dir_all};
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
-/// Update the image of an app
+/// Update the image of an app, moving the AppImages of the given scope to a new directory
 pub fn app_image_update(
     old_install_dir: String,
     new_install_dir: String,
+    scope: InstallScope,
 ) -> Result<(), String> {
     if old_install_dir.eq(&new_install_dir) {
         return Err("Old and new install directories are the same".into());
@@ -21,6 +24,28 @@ pub fn app_image_update(
         old_install_dir, new_install_dir
     );
 
+    let old_dir = Path::new(&old_install_dir);
+    let new_dir = Path::new(&new_install_dir);
+    let mut database = match InstallDatabase::open(scope) {
+        Ok(database) => database,
+        Err(e) => return Err(format!("Failed to open install database: {}", e)),
+    };
+
+    // the portable directories are moved first, copying them would lose their links and permissions
+    if let Err(e) = fs::create_dir_all(new_dir) {
+        return Err(format!("Failed to create new install directory: {}", e));
+    }
+    for record in database
+        .apps
+        .iter()
+        .filter(|record| record.app_image_path.starts_with(old_dir))
+    {
+        for version in record.versions() {
+            let new_path = relocate_path(&version.app_image_path, old_dir, new_dir);
+            move_portable_dirs(&version.app_image_path, &new_path)?;
+        }
+    }
+
     if let Err(e) = copy_dir_all(&old_install_dir, &new_install_dir) {
         return Err(format!("Failed to copy app images: {}", e));
     }
@@ -33,15 +58,28 @@ pub fn app_image_update(
 
     info!("Old install directory deleted successfully");
 
-    let desktop_entries = match find_desktop_entries_by_exec_contains(&old_install_dir) {
-        Ok(entries) => entries,
-        Err(e) => return Err(format!("Failed to find desktop entries: {}", e)),
-    };
+    // the apps whose AppImage was in the old directory
+    let mut desktop_entries: Vec<PathBuf> = Vec::new();
+    for record in database
+        .apps
+        .iter_mut()
+        .filter(|record| record.app_image_path.starts_with(old_dir))
+    {
+        desktop_entries.extend(record.desktop_entries.iter().cloned());
+        record.app_image_path = relocate_path(&record.app_image_path, old_dir, new_dir);
+        for version in record.other_versions.iter_mut() {
+            version.app_image_path = relocate_path(&version.app_image_path, old_dir, new_dir);
+        }
+        record.metainfo_path = record
+            .metainfo_path
+            .as_ref()
+            .map(|metainfo_path| relocate_path(metainfo_path, old_dir, new_dir));
+    }
 
+    let mut failed_entries = Vec::new();
     for desktop_entry in desktop_entries {
         let mut desktop_file_builder =
-            match DesktopFileBuilder::from_desktop_entry_path(&PathBuf::from(&desktop_entry), true)
-            {
+            match DesktopFileBuilder::from_desktop_entry_path(&desktop_entry, true) {
                 Ok(builder) => builder,
                 Err(e) => {
                     info!("Failed to parse desktop entry: {}", e);
@@ -49,28 +87,26 @@ pub fn app_image_update(
                 }
             };
 
-        let new_icon = desktop_file_builder
-            .icon()
-            .unwrap()
-            .replace(&old_install_dir, &new_install_dir);
-
-        desktop_file_builder.set_icon(new_icon);
+        // an entry without icon keeps none
+        if let Some(icon) = desktop_file_builder.icon() {
+            desktop_file_builder.set_icon(icon.replace(&old_install_dir, &new_install_dir));
+        }
 
         // Check exec field
-        if desktop_file_builder.exec().is_none() {
-            info!(
-                "Failed to parse desktop entry (exec missing): {}",
-                &desktop_entry
-            );
-            continue;
-        }
+        let mut command = match desktop_file_builder.exec_command() {
+            Some(command) => command,
+            None => {
+                info!(
+                    "Failed to parse desktop entry (exec missing or invalid): {:?}",
+                    &desktop_entry
+                );
+                continue;
+            }
+        };
 
-        let new_exec = desktop_file_builder
-            .exec()
-            .unwrap()
-            .replace(&old_install_dir, &new_install_dir);
-        info!("New exec: {}", new_exec);
-        desktop_file_builder.set_exec(new_exec);
+        command.relocate(old_dir, new_dir);
+        info!("New exec: {}", command);
+        desktop_file_builder.set_exec(command.to_string());
 
         // Update path fields
         if desktop_file_builder.path().is_some() {
@@ -81,26 +117,40 @@ pub fn app_image_update(
                     .replace(old_install_dir.as_str(), new_install_dir.as_str()),
             );
         }
-        match desktop_file_builder.generate_content_string() {
-            Ok(content) => {
-                let mut file = File::create(&desktop_entry);
-                match file {
-                    Ok(mut file) => {
-                        file.write_all(content.as_bytes())
-                            .expect("Error updating .desktop file");
-                        if let Err(error) = file.write_all(content.as_bytes()) {
-                            error!("Error writing .desktop file: {}", error);
-                        }
-                    }
-                    Err(error) => {
-                        error!("Error opening .desktop file: {}", error);
-                    }
+        for action in desktop_file_builder.actions() {
+            let action_command = action.exec.and_then(|exec| ExecCommand::parse(&exec).ok());
+            if let Some(mut action_command) = action_command {
+                if action_command.relocate(old_dir, new_dir) {
+                    desktop_file_builder.set_action_exec(&action.id, action_command.to_string());
                 }
             }
-            Err(error) => {
-                warn!("Error updating .desktop file: {}", error);
-            }
         }
+        if let Some(metainfo) = desktop_file_builder.metainfo() {
+            desktop_file_builder
+                .set_metainfo(metainfo.replace(old_install_dir.as_str(), new_install_dir.as_str()));
+        }
+        let written = desktop_file_builder
+            .generate_content_string()
+            .map_err(|error| error.to_string())
+            .and_then(|content| {
+                fs::write(&desktop_entry, content).map_err(|error| error.to_string())
+            });
+        if let Err(error) = written {
+            error!("Error writing .desktop file {:?}: {}", desktop_entry, error);
+            failed_entries.push(desktop_entry);
+        }
+    }
+
+    if let Err(e) = database.save(&InstallDatabase::path(scope)?) {
+        return Err(format!("Failed to update install database: {}", e));
+    }
+
+    // the AppImages are moved and recorded, the entries left pointing to the old directory are reported
+    if !failed_entries.is_empty() {
+        return Err(format!(
+            "Failed to update desktop entries: {:?}",
+            failed_entries
+        ));
     }
 
     // Add executable permissions to all app images
@@ -120,3 +170,11 @@ pub fn app_image_update(
 
     Ok(())
 }
+
+/// Move a path of the old directory to the new one
+fn relocate_path(path: &Path, old_dir: &Path, new_dir: &Path) -> PathBuf {
+    match path.strip_prefix(old_dir) {
+        Ok(relative) => new_dir.join(relative),
+        Err(_) => path.to_path_buf(),
+    }
+}
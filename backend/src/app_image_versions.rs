@@ -0,0 +1,122 @@
+use common_utils::desktop_file_builder::DesktopFileBuilder;
+use common_utils::install_database::InstallDatabase;
+use common_utils::models::exec_command::ExecCommand;
+use common_utils::models::install_scope::InstallScope;
+use common_utils::portable_helpers::{existing_portable_dirs, remove_portable_dirs};
+use log::{error, info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Make another version installed side by side the one the desktop entry of the app launches
+pub fn activate_app_version(
+    app_id: String,
+    version: String,
+    scope: InstallScope,
+) -> Result<(), String> {
+    info!(
+        "Activating version {} of app {} ({})",
+        version, app_id, scope
+    );
+
+    let mut database = InstallDatabase::open(scope)?;
+    let record = match database.apps.iter_mut().find(|record| record.id == app_id) {
+        Some(record) => record,
+        None => {
+            return Err(format!("App not found: {}", app_id));
+        }
+    };
+    let previous_path = record.app_image_path.clone();
+    record.activate_version(&version)?;
+
+    // point the desktop entries to the AppImage of the activated version
+    let signature = record
+        .signature
+        .as_ref()
+        .map(|signature| signature.to_string());
+    for desktop_entry in &record.desktop_entries {
+        retarget_desktop_entry(
+            desktop_entry,
+            &previous_path,
+            &record.app_image_path,
+            signature.clone(),
+        )?;
+    }
+
+    database.save(&InstallDatabase::path(scope)?)
+}
+
+/// Remove a version installed side by side, which is not the active one
+pub fn remove_app_version(
+    app_id: String,
+    version: String,
+    scope: InstallScope,
+    remove_portable_data: bool,
+) -> Result<(), String> {
+    info!("Removing version {} of app {} ({})", version, app_id, scope);
+
+    let mut database = InstallDatabase::open(scope)?;
+    let record = match database.apps.iter_mut().find(|record| record.id == app_id) {
+        Some(record) => record,
+        None => {
+            return Err(format!("App not found: {}", app_id));
+        }
+    };
+    let removed = record.remove_version(&version)?;
+
+    if removed.app_image_path.exists() {
+        fs::remove_file(&removed.app_image_path)
+            .map_err(|e| format!("Failed to remove AppImage: {}", e))?;
+    } else {
+        warn!("AppImage does not exist: {:?}", removed.app_image_path);
+    }
+
+    // the portable directories of the removed version are handled as on uninstallation
+    if remove_portable_data {
+        if let Err(err) = remove_portable_dirs(&removed.app_image_path) {
+            error!("Failed to remove portable directories: {}", err);
+        }
+    } else {
+        for dir in existing_portable_dirs(&removed.app_image_path) {
+            info!("Keeping portable directory {:?}", dir);
+        }
+    }
+
+    database.save(&InstallDatabase::path(scope)?)
+}
+
+/// Replace the AppImage launched by a desktop entry and its actions, keeping their arguments
+fn retarget_desktop_entry(
+    desktop_entry: &PathBuf,
+    from: &Path,
+    to: &Path,
+    signature: Option<String>,
+) -> Result<(), String> {
+    let mut desktop_file_builder =
+        DesktopFileBuilder::from_desktop_entry_path(desktop_entry, true)?;
+    let program = to.to_string_lossy().to_string();
+    let retarget = |command: ExecCommand| {
+        if Path::new(&command.program) == from {
+            command.with_program(program.clone())
+        } else {
+            command
+        }
+    };
+
+    let command = desktop_file_builder.exec_command().ok_or(format!(
+        "Invalid Exec value in desktop file: {:?}",
+        desktop_entry
+    ))?;
+    desktop_file_builder.set_exec(retarget(command).to_string());
+    for action in desktop_file_builder.actions() {
+        let action_command = action.exec.and_then(|exec| ExecCommand::parse(&exec).ok());
+        if let Some(action_command) = action_command {
+            desktop_file_builder.set_action_exec(&action.id, retarget(action_command).to_string());
+        }
+    }
+    if let Some(signature) = signature {
+        desktop_file_builder.set_signature(signature);
+    }
+
+    let content = desktop_file_builder.generate_content_string()?;
+    fs::write(desktop_entry, content).map_err(|e| format!("Failed to write desktop entry: {}", e))
+}
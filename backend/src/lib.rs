@@ -1,3 +1,5 @@
 pub mod app_image_updater;
 pub mod app_image_installer;
-pub mod app_image_uninstaller;
\ No newline at end of file
+pub mod app_image_uninstaller;
+pub mod app_image_info;
+pub mod app_image_versions;
@@ -0,0 +1,124 @@
+/// Prefix of the names of the files installed in shared directories,
+/// so files of AppHub never collide with the ones of other apps
+const INSTALLED_NAME_PREFIX: &str = "apphub-";
+
+/// Number of hex digits of the content hash used when the AppImage carries no id
+const HASH_ID_LENGTH: usize = 16;
+
+/// The stable identifier of an app, from the first usable of:
+/// the desktop file id embedded in the AppImage, its AppStream id, or the hash of its content
+pub fn app_id(desktop_file_id: Option<&str>, appstream_id: Option<&str>, sha256: &str) -> String {
+    desktop_file_id
+        .and_then(sanitize_id)
+        .or_else(|| appstream_id.and_then(sanitize_id))
+        .unwrap_or_else(|| {
+            let length = HASH_ID_LENGTH.min(sha256.len());
+            format!("sha256-{}", &sha256[..length])
+        })
+}
+
+/// How a version of an app is named: its version, or the start of its content hash when it has none
+pub fn version_label(version: Option<&str>, sha256: &str) -> String {
+    version.and_then(sanitize_id).unwrap_or_else(|| {
+        let length = HASH_ID_LENGTH.min(sha256.len());
+        sha256[..length].to_string()
+    })
+}
+
+/// The file name of an AppImage installed next to other versions of the same app
+pub fn versioned_app_image_name(app_id: &str, version: Option<&str>, sha256: &str) -> String {
+    format!("{}-{}.AppImage", app_id, version_label(version, sha256))
+}
+
+/// The identifier of an app installed before the ids existed: a lowercase slug of its name
+pub fn legacy_app_id(app_name: &str) -> String {
+    let mut slug = String::new();
+    for c in app_name.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// The name of the files installed for an app in shared directories:
+/// the icons in the icon theme, the MIME packages and the desktop entry
+pub fn installed_name(app_id: &str) -> String {
+    format!("{}{}", INSTALLED_NAME_PREFIX, app_id)
+}
+
+/// The file name of the desktop entry of an app
+pub fn desktop_file_name(app_id: &str) -> String {
+    format!("{}.desktop", installed_name(app_id))
+}
+
+/// Keep the characters allowed in desktop file ids, without the `.desktop` extension.
+/// Returns None when nothing usable is left
+fn sanitize_id(raw: &str) -> Option<String> {
+    let raw = raw.trim();
+    let raw = raw.strip_suffix(".desktop").unwrap_or(raw);
+    let mut id = String::new();
+    for c in raw.chars() {
+        if c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-' {
+            id.push(c);
+        } else if !id.is_empty() && !id.ends_with('-') {
+            id.push('-');
+        }
+    }
+    let id = id.trim_matches(|c| c == '-' || c == '.');
+    if id.is_empty() {
+        None
+    } else {
+        Some(id.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA256: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    #[test]
+    fn test_app_id() {
+        assert_eq!(
+            app_id(Some("org.kde.krita.desktop"), Some("org.kde.krita"), SHA256),
+            "org.kde.krita"
+        );
+        assert_eq!(app_id(Some("My App!.desktop"), None, SHA256), "My-App");
+        assert_eq!(
+            app_id(Some("../.desktop"), Some("com.example.App.desktop"), SHA256),
+            "com.example.App"
+        );
+        assert_eq!(app_id(None, Some("   "), SHA256), "sha256-0123456789abcdef");
+    }
+
+    #[test]
+    fn test_versioned_app_image_name() {
+        assert_eq!(
+            versioned_app_image_name("org.kde.krita", Some("5.2.3"), SHA256),
+            "org.kde.krita-5.2.3.AppImage"
+        );
+        assert_eq!(
+            versioned_app_image_name("org.kde.krita", Some("5.2 beta/1"), SHA256),
+            "org.kde.krita-5.2-beta-1.AppImage"
+        );
+        assert_eq!(
+            versioned_app_image_name("org.kde.krita", None, SHA256),
+            "org.kde.krita-0123456789abcdef.AppImage"
+        );
+    }
+
+    #[test]
+    fn test_installed_names() {
+        assert_eq!(legacy_app_id("My Cool App 2!"), "my-cool-app-2");
+        assert_eq!(legacy_app_id("--Ünïcode--"), "n-code");
+        assert_eq!(installed_name("org.kde.krita"), "apphub-org.kde.krita");
+        assert_eq!(
+            desktop_file_name("org.kde.krita"),
+            "apphub-org.kde.krita.desktop"
+        );
+    }
+}
@@ -0,0 +1,377 @@
+use std::fs::{self, File};
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use log::{debug, info};
+
+use crate::app_images_helpers::METAINFO_DIRS;
+use crate::desktop_file::DesktopFile;
+use crate::elf::{read_section, ElfInfo};
+use crate::image_fs::{EntryKind, ImageEntry, ImageFs};
+use crate::iso9660::Iso9660;
+use crate::models::app_stream_metadata::AppStreamMetadata;
+use crate::models::update_info::UpdateInfo;
+use crate::squashfs::SquashFs;
+
+/// Offset of the AppImage magic bytes inside the ELF identification
+const APP_IMAGE_MAGIC_OFFSET: u64 = 8;
+
+/// ELF section holding the update information of type-2 AppImages
+const UPDATE_INFO_SECTION: &str = ".upd_info";
+
+/// Location of the update information of type-1 AppImages
+/// (the "application used" field of the ISO 9660 primary volume descriptor)
+const TYPE1_UPDATE_INFO_OFFSET: u64 = 33651;
+const TYPE1_UPDATE_INFO_SIZE: usize = 512;
+
+/// Directories of the AppImage payload holding the metadata AppHub needs at install time
+const METADATA_DIRS: [&str; 6] = [
+    "usr/share/applications",
+    "usr/share/icons",
+    "usr/share/pixmaps",
+    "usr/share/metainfo",
+    "usr/share/appdata",
+    "usr/share/mime/packages",
+];
+
+/// Extensions of the metadata files found in the root of the AppImage payload
+const ROOT_METADATA_EXTENSIONS: [&str; 6] = ["desktop", "png", "svg", "xpm", "jpg", "jpeg"];
+
+/// The AppImage image format, see https://github.com/AppImage/AppImageSpec
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppImageType {
+    /// An ISO 9660 image with Rock Ridge extensions which is also an ELF executable
+    Type1,
+    /// An ELF runtime followed by a squashfs image
+    Type2,
+}
+
+impl AppImageType {
+    /// Detect the AppImage type from the magic bytes at offset 8 (`AI\x01` or `AI\x02`)
+    pub fn detect<R: Read + Seek>(reader: &mut R) -> Result<Self, String> {
+        let mut magic = [0u8; 3];
+        reader
+            .seek(SeekFrom::Start(APP_IMAGE_MAGIC_OFFSET))
+            .and_then(|_| reader.read_exact(&mut magic))
+            .map_err(|e| format!("Failed to read AppImage magic bytes: {}", e))?;
+        match &magic {
+            b"AI\x01" => Ok(Self::Type1),
+            b"AI\x02" => Ok(Self::Type2),
+            _ => Err("Not an AppImage: magic bytes not found".to_string()),
+        }
+    }
+}
+
+/// Reads the content of an AppImage in-process, without ever executing it
+pub struct AppImageReader {
+    path: PathBuf,
+    app_image_type: AppImageType,
+    elf: ElfInfo,
+    payload: Box<dyn ImageFs>,
+}
+
+impl AppImageReader {
+    /// Open an AppImage, parse its ELF runtime and index the embedded file system
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let mut file = File::open(path).map_err(|e| format!("Failed to open AppImage: {}", e))?;
+        let app_image_type = AppImageType::detect(&mut file)?;
+        let elf = ElfInfo::read(&mut file)?;
+        debug!("Detected AppImage {:?}", app_image_type);
+
+        let payload: Box<dyn ImageFs> = match app_image_type {
+            AppImageType::Type1 => Box::new(Iso9660::open(BufReader::new(file))?),
+            AppImageType::Type2 => {
+                debug!("AppImage payload starts at offset {}", elf.end_offset);
+                Box::new(SquashFs::open(BufReader::new(file), elf.end_offset)?)
+            }
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            app_image_type,
+            elf,
+            payload,
+        })
+    }
+
+    pub fn app_image_type(&self) -> AppImageType {
+        self.app_image_type
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn elf(&self) -> &ElfInfo {
+        &self.elf
+    }
+
+    /// Offset of the file system payload inside the AppImage file
+    pub fn payload_offset(&self) -> u64 {
+        match self.app_image_type {
+            AppImageType::Type1 => 0,
+            AppImageType::Type2 => self.elf.end_offset,
+        }
+    }
+
+    /// Read the content of an ELF section of the AppImage runtime, if present
+    pub fn read_section(&self, name: &str) -> Result<Option<Vec<u8>>, String> {
+        let section = match self.elf.section(name) {
+            Some(section) => section,
+            None => return Ok(None),
+        };
+        let mut file =
+            File::open(&self.path).map_err(|e| format!("Failed to open AppImage: {}", e))?;
+        read_section(&mut file, section).map(Some)
+    }
+
+    /// Read the update information embedded in the AppImage
+    pub fn update_info(&self) -> Result<Option<UpdateInfo>, String> {
+        let raw = match self.app_image_type {
+            AppImageType::Type1 => {
+                let mut raw = vec![0u8; TYPE1_UPDATE_INFO_SIZE];
+                File::open(&self.path)
+                    .and_then(|mut file| {
+                        file.seek(SeekFrom::Start(TYPE1_UPDATE_INFO_OFFSET))?;
+                        file.read_exact(&mut raw)
+                    })
+                    .map_err(|e| format!("Failed to read update information: {}", e))?;
+                raw
+            }
+            AppImageType::Type2 => match self.read_section(UPDATE_INFO_SECTION)? {
+                Some(raw) => raw,
+                None => return Ok(None),
+            },
+        };
+
+        let update_info = UpdateInfo::parse(&String::from_utf8_lossy(&raw));
+        debug!("AppImage update information: {:?}", update_info);
+        Ok(update_info)
+    }
+
+    /// List all the entries of the AppImage payload
+    pub fn list_files(&self) -> &[ImageEntry] {
+        self.payload.entries()
+    }
+
+    /// Read a file of the payload, following symlinks inside the image
+    pub fn read_file(&mut self, path: &str) -> Result<Vec<u8>, String> {
+        self.payload.read_file(path)
+    }
+
+    /// Paths of the .desktop files placed in the root of the payload
+    pub fn desktop_file_paths(&self) -> Vec<String> {
+        self.list_files()
+            .iter()
+            .filter(|entry| !entry.path.contains('/') && entry.path.ends_with(".desktop"))
+            .map(|entry| entry.path.clone())
+            .collect()
+    }
+
+    /// Read and parse the desktop entry placed in the root of the payload
+    pub fn desktop_file(&mut self) -> Result<DesktopFile, String> {
+        let path = self
+            .desktop_file_paths()
+            .into_iter()
+            .next()
+            .ok_or("No desktop file found")?;
+        let content = self.read_file(&path)?;
+        Ok(DesktopFile::parse(&String::from_utf8_lossy(&content)))
+    }
+
+    /// Read and parse the AppStream metainfo file of the payload, None when it has none
+    pub fn metainfo(&mut self) -> Option<Result<AppStreamMetadata, String>> {
+        let path = METAINFO_DIRS.iter().find_map(|metainfo_dir| {
+            let mut paths: Vec<String> = self
+                .list_files()
+                .iter()
+                .filter(|entry| matches!(entry.kind, EntryKind::File | EntryKind::Symlink))
+                .filter(|entry| {
+                    entry
+                        .path
+                        .strip_prefix(metainfo_dir)
+                        .and_then(|name| name.strip_prefix('/'))
+                        .is_some_and(|name| !name.contains('/') && name.ends_with(".xml"))
+                })
+                .map(|entry| entry.path.clone())
+                .collect();
+            paths.sort();
+            paths.into_iter().next()
+        })?;
+        Some(
+            self.read_file(&path)
+                .and_then(|content| AppStreamMetadata::parse(&String::from_utf8_lossy(&content))),
+        )
+    }
+
+    /// Extract the metadata files (desktop entries, .DirIcon, icons, AppStream files and MIME packages)
+    /// into the given directory, keeping their relative paths.
+    /// Symlinks are resolved inside the image and written as regular files.
+    pub fn extract_metadata(&mut self, destination: &Path) -> Result<(), String> {
+        let paths: Vec<String> = self
+            .list_files()
+            .iter()
+            .filter(|entry| matches!(entry.kind, EntryKind::File | EntryKind::Symlink))
+            .filter(|entry| is_metadata_path(&entry.path))
+            .map(|entry| entry.path.clone())
+            .collect();
+
+        for path in paths {
+            let content = match self.payload.read_file(&path) {
+                Ok(content) => content,
+                Err(e) => {
+                    debug!("Skipping {}: {}", path, e);
+                    continue;
+                }
+            };
+
+            let target = destination.join(&path);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)
+                    .map_err(|e| format!("Failed to create directory {:?}: {}", parent, e))?;
+            }
+            fs::write(&target, content)
+                .map_err(|e| format!("Failed to write {:?}: {}", target, e))?;
+        }
+
+        info!("Extracted AppImage metadata to {:?}", destination);
+        Ok(())
+    }
+}
+
+fn is_metadata_path(path: &str) -> bool {
+    match path.rsplit_once('/') {
+        None => {
+            path == ".DirIcon"
+                || Path::new(path)
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| {
+                        ROOT_METADATA_EXTENSIONS.contains(&ext.to_lowercase().as_str())
+                    })
+        }
+        Some(_) => METADATA_DIRS
+            .iter()
+            .any(|dir| path.starts_with(dir) && path[dir.len()..].starts_with('/')),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::desktop_file::DESKTOP_ENTRY_GROUP;
+    use crate::test_support::{build_app_image, build_iso9660, TestNode};
+
+    #[test]
+    fn test_extract_metadata_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_image_path = dir.path().join("Test.AppImage");
+        fs::write(
+            &app_image_path,
+            build_app_image(
+                &[],
+                &[
+                    (
+                        "test.desktop",
+                        TestNode::Symlink("usr/share/applications/test.desktop"),
+                    ),
+                    (
+                        "usr/share/applications/test.desktop",
+                        TestNode::File(b"[Desktop Entry]\n"),
+                    ),
+                    (".DirIcon", TestNode::Symlink("test.png")),
+                    ("test.png", TestNode::File(b"png")),
+                    ("AppRun", TestNode::File(b"#!/bin/sh\n")),
+                    ("usr/bin/test", TestNode::File(b"binary")),
+                    (
+                        "usr/share/mime/packages/test.xml",
+                        TestNode::File(b"<mime-info/>"),
+                    ),
+                ],
+            ),
+        )
+        .unwrap();
+
+        let mut reader = AppImageReader::open(&app_image_path).unwrap();
+        assert_eq!(
+            reader.desktop_file_paths(),
+            vec!["test.desktop".to_string()]
+        );
+        assert!(reader
+            .desktop_file()
+            .unwrap()
+            .has_group(DESKTOP_ENTRY_GROUP));
+
+        let destination = dir.path().join("metadata");
+        reader.extract_metadata(&destination).unwrap();
+
+        assert_eq!(
+            fs::read(destination.join("test.desktop")).unwrap(),
+            b"[Desktop Entry]\n"
+        );
+        assert_eq!(fs::read(destination.join(".DirIcon")).unwrap(), b"png");
+        assert!(destination
+            .join("usr/share/applications/test.desktop")
+            .is_file());
+        assert!(destination
+            .join("usr/share/mime/packages/test.xml")
+            .is_file());
+        assert!(!destination.join("AppRun").exists());
+        assert!(!destination.join("usr/bin/test").exists());
+    }
+
+    #[test]
+    fn test_update_info_from_section() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_image_path = dir.path().join("Test.AppImage");
+        let mut section = b"gh-releases-zsync|user|repo|latest|Test-*.zsync".to_vec();
+        section.resize(1024, 0);
+        fs::write(
+            &app_image_path,
+            build_app_image(&[(".upd_info", &section)], &[("test.desktop", TestNode::File(b""))]),
+        )
+        .unwrap();
+
+        let reader = AppImageReader::open(&app_image_path).unwrap();
+        assert_eq!(
+            reader.update_info().unwrap(),
+            UpdateInfo::parse("gh-releases-zsync|user|repo|latest|Test-*.zsync")
+        );
+    }
+
+    #[test]
+    fn test_detect_type_and_read_type1() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_image_path = dir.path().join("Old.AppImage");
+        fs::write(
+            &app_image_path,
+            build_iso9660(
+                &[],
+                &[
+                    (
+                        "old.desktop",
+                        TestNode::File(b"[Desktop Entry]\nName=Old\n"),
+                    ),
+                    (".DirIcon", TestNode::Symlink("old.png")),
+                    ("old.png", TestNode::Compressed(b"png")),
+                ],
+            ),
+        )
+        .unwrap();
+
+        let mut reader = AppImageReader::open(&app_image_path).unwrap();
+        assert_eq!(reader.app_image_type(), AppImageType::Type1);
+        assert_eq!(reader.payload_offset(), 0);
+        assert_eq!(reader.desktop_file_paths(), vec!["old.desktop".to_string()]);
+
+        let destination = dir.path().join("metadata");
+        reader.extract_metadata(&destination).unwrap();
+        assert_eq!(fs::read(destination.join(".DirIcon")).unwrap(), b"png");
+    }
+
+    #[test]
+    fn test_rejects_files_without_magic() {
+        let mut elf = crate::test_support::build_elf(*b"\0\0\0", &[]);
+        assert!(AppImageType::detect(&mut std::io::Cursor::new(&mut elf)).is_err());
+    }
+}
@@ -1,13 +1,39 @@
 use fs_extra::dir;
 use std::fs;
-use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
+use tempfile::TempDir;
 
+use crate::app_id_helpers::app_id;
+use crate::app_image_reader::AppImageReader;
+use crate::desktop_file::DESKTOP_ENTRY_GROUP;
 use crate::desktop_file_builder::DesktopFileBuilder;
-use crate::desktop_file_helpers::find_desktop_file_location;
 use crate::file_system_helpers::add_executable_permission;
+use crate::icon_helpers::{
+    collect_theme_icons, find_icon_theme_location, resolve_icon, update_icon_cache,
+};
+use crate::install_database::InstallDatabase;
+use crate::install_transaction::InstallTransaction;
+use crate::mime_helpers::{
+    find_mime_location, find_mime_packages, installed_mime_package_path, update_mime_database,
+};
+use crate::models::app_identity::AppIdentity;
+use crate::models::app_stream_metadata::AppStreamMetadata;
+use crate::models::install_record::InstallRecord;
+use crate::models::install_scope::InstallScope;
 use crate::models::installed_app::InstalledApp;
-use log::{debug, error, info};
+use crate::models::localized_string::LocalizedString;
+use crate::models::update_info::UpdateInfo;
+use crate::portable_helpers::existing_portable_dirs;
+use crate::signature_helpers::app_image_digest;
+use crate::version_helpers::app_version;
+use log::{debug, error, info, warn};
+
+/// Prefix of the private temporary directories the AppImage metadata is extracted into
+const EXTRACTION_DIR_PREFIX: &str = "apphub-extract-";
+
+/// Directories of the AppImage holding AppStream metainfo files, in order of preference
+pub(crate) const METAINFO_DIRS: [&str; 2] = ["usr/share/metainfo", "usr/share/appdata"];
 
 /// Install an AppImage file using the given file path
 pub fn install_app_image_from_path(
@@ -39,9 +65,11 @@ pub fn install_app_image_from_path(
     Ok("Installation successful".to_string())
 }
 
-/// Extract the .desktop file from the AppImage
-/// Returns the path to the extracted .desktop file
-pub fn app_image_extract_squashroot(app_image_path: &PathBuf) -> Result<PathBuf, &'static str> {
+/// Extract the metadata files (.desktop file, icons, AppStream files) from the AppImage
+/// into a new temporary directory only the current user can access.
+/// The AppImage is read in-process and never executed, nothing is written next to it.
+/// The directory is removed when the returned guard is dropped, on errors and panics too
+pub fn app_image_extract_squashroot(app_image_path: &Path) -> Result<TempDir, &'static str> {
     info!("Starting extraction of .desktop file from AppImage...");
 
     if !app_image_path.exists() || !app_image_path.is_file() {
@@ -49,97 +77,77 @@ pub fn app_image_extract_squashroot(app_image_path: &PathBuf) -> Result<PathBuf,
         return Err("AppImage file does not exist or is not a file");
     }
 
-    let app_name = match app_image_path.file_name() {
-        None => return Err("Failed to get AppImage file name"),
-        Some(name) => name
-            .to_str()
-            .ok_or_else(|| "Failed to convert OsStr to str")?,
-    };
-
-    // Get parent directory of app_image_path
-    let parent_dir = match app_image_path.parent() {
-        None => {
-            error!("Failed to get parent directory of AppImage file");
-            return Err("Failed to get parent directory of AppImage file");
+    let mut reader = match AppImageReader::open(app_image_path) {
+        Ok(reader) => reader,
+        Err(err) => {
+            error!("Failed to read AppImage: {}", err);
+            return Err("Failed to read AppImage");
         }
-        Some(dir) => dir,
     };
-
-    let command = format!(
-        "cd {} && ./{} --appimage-extract",
-        parent_dir.to_str().unwrap(),
-        app_name
+    info!("Reading {:?} AppImage", reader.app_image_type());
+    debug!(
+        "AppImage payload found at offset {}",
+        reader.payload_offset()
     );
-    debug!("Running command: {}", command);
 
-    let output = std::process::Command::new("bash")
-        .arg("-c")
-        .arg(command)
-        .output()
-        .expect("Failed to execute command");
-
-    if output.status.success() {
-        info!("Successfully extracted .desktop file from AppImage.");
-    } else {
-        let err = String::from_utf8_lossy(&output.stderr);
+    // under a unique name, only accessible by the current user
+    let squashfs_root = match tempfile::Builder::new()
+        .prefix(EXTRACTION_DIR_PREFIX)
+        .permissions(fs::Permissions::from_mode(0o700))
+        .tempdir()
+    {
+        Ok(squashfs_root) => squashfs_root,
+        Err(err) => {
+            error!("Failed to create extraction directory: {}", err);
+            return Err("Failed to create extraction directory");
+        }
+    };
+    if let Err(err) = reader.extract_metadata(squashfs_root.path()) {
         error!("Failed to extract AppImage desktop file: {}", err);
         return Err("Failed to extract AppImage desktop file");
     }
-    let squashfs_root_path = parent_dir.join(format!("squashfs-root"));
-    Ok(squashfs_root_path)
-}
-
-/// Install the icons from the AppImage by moving them to the installation dir icons folder
-pub fn choose_icon(
-    squashfs_root_path: &PathBuf,
-    installation_path: &PathBuf,
-) -> Result<PathBuf, &'static str> {
-    // Find all icons file
-    let image_files = find_image_files(squashfs_root_path);
-    debug!("Found {} image files", image_files.len());
-    debug!("Image files: {:?}", image_files);
-    if image_files.len() == 0 {
-        return Err("No image files found");
-    }
 
-    // Choose the biggest image file
-    let biggest_image = image_files
-        .iter()
-        .max_by_key(|f| f.metadata().unwrap().len())
-        .unwrap();
-
-    debug!("Biggest image file: {:?}", biggest_image);
+    info!("Successfully extracted .desktop file from AppImage.");
+    Ok(squashfs_root)
+}
 
-    // Check if the icons directory exists or create it
-    let icons_dir = installation_path.join("icons");
-    match fs::create_dir(&icons_dir) {
-        Ok(_) => {
-            info!("Created icons directory: {:?}", icons_dir);
-        }
-        Err(ref e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
-            info!("Icons directory already exists");
-        }
+/// Install the icon of the AppImage in the hicolor icon theme, at every size it is available in.
+/// The icon is chosen by `resolve_icon`, following the `Icon=` key of the desktop entry,
+/// and installed under the given theme name.
+/// The icons are staged in the transaction, the icon cache is up to the caller once it is committed.
+/// Returns the paths of the installed icon files
+pub fn install_icons(
+    squashfs_root_path: &Path,
+    theme_dir: &Path,
+    icon_name: Option<String>,
+    theme_icon_name: &str,
+    transaction: &mut InstallTransaction,
+) -> Result<Vec<PathBuf>, &'static str> {
+    let icon_choice = match resolve_icon(squashfs_root_path, icon_name.as_deref()) {
+        Ok(icon_choice) => icon_choice,
         Err(e) => {
-            error!("Failed to create icons directory: {}", e);
-            return Err("Failed to create icons directory");
+            error!("{}", e);
+            return Err("No icon found");
         }
-    }
+    };
+    info!("Chosen icon {:?}: {}", icon_choice.path, icon_choice.reason);
 
-    // Copy the biggest image file into the installation path
-    let icon_path = installation_path
-        .join("icons")
-        .join(biggest_image.file_name().unwrap());
-    match fs::copy(biggest_image, &icon_path) {
-        Ok(_) => {
-            info!("Copied icon to: {:?}", icon_path);
-        }
-        Err(e) => {
-            error!("Failed to copy icon: {}", e);
-            return Err("Failed to copy icon");
+    let mut icon_paths = Vec::new();
+    for icon in collect_theme_icons(squashfs_root_path, &icon_choice, icon_name.as_deref()) {
+        let icon_path = icon.installed_path(theme_dir, theme_icon_name);
+        match transaction.stage_copy(&icon.path, &icon_path) {
+            Ok(_) => {
+                info!("Staged icon {:?} for: {:?}", icon.path, icon_path);
+            }
+            Err(e) => {
+                error!("Failed to copy icon: {}", e);
+                return Err("Failed to copy icon");
+            }
         }
+        icon_paths.push(icon_path);
     }
 
-    Ok(icon_path)
+    Ok(icon_paths)
 }
 
 fn recursive_copy(source: &Path, destination: &Path) -> Result<(), &'static str> {
@@ -157,7 +165,25 @@ fn recursive_copy(source: &Path, destination: &Path) -> Result<(), &'static str>
     Ok(())
 }
 
-pub fn remove_icon(icon_path: &PathBuf) -> Result<(), &'static str> {
+/// Remove the icon files installed for an app in the given scope
+pub fn remove_icons(icon_paths: &[PathBuf], scope: InstallScope) -> Result<(), &'static str> {
+    let theme_dir = find_icon_theme_location(scope)?;
+    for icon_path in icon_paths {
+        remove_icon(icon_path, &theme_dir)?;
+    }
+    update_icon_cache(&theme_dir);
+    Ok(())
+}
+
+/// Remove an icon file, which must be in the icon theme of the scope
+pub fn remove_icon(icon_path: &Path, theme_dir: &Path) -> Result<(), &'static str> {
+    if !is_inside(icon_path, theme_dir) {
+        error!(
+            "Refusing to remove {:?}, outside of {:?}",
+            icon_path, theme_dir
+        );
+        return Err("Icon is outside of the icon theme");
+    }
     if icon_path.exists() {
         match fs::remove_file(icon_path) {
             Ok(_) => {
@@ -175,60 +201,391 @@ pub fn remove_icon(icon_path: &PathBuf) -> Result<(), &'static str> {
     Ok(())
 }
 
-/// Find all the image files in the given directory
-fn find_image_files(dir: &PathBuf) -> Vec<PathBuf> {
-    let mut image_files = Vec::new();
-    let extensions = vec!["png", "jpg", "jpeg", "svg"];
+/// Install the shared-mime-info packages shipped in the AppImage into the MIME database,
+/// under the given prefix. The packages are staged in the transaction,
+/// the database is rebuilt by the caller once it is committed.
+/// Returns the paths of the installed package files
+pub fn install_mime_packages(
+    squashfs_root_path: &Path,
+    mime_dir: &Path,
+    prefix: &str,
+    transaction: &mut InstallTransaction,
+) -> Result<Vec<PathBuf>, &'static str> {
+    let mut package_paths = Vec::new();
+    for package in find_mime_packages(squashfs_root_path) {
+        let package_path = installed_mime_package_path(mime_dir, prefix, &package);
+        match transaction.stage_copy(&package, &package_path) {
+            Ok(_) => {
+                info!("Staged MIME package {:?} for: {:?}", package, package_path);
+            }
+            Err(e) => {
+                error!("Failed to copy MIME package: {}", e);
+                return Err("Failed to copy MIME package");
+            }
+        }
+        package_paths.push(package_path);
+    }
+
+    Ok(package_paths)
+}
 
-    for entry in WalkDir::new(dir) {
-        let entry = entry.unwrap();
-        if entry.file_type().is_file() {
-            if let Some(ext) = entry.path().extension() {
-                if extensions.contains(&ext.to_str().unwrap()) {
-                    image_files.push(entry.path().to_path_buf());
+/// Remove the shared-mime-info packages installed for an app and rebuild the MIME database
+pub fn remove_mime_packages(
+    package_paths: &[PathBuf],
+    scope: InstallScope,
+) -> Result<(), &'static str> {
+    if package_paths.is_empty() {
+        return Ok(());
+    }
+
+    let mime_dir = find_mime_location(scope)?;
+    for package_path in package_paths {
+        if !is_inside(package_path, &mime_dir) {
+            error!(
+                "Refusing to remove {:?}, outside of {:?}",
+                package_path, mime_dir
+            );
+            return Err("MIME package is outside of the MIME database");
+        }
+        if package_path.exists() {
+            match fs::remove_file(package_path) {
+                Ok(_) => {
+                    info!("Removed MIME package: {:?}", package_path);
+                }
+                Err(e) => {
+                    error!("Failed to remove MIME package: {}", e);
+                    return Err("Failed to remove MIME package");
                 }
             }
+        } else {
+            info!("MIME package does not exist: {:?}", package_path);
         }
     }
 
-    image_files
+    update_mime_database(&mime_dir);
+    Ok(())
 }
 
-/// Detect all installed app images and reads it's metadata
-pub fn read_all_app() -> Result<Vec<InstalledApp>, String> {
-    let mut apps: Vec<InstalledApp> = Vec::new();
+/// Identify the app packaged in an AppImage from its embedded metadata, without extracting it
+pub fn identify_app_image(reader: &mut AppImageReader) -> Result<AppIdentity, String> {
+    let desktop_file_id = reader.desktop_file_paths().into_iter().next();
+    let desktop_file = reader.desktop_file()?;
+    let metadata = match reader.metainfo() {
+        Some(Ok(metadata)) => Some(metadata),
+        Some(Err(err)) => {
+            error!("Ignoring invalid metainfo file: {}", err);
+            None
+        }
+        None => None,
+    };
+    let sha256 = app_image_digest(reader.path(), &[])?;
+
+    Ok(AppIdentity {
+        id: app_id(
+            desktop_file_id.as_deref(),
+            metadata
+                .as_ref()
+                .and_then(|metadata| metadata.id.as_deref()),
+            &sha256,
+        ),
+        name: desktop_file
+            .get(DESKTOP_ENTRY_GROUP, "Name")
+            .ok_or("Failed to get app name")?,
+        version: app_version(&desktop_file, metadata.as_ref()),
+        sha256,
+    })
+}
 
-    // read all .desktop files in the applications directory
-    let applications_dir: PathBuf = find_desktop_file_location().map_err(|e| e.to_string())?;
-
-    match std::fs::read_dir(applications_dir) {
-        Ok(entries) => {
-            for entry in entries {
-                let entry = entry.as_ref().unwrap();
-                let desktop_file = DesktopFileBuilder::from_desktop_entry_path(&entry.path(), true);
-                if desktop_file.is_err() {
-                    error!(
-                        "Failed to read desktop file: {}",
-                        desktop_file.err().unwrap()
-                    );
-                    continue;
-                }
+/// Find the AppStream metainfo file shipped in the extracted AppImage
+pub fn find_metainfo_file(squashfs_root_path: &Path) -> Option<PathBuf> {
+    for metainfo_dir in METAINFO_DIRS {
+        let mut metainfo_files: Vec<PathBuf> =
+            match fs::read_dir(squashfs_root_path.join(metainfo_dir)) {
+                Ok(entries) => entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .filter(|path| {
+                        path.is_file() && path.extension().is_some_and(|ext| ext == "xml")
+                    })
+                    .collect(),
+                Err(_) => continue,
+            };
+        metainfo_files.sort();
+        if let Some(metainfo_file) = metainfo_files.into_iter().next() {
+            debug!("Found metainfo file: {:?}", metainfo_file);
+            return Some(metainfo_file);
+        }
+    }
+    None
+}
+
+/// Stage the AppStream metainfo file in the installation dir metainfo folder
+pub fn install_metainfo(
+    metainfo_path: &Path,
+    installation_path: &Path,
+    transaction: &mut InstallTransaction,
+) -> Result<PathBuf, &'static str> {
+    let metainfo_dir = metainfo_dir(installation_path);
+    let file_name = match metainfo_path.file_name() {
+        Some(file_name) => file_name,
+        None => return Err("Invalid metainfo file path"),
+    };
+    let installed_metainfo_path = metainfo_dir.join(file_name);
+    match transaction.stage_copy(metainfo_path, &installed_metainfo_path) {
+        Ok(_) => {
+            info!("Staged metainfo file for: {:?}", installed_metainfo_path);
+        }
+        Err(e) => {
+            error!("Failed to copy metainfo file: {}", e);
+            return Err("Failed to copy metainfo file");
+        }
+    }
+
+    Ok(installed_metainfo_path)
+}
 
-                let desktop_entry = desktop_file.unwrap();
+/// The directory of the AppStream metainfo files, in the installation directory
+pub fn metainfo_dir(installation_path: &Path) -> PathBuf {
+    installation_path.join("metainfo")
+}
 
-                apps.push(InstalledApp {
-                    name: desktop_entry.name().unwrap(),
-                    app_path: desktop_entry.exec().unwrap(),
-                    version: desktop_entry.version(),
-                    categories: desktop_entry.categories(),
-                    icon_path: Some(PathBuf::from(desktop_entry.icon().unwrap())),
-                });
+/// Remove a metainfo file, which must be in the metainfo directory of the installation directory
+pub fn remove_metainfo(metainfo_path: &Path, installation_path: &Path) -> Result<(), &'static str> {
+    let metainfo_dir = metainfo_dir(installation_path);
+    if !is_inside(metainfo_path, &metainfo_dir) {
+        error!(
+            "Refusing to remove {:?}, outside of {:?}",
+            metainfo_path, metainfo_dir
+        );
+        return Err("Metainfo file is outside of the metainfo directory");
+    }
+    if metainfo_path.exists() {
+        match fs::remove_file(metainfo_path) {
+            Ok(_) => {
+                info!("Removed metainfo file: {:?}", metainfo_path);
+            }
+            Err(e) => {
+                error!("Failed to remove metainfo file: {}", e);
+                return Err("Failed to remove metainfo file");
             }
         }
-        Err(err) => {
-            return Err(format!("Failed to read directory: {}", err));
+    } else {
+        info!("Metainfo file does not exist: {:?}", metainfo_path);
+    }
+
+    Ok(())
+}
+
+/// Whether the path is a file of the directory, without `..` components leading out of it.
+/// The paths recorded for an app are only removed from the directories AppHub installs them in
+fn is_inside(path: &Path, dir: &Path) -> bool {
+    path != dir
+        && path.starts_with(dir)
+        && !path
+            .components()
+            .any(|component| component == Component::ParentDir)
+}
+
+/// Choose the icon file to display among the installed sizes: the biggest bitmap.
+/// `icons` is the value of the `X-AppHub-Icons` key
+fn preferred_icon_file(icons: &str) -> Option<PathBuf> {
+    let icon_paths: Vec<PathBuf> = icons
+        .split(';')
+        .filter(|icon| !icon.is_empty())
+        .map(PathBuf::from)
+        .collect();
+    let size = |path: &PathBuf| -> u32 {
+        path.components()
+            .rev()
+            .nth(2)
+            .and_then(|size_dir| {
+                let size_dir = size_dir.as_os_str().to_string_lossy();
+                size_dir.split('x').next()?.parse().ok()
+            })
+            .unwrap_or(0)
+    };
+    icon_paths
+        .iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == "png"))
+        .max_by_key(|path| size(path))
+        .or(icon_paths.first())
+        .cloned()
+}
+
+/// Detect all installed app images and reads it's metadata,
+/// from the install databases of the current user and then of all users
+pub fn read_all_app() -> Result<Vec<InstalledApp>, String> {
+    let mut apps: Vec<InstalledApp> = Vec::new();
+
+    for scope in InstallScope::ALL {
+        match InstallDatabase::open(scope) {
+            Ok(database) => apps.extend(database.apps.iter().filter_map(read_installed_app)),
+            Err(err) => error!("Failed to open the {} database: {}", scope, err),
         }
     }
 
     Ok(apps)
 }
+
+/// Read the metadata of an installed app from its desktop entry
+fn read_installed_app(record: &InstallRecord) -> Option<InstalledApp> {
+    let entry_path = record.desktop_entries.first()?;
+    let desktop_entry = match DesktopFileBuilder::from_desktop_entry_path(entry_path, true) {
+        Ok(desktop_entry) => desktop_entry,
+        Err(err) => {
+            error!("Failed to read desktop file {:?}: {}", entry_path, err);
+            return None;
+        }
+    };
+    let exec = match desktop_entry.exec_command() {
+        Some(exec) => exec,
+        None => {
+            error!("Invalid Exec value in desktop file: {:?}", entry_path);
+            return None;
+        }
+    };
+
+    // the name the app was installed with stands in for a missing one
+    let name = desktop_entry.name().unwrap_or_else(|| {
+        warn!("No Name in desktop file: {:?}", entry_path);
+        record.name.clone()
+    });
+
+    Some(InstalledApp {
+        id: record.id.clone(),
+        localized_name: desktop_entry
+            .localized("Name")
+            .unwrap_or_else(|| LocalizedString::new(name.clone())),
+        name,
+        generic_name: desktop_entry.localized("GenericName"),
+        comment: desktop_entry.localized("Comment"),
+        app_path: exec.program.clone(),
+        exec,
+        version: record.version.clone().or_else(|| desktop_entry.version()),
+        other_versions: record.other_versions.clone(),
+        portable_dirs: record
+            .versions()
+            .iter()
+            .flat_map(|version| existing_portable_dirs(&version.app_image_path))
+            .collect(),
+        categories: desktop_entry.categories(),
+        icon_path: match desktop_entry.icons() {
+            Some(icons) => preferred_icon_file(&icons),
+            None => desktop_entry.icon().map(PathBuf::from),
+        },
+        update_info: desktop_entry
+            .update_information()
+            .and_then(|raw| UpdateInfo::parse(&raw)),
+        signature: record.signature.clone(),
+        metadata: desktop_entry.metainfo().and_then(|path| {
+            AppStreamMetadata::from_path(Path::new(&path))
+                .map_err(|err| error!("Failed to read metainfo: {}", err))
+                .ok()
+        }),
+        actions: desktop_entry.actions(),
+        mime_types: desktop_entry.mime_types(),
+        scope: record.scope,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{build_app_image, TestNode};
+
+    #[test]
+    fn test_refuses_removals_outside_of_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let installation_dir = dir.path().join("Applications");
+        let outside = dir.path().join("shadow");
+        fs::write(&outside, "secret").unwrap();
+
+        assert!(remove_metainfo(&outside, &installation_dir).is_err());
+        let escaping = metainfo_dir(&installation_dir)
+            .join("..")
+            .join("..")
+            .join("shadow");
+        assert!(remove_metainfo(&escaping, &installation_dir).is_err());
+        assert!(remove_icon(&outside, &installation_dir).is_err());
+        assert!(outside.exists());
+
+        let metainfo = metainfo_dir(&installation_dir).join("org.example.App.metainfo.xml");
+        fs::create_dir_all(metainfo.parent().unwrap()).unwrap();
+        fs::write(&metainfo, "<component/>").unwrap();
+        remove_metainfo(&metainfo, &installation_dir).unwrap();
+        assert!(!metainfo.exists());
+    }
+
+    #[test]
+    fn test_extract_into_private_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let media_dir = dir.path().join("media");
+        fs::create_dir(&media_dir).unwrap();
+        let app_image_path = media_dir.join("Example.AppImage");
+        fs::write(
+            &app_image_path,
+            build_app_image(
+                &[],
+                &[("example.desktop", TestNode::File(b"[Desktop Entry]\n"))],
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&media_dir, fs::Permissions::from_mode(0o555)).unwrap();
+
+        let first = app_image_extract_squashroot(&app_image_path).unwrap();
+        let second = app_image_extract_squashroot(&app_image_path).unwrap();
+        let first_path = first.path().to_path_buf();
+        assert_ne!(first_path, second.path());
+        assert!(!first_path.starts_with(&media_dir));
+        assert!(!media_dir.join("squashfs-root").exists());
+        assert!(first_path.join("example.desktop").is_file());
+        assert_eq!(
+            fs::metadata(&first_path).unwrap().permissions().mode() & 0o777,
+            0o700
+        );
+
+        drop(first);
+        assert!(!first_path.exists());
+        assert!(second.path().exists());
+        fs::set_permissions(&media_dir, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_identify_app_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_image_path = dir.path().join("Example.AppImage");
+        fs::write(
+            &app_image_path,
+            build_app_image(
+                &[],
+                &[
+                    (
+                        "org.example.App.desktop",
+                        TestNode::File(b"[Desktop Entry]\nType=Application\nName=Example\n"),
+                    ),
+                    (
+                        "usr/share/metainfo/org.example.App.appdata.xml",
+                        TestNode::File(
+                            b"<component><id>org.example.Other</id><releases>\
+                              <release version=\"1.2.0\"/></releases></component>",
+                        ),
+                    ),
+                ],
+            ),
+        )
+        .unwrap();
+
+        let mut reader = AppImageReader::open(&app_image_path).unwrap();
+        let metadata = reader.metainfo().unwrap().unwrap();
+        assert_eq!(metadata.id.as_deref(), Some("org.example.Other"));
+
+        let identity = identify_app_image(&mut reader).unwrap();
+        assert_eq!(identity.id, "org.example.App");
+        assert_eq!(identity.name, "Example");
+        assert_eq!(identity.version.as_deref(), Some("1.2.0"));
+        assert_eq!(
+            identity.sha256,
+            app_image_digest(&app_image_path, &[]).unwrap()
+        );
+    }
+}
@@ -1,6 +0,0 @@
-
-pub struct DesktopEntry {
-    pub exec: String,
-    pub name: String,
-    pub icon: String,
-}
@@ -0,0 +1,590 @@
+use std::fmt;
+use std::path::Path;
+
+/// The main group of a desktop file
+pub const DESKTOP_ENTRY_GROUP: &str = "Desktop Entry";
+
+/// A line of a desktop file, with its original text so the file can be written back unchanged
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Line {
+    /// Blank lines, comments and anything else AppHub does not interpret, kept verbatim
+    Other(String),
+    /// A `[Group Name]` header
+    Group { name: String, raw: String },
+    /// A `Key[locale]=value` entry. The value is kept escaped, as in the file
+    Entry {
+        key: String,
+        locale: Option<String>,
+        value: String,
+        raw: String,
+    },
+}
+
+impl Line {
+    fn parse(raw: &str) -> Self {
+        let text = raw.trim_end_matches('\r');
+        let trimmed = text.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            return Self::Other(raw.to_string());
+        }
+
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            return Self::Group {
+                name: trimmed[1..trimmed.len() - 1].to_string(),
+                raw: raw.to_string(),
+            };
+        }
+
+        let (name, value) = match text.split_once('=') {
+            Some(entry) => entry,
+            None => return Self::Other(raw.to_string()),
+        };
+        let name = name.trim();
+        let (key, locale) = match name.split_once('[') {
+            Some((key, locale)) if locale.ends_with(']') => {
+                (key, Some(locale[..locale.len() - 1].to_string()))
+            }
+            Some(_) => return Self::Other(raw.to_string()),
+            None => (name, None),
+        };
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Self::Other(raw.to_string());
+        }
+
+        Self::Entry {
+            key: key.to_string(),
+            locale,
+            value: value.trim_start().to_string(),
+            raw: raw.to_string(),
+        }
+    }
+
+    fn raw(&self) -> &str {
+        match self {
+            Self::Other(raw) | Self::Group { raw, .. } | Self::Entry { raw, .. } => raw,
+        }
+    }
+
+    fn is_entry(&self, key: &str, locale: Option<&str>) -> bool {
+        match self {
+            Self::Entry {
+                key: entry_key,
+                locale: entry_locale,
+                ..
+            } => entry_key == key && entry_locale.as_deref() == locale,
+            _ => false,
+        }
+    }
+}
+
+/// The content of a line of a desktop file, as seen by the validator
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineContent<'a> {
+    /// A blank line or a comment
+    Blank,
+    Group(&'a str),
+    /// An entry, with its value still escaped
+    Entry {
+        key: &'a str,
+        locale: Option<&'a str>,
+        value: &'a str,
+    },
+    /// A line which is neither a comment, a group header nor a valid entry
+    Invalid(&'a str),
+}
+
+/// A freedesktop Desktop Entry file, see
+/// https://specifications.freedesktop.org/desktop-entry-spec/latest/
+///
+/// The file is kept line by line: writing it back reproduces the original content byte-for-byte,
+/// except for the keys that were explicitly changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DesktopFile {
+    lines: Vec<Line>,
+    trailing_newline: bool,
+}
+
+impl Default for DesktopFile {
+    fn default() -> Self {
+        Self {
+            lines: Vec::new(),
+            trailing_newline: true,
+        }
+    }
+}
+
+impl DesktopFile {
+    /// Parse the content of a desktop file.
+    /// Lines which are not groups or entries are kept as they are.
+    pub fn parse(content: &str) -> Self {
+        if content.is_empty() {
+            return Self::default();
+        }
+        let trailing_newline = content.ends_with('\n');
+        let content = content.strip_suffix('\n').unwrap_or(content);
+        Self {
+            lines: content.split('\n').map(Line::parse).collect(),
+            trailing_newline,
+        }
+    }
+
+    /// Read and parse a desktop file
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read desktop file {:?}: {}", path, e))?;
+        Ok(Self::parse(&content))
+    }
+
+    /// The content of each line, numbered from 1
+    pub fn numbered_lines(&self) -> impl Iterator<Item = (usize, LineContent<'_>)> {
+        self.lines.iter().enumerate().map(|(index, line)| {
+            let content = match line {
+                Line::Other(raw) => {
+                    let text = raw.trim();
+                    if text.is_empty() || text.starts_with('#') {
+                        LineContent::Blank
+                    } else {
+                        LineContent::Invalid(raw.trim_end_matches('\r'))
+                    }
+                }
+                Line::Group { name, .. } => LineContent::Group(name),
+                Line::Entry {
+                    key, locale, value, ..
+                } => LineContent::Entry {
+                    key,
+                    locale: locale.as_deref(),
+                    value,
+                },
+            };
+            (index + 1, content)
+        })
+    }
+
+    /// Names of the groups, in file order
+    pub fn groups(&self) -> Vec<String> {
+        self.lines
+            .iter()
+            .filter_map(|line| match line {
+                Line::Group { name, .. } => Some(name.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn has_group(&self, group: &str) -> bool {
+        self.group_range(group).is_some()
+    }
+
+    /// Keys of a group, with their locale, in file order
+    pub fn keys(&self, group: &str) -> Vec<(String, Option<String>)> {
+        match self.group_range(group) {
+            Some((start, end)) => self.lines[start..end]
+                .iter()
+                .filter_map(|line| match line {
+                    Line::Entry { key, locale, .. } => Some((key.clone(), locale.clone())),
+                    _ => None,
+                })
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// The value of a key, as written in the file
+    pub fn get_raw(&self, group: &str, key: &str, locale: Option<&str>) -> Option<&str> {
+        let (start, end) = self.group_range(group)?;
+        self.lines[start..end]
+            .iter()
+            .find(|line| line.is_entry(key, locale))
+            .and_then(|line| match line {
+                Line::Entry { value, .. } => Some(value.as_str()),
+                _ => None,
+            })
+    }
+
+    /// The unescaped value of a non localized key
+    pub fn get(&self, group: &str, key: &str) -> Option<String> {
+        self.get_raw(group, key, None)
+            .map(|value| unescape(value, false))
+    }
+
+    /// The unescaped value of a key for exactly the given locale
+    pub fn get_localized(&self, group: &str, key: &str, locale: &str) -> Option<String> {
+        self.get_raw(group, key, Some(locale))
+            .map(|value| unescape(value, false))
+    }
+
+    /// The locales a key is translated to, in file order
+    pub fn locales(&self, group: &str, key: &str) -> Vec<String> {
+        self.keys(group)
+            .into_iter()
+            .filter(|(entry_key, _)| entry_key == key)
+            .filter_map(|(_, locale)| locale)
+            .collect()
+    }
+
+    /// The value of a key holding a list of strings separated by `;`
+    pub fn get_list(&self, group: &str, key: &str) -> Option<Vec<String>> {
+        self.get_raw(group, key, None).map(split_list)
+    }
+
+    /// The value of a boolean key
+    pub fn get_bool(&self, group: &str, key: &str) -> Option<bool> {
+        match self.get_raw(group, key, None)?.trim() {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Set the value of a non localized key, escaping it.
+    /// The group is created if missing, the key is added at the end of the group.
+    pub fn set(&mut self, group: &str, key: &str, value: &str) {
+        if self.get(group, key).as_deref() != Some(value) {
+            self.set_raw(group, key, None, &escape(value, false));
+        }
+    }
+
+    /// Set the value of a key for the given locale
+    pub fn set_localized(&mut self, group: &str, key: &str, locale: &str, value: &str) {
+        if self.get_localized(group, key, locale).as_deref() != Some(value) {
+            self.set_raw(group, key, Some(locale), &escape(value, false));
+        }
+    }
+
+    /// Set a list of strings, each one terminated by `;`
+    pub fn set_list(&mut self, group: &str, key: &str, values: &[String]) {
+        let value: String = values
+            .iter()
+            .map(|value| format!("{};", escape(value, true)))
+            .collect();
+        self.set_raw(group, key, None, &value);
+    }
+
+    pub fn set_bool(&mut self, group: &str, key: &str, value: bool) {
+        self.set_raw(group, key, None, if value { "true" } else { "false" });
+    }
+
+    /// Set the value of a key, without escaping it
+    pub fn set_raw(&mut self, group: &str, key: &str, locale: Option<&str>, value: &str) {
+        let raw = match locale {
+            Some(locale) => format!("{}[{}]={}", key, locale, value),
+            None => format!("{}={}", key, value),
+        };
+        let mut line = Line::Entry {
+            key: key.to_string(),
+            locale: locale.map(|locale| locale.to_string()),
+            value: value.to_string(),
+            raw,
+        };
+
+        let (start, end) = match self.group_range(group) {
+            Some(range) => range,
+            None => {
+                self.add_group(group);
+                self.push_line_ending(&mut line);
+                self.lines.push(line);
+                return;
+            }
+        };
+
+        // Replace the existing entry in place
+        if let Some(index) = (start..end).find(|index| self.lines[*index].is_entry(key, locale)) {
+            let current = &self.lines[index];
+            if let (Line::Entry { value: current, .. }, Line::Entry { raw, .. }) =
+                (current, &mut line)
+            {
+                if current == value {
+                    return;
+                }
+                // Keep the line ending of the file
+                if self.lines[index].raw().ends_with('\r') {
+                    raw.push('\r');
+                }
+            }
+            self.lines[index] = line;
+            return;
+        }
+
+        // Add the entry after the last entry of the group, before trailing blank lines
+        let index = (start..end)
+            .rev()
+            .find(|index| !matches!(self.lines[*index], Line::Other(_)))
+            .unwrap_or(start)
+            + 1;
+        self.push_line_ending(&mut line);
+        self.lines.insert(index, line);
+    }
+
+    /// Remove a key and all its translations. Returns true if the key was present
+    pub fn remove(&mut self, group: &str, key: &str) -> bool {
+        let (start, end) = match self.group_range(group) {
+            Some(range) => range,
+            None => return false,
+        };
+        let before = self.lines.len();
+        let mut index = 0;
+        self.lines.retain(|line| {
+            let in_group = index >= start && index < end;
+            index += 1;
+            !(in_group && matches!(line, Line::Entry { key: entry_key, .. } if entry_key == key))
+        });
+        self.lines.len() != before
+    }
+
+    /// Remove a group with all its entries. Returns true if the group was present
+    pub fn remove_group(&mut self, group: &str) -> bool {
+        match self.group_range(group) {
+            Some((start, end)) => {
+                self.lines.drain(start..end);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Index of the group header and of the line following the group
+    fn group_range(&self, group: &str) -> Option<(usize, usize)> {
+        let start = self
+            .lines
+            .iter()
+            .position(|line| matches!(line, Line::Group { name, .. } if name == group))?;
+        let end = self.lines[start + 1..]
+            .iter()
+            .position(|line| matches!(line, Line::Group { .. }))
+            .map(|position| start + 1 + position)
+            .unwrap_or(self.lines.len());
+        Some((start, end))
+    }
+
+    fn add_group(&mut self, group: &str) {
+        let line_ending = if self.uses_crlf() { "\r" } else { "" };
+        if self
+            .lines
+            .last()
+            .is_some_and(|line| !line.raw().trim().is_empty())
+        {
+            self.lines.push(Line::Other(line_ending.to_string()));
+        }
+        self.lines.push(Line::Group {
+            name: group.to_string(),
+            raw: format!("[{}]{}", group, line_ending),
+        });
+    }
+
+    /// Whether the file ends its lines with `\r\n`, as told by its first line
+    fn uses_crlf(&self) -> bool {
+        self.lines
+            .first()
+            .is_some_and(|line| line.raw().ends_with('\r'))
+    }
+
+    /// End a new line like the other lines of the file
+    fn push_line_ending(&self, line: &mut Line) {
+        if let Line::Entry { raw, .. } = line {
+            if self.uses_crlf() {
+                raw.push('\r');
+            }
+        }
+    }
+}
+
+/// Write the desktop file back, keeping the original text of the lines which were not changed
+impl fmt::Display for DesktopFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, line) in self.lines.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", line.raw())?;
+        }
+        if self.trailing_newline && !self.lines.is_empty() {
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Unescape `\s`, `\n`, `\t`, `\r` and `\\`. In lists `\;` is a literal semicolon
+fn unescape(value: &str, list: bool) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => result.push(' '),
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some('\\') => result.push('\\'),
+            Some(';') if list => result.push(';'),
+            Some(other) => {
+                result.push('\\');
+                result.push(other);
+            }
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+fn escape(value: &str, list: bool) -> String {
+    let mut result = String::with_capacity(value.len());
+    for (index, c) in value.chars().enumerate() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\t' => result.push_str("\\t"),
+            '\r' => result.push_str("\\r"),
+            // Leading spaces would be stripped by parsers
+            ' ' if index == 0 => result.push_str("\\s"),
+            ';' if list => result.push_str("\\;"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// Split a list value on the `;` which are not escaped
+fn split_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                current.push(c);
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+            }
+            ';' => items.push(unescape(&std::mem::take(&mut current), true)),
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        items.push(unescape(&current, true));
+    }
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESKTOP_FILE: &str = "# Generated by a tool\r
+[Desktop Entry]\r
+Type=Application\r
+GenericName=Editor\r
+Name=Example\r
+Name[it]=Esempio\r
+Comment = Edit\\sfiles\\nquickly\r
+Exec=example %F\r
+Categories=Utility;Text\\;Tools;\r
+Terminal=false\r
+X-Unknown-Key=kept\r
+\r
+[Desktop Action new-window]\r
+Name=New Window\r
+Exec=example --new-window\r
+";
+
+    #[test]
+    fn test_round_trip() {
+        let desktop_file = DesktopFile::parse(DESKTOP_FILE);
+        assert_eq!(desktop_file.to_string(), DESKTOP_FILE);
+
+        let without_newline = "[Desktop Entry]\nName=Example";
+        assert_eq!(
+            DesktopFile::parse(without_newline).to_string(),
+            without_newline
+        );
+    }
+
+    #[test]
+    fn test_read_values() {
+        let desktop_file = DesktopFile::parse(DESKTOP_FILE);
+        assert_eq!(
+            desktop_file.groups(),
+            vec!["Desktop Entry", "Desktop Action new-window"]
+        );
+        assert_eq!(
+            desktop_file.get(DESKTOP_ENTRY_GROUP, "Name").as_deref(),
+            Some("Example")
+        );
+        assert_eq!(
+            desktop_file
+                .get_localized(DESKTOP_ENTRY_GROUP, "Name", "it")
+                .as_deref(),
+            Some("Esempio")
+        );
+        assert_eq!(
+            desktop_file.locales(DESKTOP_ENTRY_GROUP, "Name"),
+            vec!["it"]
+        );
+        assert_eq!(
+            desktop_file.get(DESKTOP_ENTRY_GROUP, "Comment").as_deref(),
+            Some("Edit files\nquickly")
+        );
+        assert_eq!(
+            desktop_file.get_list(DESKTOP_ENTRY_GROUP, "Categories"),
+            Some(vec!["Utility".to_string(), "Text;Tools".to_string()])
+        );
+        assert_eq!(
+            desktop_file.get_bool(DESKTOP_ENTRY_GROUP, "Terminal"),
+            Some(false)
+        );
+        assert_eq!(
+            desktop_file
+                .get("Desktop Action new-window", "Exec")
+                .as_deref(),
+            Some("example --new-window")
+        );
+        assert_eq!(desktop_file.get(DESKTOP_ENTRY_GROUP, "Missing"), None);
+    }
+
+    #[test]
+    fn test_only_changed_keys_are_rewritten() {
+        let mut desktop_file = DesktopFile::parse(DESKTOP_FILE);
+        desktop_file.set(DESKTOP_ENTRY_GROUP, "Exec", "/opt/example.AppImage %F");
+        desktop_file.set(DESKTOP_ENTRY_GROUP, "X-AppHub", "true");
+        desktop_file.set("Desktop Action new-window", "Name", " New\\Window");
+        desktop_file.remove(DESKTOP_ENTRY_GROUP, "Terminal");
+        desktop_file.set("Desktop Action private", "Name", "Private");
+
+        let expected = DESKTOP_FILE
+            .replace("Exec=example %F\r\n", "Exec=/opt/example.AppImage %F\r\n")
+            .replace("Terminal=false\r\n", "")
+            .replace(
+                "X-Unknown-Key=kept\r\n",
+                "X-Unknown-Key=kept\r\nX-AppHub=true\r\n",
+            )
+            .replace("Name=New Window\r\n", "Name=\\sNew\\\\Window\r\n")
+            + "\r\n[Desktop Action private]\r\nName=Private\r\n";
+        assert_eq!(desktop_file.to_string(), expected);
+        assert_eq!(
+            desktop_file
+                .get("Desktop Action new-window", "Name")
+                .as_deref(),
+            Some(" New\\Window")
+        );
+    }
+
+    #[test]
+    fn test_new_file() {
+        let mut desktop_file = DesktopFile::default();
+        desktop_file.set(DESKTOP_ENTRY_GROUP, "Name", "Example");
+        desktop_file.set_list(
+            DESKTOP_ENTRY_GROUP,
+            "Keywords",
+            &["a;b".to_string(), "c".to_string()],
+        );
+        desktop_file.set("Desktop Action new", "Name", "New");
+        assert_eq!(
+            desktop_file.to_string(),
+            "[Desktop Entry]\nName=Example\nKeywords=a\\;b;c;\n\n[Desktop Action new]\nName=New\n"
+        );
+    }
+}
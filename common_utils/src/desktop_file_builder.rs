@@ -1,6 +1,9 @@
 use std::path::PathBuf;
-use regex::Regex;
-use log::info;
+use log::{info, warn};
+use crate::desktop_file::{DesktopFile, DESKTOP_ENTRY_GROUP};
+use crate::models::desktop_action::DesktopAction;
+use crate::models::exec_command::ExecCommand;
+use crate::models::localized_string::LocalizedString;
 
 pub struct DesktopFileBuilder {
     /// The `type_` field represents the type of the application. It's usually "Application" for desktop applications.
@@ -32,6 +35,24 @@ pub struct DesktopFileBuilder {
 
     /// The `no_sandbox` field indicates whether the application should be run in a sandbox.
     no_sanbox: Option<bool>,
+
+    /// The `update_information` field holds the update information embedded in the AppImage.
+    update_information: Option<String>,
+
+    /// The `signature` field holds the result of the AppImage signature verification.
+    signature: Option<String>,
+
+    /// The `metainfo` field represents the path of the installed AppStream metainfo file.
+    metainfo: Option<String>,
+
+    /// The `icons` field lists the icon files installed for the application.
+    icons: Option<String>,
+
+    /// The `mime_packages` field lists the shared-mime-info packages installed for the application.
+    mime_packages: Option<String>,
+
+    /// The `source` field holds the parsed desktop file the builder was read from.
+    source: DesktopFile,
 }
 
 impl DesktopFileBuilder {
@@ -47,76 +68,91 @@ impl DesktopFileBuilder {
             terminal: None,
             categories: None,
             no_sanbox: None,
+            update_information: None,
+            signature: None,
+            metainfo: None,
+            icons: None,
+            mime_packages: None,
+            source: DesktopFile::default(),
         }
     }
 
     /// Read a .desktop file from a given path and return a DesktopFileBuilder instance.
+    /// The parsed file is kept, so the generated content only differs in the keys that were set.
     pub fn from_desktop_entry_path(path: &PathBuf, is_app_hub_app: bool) -> Result<Self, &'static str> {
-        // Read the file content
-        let file_content = match std::fs::read_to_string(&path) {
-            Ok(content) => content,
+        // Parse the file content
+        let desktop_file = match DesktopFile::from_path(path) {
+            Ok(desktop_file) => desktop_file,
             Err(e) => {
                 log::error!("Failed to read file: {}", e);
                 return Err("Failed to read .desktop file");
             }
         };
 
+        let desktop_file_builder = Self::from_desktop_file(desktop_file, is_app_hub_app)?;
+        info!("Successfully parsed .desktop file from path: {:?}", path);
+        Ok(desktop_file_builder)
+    }
+
+    /// Create a DesktopFileBuilder from an already parsed .desktop file
+    pub fn from_desktop_file(
+        mut desktop_file: DesktopFile,
+        is_app_hub_app: bool,
+    ) -> Result<Self, &'static str> {
+        if !desktop_file.has_group(DESKTOP_ENTRY_GROUP) {
+            return Err("Invalid .desktop file");
+        }
+
         // Check if the file content contains the AppHub specific field
-        if is_app_hub_app && !file_content.contains("X-AppHub=true") {
+        if is_app_hub_app && desktop_file.get_bool(DESKTOP_ENTRY_GROUP, "X-AppHub") != Some(true) {
             return Err("Invalid .desktop file");
         }
 
-        // Parse the file content
         let mut desktop_file_builder = DesktopFileBuilder::new();
+        let get = |key: &str| {
+            let value = desktop_file.get(DESKTOP_ENTRY_GROUP, key);
+            if let Some(value) = &value {
+                info!("Setting '{}' to '{}'", key, value);
+            }
+            value
+        };
 
-        let re_type = Regex::new(r"(?m)Type=(.*)$").unwrap();
-        let re_version = Regex::new(r"(?m)Version=(.*)$").unwrap();
-        let re_name = Regex::new(r"(?m)Name=(.*)$").unwrap();
-        let re_comment = Regex::new(r"(?m)Comment=(.*)$").unwrap();
-        let re_path = Regex::new(r"(?m)Path=(.*)$").unwrap();
-        let re_exec = Regex::new(r"(?m)Exec=(.*)$").unwrap();
-        let re_icon = Regex::new(r"(?m)Icon=(.*)$").unwrap();
-        let re_terminal = Regex::new(r"(?m)Terminal=(.*)$").unwrap();
-        let re_categories = Regex::new(r"(?m)Categories=(.*)$").unwrap();
-
-        if let Some(cap) = re_type.captures(&file_content) {
-            info!("Setting 'Type' to '{}'", &cap[1]);
-            desktop_file_builder.set_type(cap[1].to_string());
-        }
-        if let Some(cap) = re_version.captures(&file_content) {
-            info!("Setting 'Version' to '{}'", &cap[1]);
-            desktop_file_builder.set_version(cap[1].to_string());
-        }
-        if let Some(cap) = re_name.captures(&file_content) {
-            info!("Setting 'Name' to '{}'", &cap[1]);
-            desktop_file_builder.set_name(cap[1].to_string());
-        }
-        if let Some(cap) = re_comment.captures(&file_content) {
-            info!("Setting 'Comment' to '{}'", &cap[1]);
-            desktop_file_builder.set_comment(cap[1].to_string());
-        }
-        if let Some(cap) = re_path.captures(&file_content) {
-            info!("Setting 'Path' to '{}'", &cap[1]);
-            desktop_file_builder.set_path(cap[1].to_string());
-        }
-        if let Some(cap) = re_exec.captures(&file_content) {
-            info!("Setting 'Exec' to '{}'", &cap[1]);
-            desktop_file_builder.set_exec(cap[1].to_string());
+        desktop_file_builder.type_ = get("Type");
+        desktop_file_builder.version = get("Version");
+        desktop_file_builder.name = get("Name");
+        desktop_file_builder.comment = get("Comment");
+        desktop_file_builder.path = get("Path");
+        desktop_file_builder.exec = get("Exec");
+        desktop_file_builder.icon = get("Icon");
+        desktop_file_builder.terminal = desktop_file.get_bool(DESKTOP_ENTRY_GROUP, "Terminal");
+        // Lists are kept as written, `;` separated
+        desktop_file_builder.categories = desktop_file
+            .get_raw(DESKTOP_ENTRY_GROUP, "Categories", None)
+            .map(|categories| categories.to_string());
+        // The AppHub keys are only trusted in the entries AppHub wrote,
+        // the ones of another entry, such as the one embedded in an AppImage, are dropped
+        if is_app_hub_app {
+            desktop_file_builder.update_information = get("X-AppHub-UpdateInformation");
+            desktop_file_builder.signature = get("X-AppHub-Signature");
+            desktop_file_builder.metainfo = get("X-AppHub-Metainfo");
+            desktop_file_builder.icons = desktop_file
+                .get_raw(DESKTOP_ENTRY_GROUP, "X-AppHub-Icons", None)
+                .map(|icons| icons.to_string());
+            desktop_file_builder.mime_packages = desktop_file
+                .get_raw(DESKTOP_ENTRY_GROUP, "X-AppHub-MimePackages", None)
+                .map(|mime_packages| mime_packages.to_string());
         }
-        if let Some(cap) = re_icon.captures(&file_content) {
-            info!("Setting 'Icon' to '{}'", &cap[1]);
-            desktop_file_builder.set_icon(cap[1].to_string());
-        }
-        if let Some(cap) = re_terminal.captures(&file_content) {
-            info!("Setting 'Terminal' to '{}'", &cap[1]);
-            desktop_file_builder.set_terminal(cap[1].eq("true"));
-        }
-        if let Some(cap) = re_categories.captures(&file_content) {
-            info!("Setting 'Categories' to '{}'", &cap[1]);
-            desktop_file_builder.set_categories(cap[1].split(";").map(|s| s.to_string()).collect());
+        if !is_app_hub_app {
+            for group in desktop_file.groups() {
+                for (key, _) in desktop_file.keys(&group) {
+                    if key.starts_with("X-AppHub") && desktop_file.remove(&group, &key) {
+                        warn!("Dropping {} of a desktop entry not written by AppHub", key);
+                    }
+                }
+            }
         }
+        desktop_file_builder.source = desktop_file;
 
-        info!("Successfully parsed .desktop file from path: {:?}", path);
         Ok(desktop_file_builder)
     }
 
@@ -175,7 +211,41 @@ impl DesktopFileBuilder {
         self
     }
 
+    pub fn set_update_information(&mut self, update_information: String) -> &mut Self {
+        self.update_information = Some(update_information);
+        self
+    }
+
+    pub fn set_signature(&mut self, signature: String) -> &mut Self {
+        self.signature = Some(signature);
+        self
+    }
+
+    pub fn set_metainfo(&mut self, metainfo: String) -> &mut Self {
+        self.metainfo = Some(metainfo);
+        self
+    }
+
+    pub fn set_mime_packages(&mut self, mime_packages: Vec<String>) -> &mut Self {
+        let mime_packages = mime_packages.join(";");
+        self.mime_packages = Some(mime_packages);
+        self
+    }
+
+    /// Set the command of a desktop action
+    pub fn set_action_exec(&mut self, id: &str, exec: String) -> &mut Self {
+        self.source.set(&action_group(id), "Exec", &exec);
+        self
+    }
+
+    pub fn set_icons(&mut self, icons: Vec<String>) -> &mut Self {
+        let icons = icons.join(";");
+        self.icons = Some(icons);
+        self
+    }
+
     /// Generate the content of the .desktop file as a string.
+    /// Keys which are not handled by the builder are kept unchanged.
     pub fn generate_content_string(self) -> Result<String, &'static str> {
         // Check mandatory fields
         if self.type_.is_none() {
@@ -188,53 +258,71 @@ impl DesktopFileBuilder {
             return Err("Exec field is mandatory");
         }
 
-        // Create the file content
-        let mut desktop_file_content = String::from("[Desktop Entry]\n");
+        let mut desktop_file = self.source;
+        let group = DESKTOP_ENTRY_GROUP;
 
         if let Some(type_) = self.type_ {
-            desktop_file_content.push_str(&format!("Type={}\n", type_));
+            desktop_file.set(group, "Type", &type_);
         }
 
         if let Some(version) = self.version {
-            desktop_file_content.push_str(&format!("Version={}\n", version));
+            desktop_file.set(group, "Version", &version);
         }
 
         if let Some(name) = self.name {
-            desktop_file_content.push_str(&format!("Name={}\n", name));
+            desktop_file.set(group, "Name", &name);
         }
 
         if let Some(comment) = self.comment {
-            desktop_file_content.push_str(&format!("Comment={}\n", comment));
+            desktop_file.set(group, "Comment", &comment);
         }
 
         if let Some(path) = self.path {
-            desktop_file_content.push_str(&format!("Path={}\n", path));
+            desktop_file.set(group, "Path", &path);
         }
 
         if let Some(exec) = self.exec {
             if self.no_sanbox.is_some() && self.no_sanbox.unwrap() {
-                desktop_file_content.push_str(&format!("Exec={} --no-sandbox\n", exec));
+                let mut command =
+                    ExecCommand::parse(&exec).map_err(|_| "Exec field is not a valid command")?;
+                command.add_argument("--no-sandbox");
+                desktop_file.set(group, "Exec", &command.to_string());
             } else {
-                desktop_file_content.push_str(&format!("Exec={}\n", exec));
+                desktop_file.set(group, "Exec", &exec);
             }
         }
 
         if let Some(icon) = self.icon {
-            desktop_file_content.push_str(&format!("Icon={}\n", icon));
+            desktop_file.set(group, "Icon", &icon);
         }
 
         if let Some(terminal) = self.terminal {
-            desktop_file_content.push_str(&format!("Terminal={}\n", terminal));
+            desktop_file.set_bool(group, "Terminal", terminal);
         }
 
         if let Some(categories) = self.categories {
-            desktop_file_content.push_str(&format!("Categories={}\n", categories));
+            desktop_file.set_raw(group, "Categories", None, &categories);
         }
 
         // AppHub specific fields
-        desktop_file_content.push_str("X-AppHub=true\n");
+        if let Some(update_information) = self.update_information {
+            desktop_file.set(group, "X-AppHub-UpdateInformation", &update_information);
+        }
+        if let Some(signature) = self.signature {
+            desktop_file.set(group, "X-AppHub-Signature", &signature);
+        }
+        if let Some(metainfo) = self.metainfo {
+            desktop_file.set(group, "X-AppHub-Metainfo", &metainfo);
+        }
+        if let Some(icons) = self.icons {
+            desktop_file.set_raw(group, "X-AppHub-Icons", None, &icons);
+        }
+        if let Some(mime_packages) = self.mime_packages {
+            desktop_file.set_raw(group, "X-AppHub-MimePackages", None, &mime_packages);
+        }
+        desktop_file.set_bool(group, "X-AppHub", true);
 
-        Ok(desktop_file_content)
+        Ok(desktop_file.to_string())
     }
 
     pub fn type_(&self) -> Option<String> {
@@ -257,6 +345,13 @@ impl DesktopFileBuilder {
         self.exec.clone()
     }
 
+    /// The parsed `Exec` command, if it is valid
+    pub fn exec_command(&self) -> Option<ExecCommand> {
+        self.exec
+            .as_deref()
+            .and_then(|exec| ExecCommand::parse(exec).ok())
+    }
+
     pub fn comment(&self) -> Option<String> {
         self.comment.clone()
     }
@@ -276,6 +371,68 @@ impl DesktopFileBuilder {
     pub fn no_sanbox(&self) -> Option<bool> {
         self.no_sanbox
     }
+
+    pub fn update_information(&self) -> Option<String> {
+        self.update_information.clone()
+    }
+
+    pub fn signature(&self) -> Option<String> {
+        self.signature.clone()
+    }
+
+    pub fn metainfo(&self) -> Option<String> {
+        self.metainfo.clone()
+    }
+
+    pub fn icons(&self) -> Option<String> {
+        self.icons.clone()
+    }
+
+    pub fn mime_packages(&self) -> Option<String> {
+        self.mime_packages.clone()
+    }
+
+    /// A localestring key of the desktop entry with its translations, as read from the file
+    pub fn localized(&self, key: &str) -> Option<LocalizedString> {
+        let group = DESKTOP_ENTRY_GROUP;
+        let mut localized = LocalizedString::new(self.source.get(group, key)?);
+        for locale in self.source.locales(group, key) {
+            if let Some(value) = self.source.get_localized(group, key, &locale) {
+                localized.translations.insert(locale, value);
+            }
+        }
+        Some(localized)
+    }
+
+    /// The MIME types the application can open, from the `MimeType=` key
+    pub fn mime_types(&self) -> Vec<String> {
+        self.source
+            .get_list(DESKTOP_ENTRY_GROUP, "MimeType")
+            .unwrap_or_default()
+    }
+
+    /// The desktop actions listed in the `Actions=` key.
+    /// Actions without a group or a name are ignored, as required by the specification.
+    pub fn actions(&self) -> Vec<DesktopAction> {
+        self.source
+            .get_list(DESKTOP_ENTRY_GROUP, "Actions")
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|id| {
+                let group = action_group(&id);
+                Some(DesktopAction {
+                    name: self.source.get(&group, "Name")?,
+                    exec: self.source.get(&group, "Exec"),
+                    icon: self.source.get(&group, "Icon"),
+                    id,
+                })
+            })
+            .collect()
+    }
+}
+
+fn action_group(id: &str) -> String {
+    format!("Desktop Action {}", id)
 }
 
 #[cfg(test)]
@@ -295,6 +452,10 @@ mod tests {
         assert!(builder.terminal().is_none());
         assert!(builder.categories().is_none());
         assert!(builder.no_sanbox().is_none());
+        assert!(builder.update_information().is_none());
+        assert!(builder.signature().is_none());
+        assert!(builder.metainfo().is_none());
+        assert!(builder.icons().is_none());
     }
 
     #[test]
@@ -342,4 +503,137 @@ mod tests {
         let expected_content = "[Desktop Entry]\nType=Application\nVersion=1.0\nName=Test App\nComment=This is a test\nPath=/usr/bin/test\nExec=test --no-sandbox\nIcon=/usr/share/icons/test.png\nTerminal=true\nCategories=Utility\nX-AppHub=true\n";
         assert_eq!(content, expected_content);
     }
+
+    #[test]
+    fn test_from_desktop_entry_path_keeps_other_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.desktop");
+        let content = "[Desktop Entry]\n\
+                       # A comment\n\
+                       Type=Application\n\
+                       GenericName=Generic\n\
+                       Name=Test App\n\
+                       Name[it]=App di prova\n\
+                       Exec=test %U\n\
+                       Categories=Utility;\n\
+                       \n\
+                       [Desktop Action new]\n\
+                       Name=New\n\
+                       Exec=test --new\n";
+        std::fs::write(&path, content).unwrap();
+
+        let builder = DesktopFileBuilder::from_desktop_entry_path(&path, false).unwrap();
+        assert_eq!(builder.name(), Some("Test App".to_string()));
+        assert_eq!(builder.exec(), Some("test %U".to_string()));
+        let name = builder.localized("Name").unwrap();
+        assert_eq!(name.default, "Test App");
+        assert_eq!(name.translations.get("it").unwrap(), "App di prova");
+        assert!(builder.localized("Comment").is_none());
+        assert!(DesktopFileBuilder::from_desktop_entry_path(&path, true).is_err());
+
+        let mut builder = builder;
+        builder.set_exec("/opt/test.AppImage".to_string());
+        let content = builder.generate_content_string().unwrap();
+        assert_eq!(
+            content,
+            "[Desktop Entry]\n\
+             # A comment\n\
+             Type=Application\n\
+             GenericName=Generic\n\
+             Name=Test App\n\
+             Name[it]=App di prova\n\
+             Exec=/opt/test.AppImage\n\
+             Categories=Utility;\n\
+             X-AppHub=true\n\
+             \n\
+             [Desktop Action new]\n\
+             Name=New\n\
+             Exec=test --new\n"
+        );
+
+        // Generating again the content of an AppHub desktop file changes nothing
+        std::fs::write(&path, &content).unwrap();
+        let builder = DesktopFileBuilder::from_desktop_entry_path(&path, true).unwrap();
+        assert_eq!(builder.generate_content_string().unwrap(), content);
+    }
+
+    #[test]
+    fn test_drops_app_hub_keys_of_other_entries() {
+        let desktop_file = DesktopFile::parse(
+            "[Desktop Entry]\n\
+             Type=Application\n\
+             Name=Hostile\n\
+             Exec=hostile\n\
+             X-AppHub=true\n\
+             X-AppHub-Signature=trusted;0123456789ABCDEF\n\
+             X-AppHub-UpdateInformation=zsync|https://example.com/fake.zsync\n\
+             X-AppHub-Metainfo=/etc/shadow\n\
+             X-AppHub-Icons=/etc/passwd;\n\
+             X-AppHub-MimePackages=/etc/group;\n\
+             X-AppHub-Custom=kept?\n\
+             \n\
+             [Desktop Action open]\n\
+             Name=Open\n\
+             X-AppHub-Metainfo=/etc/shadow\n",
+        );
+
+        let builder = DesktopFileBuilder::from_desktop_file(desktop_file.clone(), false).unwrap();
+        assert!(builder.signature().is_none());
+        assert!(builder.update_information().is_none());
+        assert!(builder.metainfo().is_none());
+        assert!(builder.icons().is_none());
+        assert!(builder.mime_packages().is_none());
+        let content = builder.generate_content_string().unwrap();
+        assert!(!content.contains("/etc/"));
+        assert!(!content.contains("trusted"));
+        assert!(!content.contains("X-AppHub-Custom"));
+
+        // an entry written by AppHub keeps them
+        let builder = DesktopFileBuilder::from_desktop_file(desktop_file, true).unwrap();
+        assert_eq!(builder.metainfo(), Some("/etc/shadow".to_string()));
+    }
+
+    #[test]
+    fn test_actions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.desktop");
+        let content = "[Desktop Entry]\n\
+                       Type=Application\n\
+                       Name=Test App\n\
+                       Exec=test %U\n\
+                       MimeType=text/plain;application/x-example;\n\
+                       Actions=new-window;missing;\n\
+                       \n\
+                       [Desktop Action new-window]\n\
+                       Name=New Window\n\
+                       Exec=test --new-window %U\n\
+                       Icon=test-new\n";
+        std::fs::write(&path, content).unwrap();
+
+        let mut builder = DesktopFileBuilder::from_desktop_entry_path(&path, false).unwrap();
+        assert_eq!(
+            builder.actions(),
+            vec![DesktopAction {
+                id: "new-window".to_string(),
+                name: "New Window".to_string(),
+                exec: Some("test --new-window %U".to_string()),
+                icon: Some("test-new".to_string()),
+            }]
+        );
+
+        assert_eq!(
+            builder.mime_types(),
+            vec!["text/plain".to_string(), "application/x-example".to_string()]
+        );
+
+        builder.set_action_exec(
+            "new-window",
+            "/opt/test.AppImage --new-window %U".to_string(),
+        );
+        let content = builder.generate_content_string().unwrap();
+        assert!(content.contains("MimeType=text/plain;application/x-example;\n"));
+        assert!(content.contains(
+            "[Desktop Action new-window]\nName=New Window\nExec=/opt/test.AppImage --new-window %U\n"
+        ));
+    }
 }
@@ -1,188 +1,107 @@
+use std::collections::HashSet;
 use std::{env, fs};
 use std::path::{Path, PathBuf};
-use log::{error};
-use crate::desktop_entry::DesktopEntry;
-use crate::desktop_file_builder::DesktopFileBuilder;
-
-/// Find the desktop entry of the application with the given name.
-/// The function reads all the .desktop files in the applications directory and compares the "Name" value
-/// of each file with the given app_name. If a match is found, the function returns the DesktopEntry struct
-pub fn find_desktop_entry(app_name: String) -> Result<DesktopEntry, String> {
-    // read all .desktop files in the applications directory
-    let applications_dir: PathBuf = find_desktop_file_location().map_err(|e| e.to_string())?;
-    match std::fs::read_dir(applications_dir) {
-        Ok(entries) => {
-            for entry in entries {
-                let entry = match entry {
-                    Ok(entry) => {
-                        entry
-                    }
-                    Err(error) => {
-                        error!("{}", error);
-                        continue; // Skip the current entry
-                    }
-                };
-
-                let entry_path = entry.path();
-                let desktop_file = DesktopFileBuilder::from_desktop_entry_path(&entry_path, true);
-
-                match desktop_file {
-                    Ok(desktop_entry) => {
-                        if desktop_entry.name().unwrap() == app_name {
-                            return Ok(DesktopEntry {
-                                exec: desktop_entry.exec().unwrap(),
-                                name: desktop_entry.name().unwrap(),
-                                icon: desktop_entry.icon().unwrap(),
-                            });
-                        }
-                    }
-                    Err(err) => {
-                        error!("Failed to read desktop file: {}", err);
-                        continue;
-                    }
+use log::debug;
+use crate::desktop_file::{DesktopFile, DESKTOP_ENTRY_GROUP};
+use crate::models::install_scope::InstallScope;
+use crate::xdg_helpers::applications_search_path;
+
+/// Keys users set in the desktop entry of an installed app to customize it,
+/// which AppImages do not set themselves
+const CUSTOMIZATION_KEYS: [&str; 4] = ["NoDisplay", "Hidden", "OnlyShowIn", "NotShowIn"];
+
+/// Find the .desktop files visible in the search path of the given scopes, in order of preference.
+/// A file shadows the files with the same desktop file ID found later, like the desktop environment does.
+pub fn find_desktop_files(scopes: &[InstallScope]) -> Vec<(InstallScope, PathBuf)> {
+    let mut desktop_file_ids: HashSet<String> = HashSet::new();
+    let mut desktop_files: Vec<(InstallScope, PathBuf)> = Vec::new();
+
+    for scope in scopes {
+        for applications_dir in applications_search_path(*scope) {
+            for path in collect_desktop_files(&applications_dir) {
+                let desktop_file_id = desktop_file_id(&applications_dir, &path);
+                if desktop_file_ids.insert(desktop_file_id) {
+                    desktop_files.push((*scope, path));
+                } else {
+                    debug!("Skipping shadowed desktop file: {:?}", path);
                 }
             }
-            return Err(format!("App not found: {}", app_name));
-        }
-        Err(err) => {
-            return Err(format!("Failed to read directory: {}", err));
         }
     }
+    desktop_files
 }
 
-/// Find the desktop entries containing the given string in the "Exec" value.
-/// The function reads all the .desktop files in the applications directory and compares the "Exec" value
-/// of each file with the given contains_exec. If a match is found, the function returns a vector of paths
-/// to the .desktop files.
-pub fn find_desktop_entries_by_exec_contains(contains_exec: &String) -> Result<Vec<String>, String> {
-    let mut desktop_entries_paths: Vec<String> = Vec::new();
-
-    // read all .desktop files in the applications directory
-    let applications_dir: PathBuf = find_desktop_file_location().map_err(|e| e.to_string())?;
-    match std::fs::read_dir(applications_dir) {
-        Ok(entries) => {
-            for entry in entries {
-                let entry = match entry {
-                    Ok(entry) => {
-                        entry
-                    }
-                    Err(error) => {
-                        error!("{}", error);
-                        continue; // Skip the current entry
-                    }
-                };
-
-                let entry_path = entry.path();
-                let desktop_file = DesktopFileBuilder::from_desktop_entry_path(&entry_path, true);
-
-                match desktop_file {
-                    Ok(desktop_entry) => {
-                        if desktop_entry.exec().unwrap().contains(contains_exec) {
-                            desktop_entries_paths.push(entry_path.to_string_lossy().to_string());
-                        }
-                    }
-                    Err(error) => {
-                        error!("{}", error);
-                    }
-                }
-            }
-        }
-        Err(err) => {
-            return Err(format!("Failed to read directory: {}", err));
+/// The .desktop files of an applications directory and its subdirectories, sorted by path
+fn collect_desktop_files(dir: &Path) -> Vec<PathBuf> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut desktop_files: Vec<PathBuf> = Vec::new();
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if path.is_dir() {
+            desktop_files.extend(collect_desktop_files(&path));
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "desktop")
+        {
+            desktop_files.push(path);
         }
     }
-
-    Ok(desktop_entries_paths)
+    desktop_files.sort();
+    desktop_files
 }
 
-/// Delete the desktop file of the application with the given name.
-/// The function returns true if the file is successfully deleted, and false otherwise.
-/// If the file is not found, the function returns an error message.
-/// The function reads all the .desktop files in the applications directory and compares the "Name" value
-/// of each file with the given app_name. If a match is found, the file is deleted.
-pub fn delete_desktop_file_by_name(app_name: &String) -> Result<bool, String> {
-    let applications_dir: PathBuf = find_desktop_file_location().map_err(|e| e.to_string())?;
-    match std::fs::read_dir(applications_dir) {
-        Ok(entries) => {
-            for entry in entries {
-
-                let entry = match entry {
-                    Ok(entry) => {
-                        entry
-                    }
-                    Err(error) => {
-                        error!("{}", error);
-                        continue; // Skip the current entry
-                    }
-                };
-
-                let entry_path = entry.path();
-                let path_str = entry_path.to_str().ok_or("Failed to convert path to string")?;
-                let desktop_file = DesktopFileBuilder::from_desktop_entry_path(&entry_path, true);
-
-                match desktop_file {
-                    Ok(desktop_entry) => {
-                        match desktop_entry.name() {
-                            Some(name) => {
-                                if name == *app_name {
-                                    match fs::remove_file(path_str) {
-                                        Ok(_res) => {
-                                            return Ok(true);
-                                        }
-                                        Err(error) => {
-                                            return Err(format!("Failed to remove file: {}", error));
-                                        }
-                                    }
-                                }
-                            }
-                            None => {
-                                error!("Failed to read desktop file: Name is None");
-                                continue;
-                            }
-                        }
-                    }
-                    Err(error) => {
-                        error!("{}", error);
-                    }
-                }
-            }
-            return Err(format!("App not found: {}", app_name));
-        }
-        Err(err) => {
-            return Err(format!("Failed to read directory: {}", err));
-        }
-    }
+/// The desktop file ID: the path relative to the applications directory, with `/` replaced by `-`
+fn desktop_file_id(applications_dir: &Path, path: &Path) -> String {
+    path.strip_prefix(applications_dir)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('/', "-")
 }
 
-pub fn find_desktop_file_location() -> Result<PathBuf, &'static str> {
-    // Check the KDE environment variable
-    if let Ok(path) = env::var("KDE_INSTALL_APPDIR") {
-        return Ok(PathBuf::from(path));
-    }
-
-    // Check the standard directories
-    let global_path = "/usr/share/applications";
-    if Path::new(global_path).exists() {
-        return Ok(PathBuf::from(global_path));
+/// Find the directory where AppHub writes the desktop entries of the given scope,
+/// `$XDG_DATA_HOME/applications` for the user, `/usr/share/applications` for the system.
+/// The user directory is created if it does not exist yet.
+pub fn find_desktop_file_location(scope: InstallScope) -> Result<PathBuf, &'static str> {
+    if scope == InstallScope::System {
+        // Check the KDE environment variable
+        if let Ok(path) = env::var("KDE_INSTALL_APPDIR") {
+            return Ok(PathBuf::from(path));
+        }
     }
 
-    let home_dir = dirs::home_dir().ok_or("Failed to get home directory")?;
-    let local_path = home_dir.join(".local").join("share").join("applications");
-    if Path::new(&local_path).exists() {
-        return Ok(local_path);
+    let applications_dir = scope.data_dir()?.join("applications");
+    if scope == InstallScope::User && !applications_dir.exists() {
+        fs::create_dir_all(&applications_dir)
+            .map_err(|_| "Failed to create applications directory")?;
     }
 
-    // If neither environment variable is set, use a default path
-    let home_dir = dirs::home_dir().ok_or("Failed to get home directory")?;
-    let default_path = home_dir.join(".local").join("share").join("applications");
-
-    if Path::new(&default_path).exists() {
-        Ok(default_path)
+    if applications_dir.exists() {
+        Ok(applications_dir)
     } else {
-        Err("Default path does not exist")
+        Err("Applications directory does not exist")
     }
 }
 
+/// Copy the customizations of the installed desktop entry of an app into the entry of its new version.
+/// Returns the number of copied keys
+pub fn keep_customizations(installed: &DesktopFile, desktop_file: &mut DesktopFile) -> usize {
+    let mut kept = 0;
+    for (key, locale) in installed.keys(DESKTOP_ENTRY_GROUP) {
+        if !CUSTOMIZATION_KEYS.contains(&key.as_str()) {
+            continue;
+        }
+        if let Some(value) = installed.get_raw(DESKTOP_ENTRY_GROUP, &key, locale.as_deref()) {
+            debug!("Keeping the customized {} key", key);
+            desktop_file.set_raw(DESKTOP_ENTRY_GROUP, &key, locale.as_deref(), value);
+            kept += 1;
+        }
+    }
+    kept
+}
 
 // Unit tests
 #[cfg(test)]
@@ -191,8 +110,55 @@ mod tests {
 
     #[test]
     fn test_find_desktop_file_location() {
-        let result = find_desktop_file_location().unwrap();
-        println!("Desktop file location: {}", result);
-        assert!(!result.is_empty());
+        let result = find_desktop_file_location(InstallScope::System).unwrap();
+        println!("Desktop file location: {}", result.display());
+        assert!(!result.as_os_str().is_empty());
+    }
+
+    #[test]
+    fn test_keep_customizations() {
+        let installed = DesktopFile::parse(
+            "[Desktop Entry]\nName=App 1.0\nNoDisplay=true\nNotShowIn=KDE;\nX-AppHub=true\n",
+        );
+        let mut desktop_file = DesktopFile::parse("[Desktop Entry]\nName=App 2.0\n");
+
+        assert_eq!(keep_customizations(&installed, &mut desktop_file), 2);
+        assert_eq!(
+            desktop_file.get(DESKTOP_ENTRY_GROUP, "Name"),
+            Some("App 2.0".to_string())
+        );
+        assert_eq!(
+            desktop_file.get_bool(DESKTOP_ENTRY_GROUP, "NoDisplay"),
+            Some(true)
+        );
+        assert_eq!(
+            desktop_file.get_raw(DESKTOP_ENTRY_GROUP, "NotShowIn", None),
+            Some("KDE;")
+        );
+        assert_eq!(desktop_file.get(DESKTOP_ENTRY_GROUP, "X-AppHub"), None);
+    }
+
+    #[test]
+    fn test_collect_desktop_files() {
+        let applications_dir = tempfile::tempdir().unwrap();
+        let vendor_dir = applications_dir.path().join("vendor");
+        fs::create_dir_all(&vendor_dir).unwrap();
+        fs::write(applications_dir.path().join("app.desktop"), "").unwrap();
+        fs::write(applications_dir.path().join("mimeinfo.cache"), "").unwrap();
+        fs::write(vendor_dir.join("tool.desktop"), "").unwrap();
+
+        let desktop_files = collect_desktop_files(applications_dir.path());
+        assert_eq!(
+            desktop_files,
+            vec![
+                applications_dir.path().join("app.desktop"),
+                vendor_dir.join("tool.desktop")
+            ]
+        );
+        let ids: Vec<String> = desktop_files
+            .iter()
+            .map(|path| desktop_file_id(applications_dir.path(), path))
+            .collect();
+        assert_eq!(ids, vec!["app.desktop", "vendor-tool.desktop"]);
     }
-}
\ No newline at end of file
+}
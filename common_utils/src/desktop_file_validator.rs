@@ -0,0 +1,750 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::desktop_file::{DesktopFile, LineContent, DESKTOP_ENTRY_GROUP};
+use crate::models::exec_command::ExecCommand;
+
+/// Keys defined by the Desktop Entry specification
+const KNOWN_KEYS: [&str; 25] = [
+    "Type",
+    "Version",
+    "Name",
+    "GenericName",
+    "NoDisplay",
+    "Comment",
+    "Icon",
+    "Hidden",
+    "OnlyShowIn",
+    "NotShowIn",
+    "DBusActivatable",
+    "TryExec",
+    "Exec",
+    "Path",
+    "Terminal",
+    "Actions",
+    "MimeType",
+    "Categories",
+    "Implements",
+    "Keywords",
+    "StartupNotify",
+    "StartupWMClass",
+    "URL",
+    "PrefersNonDefaultGPU",
+    "SingleMainWindow",
+];
+
+/// Keys which were part of older versions of the specification
+const DEPRECATED_KEYS: [&str; 11] = [
+    "Encoding",
+    "MiniIcon",
+    "TerminalOptions",
+    "Protocols",
+    "Extensions",
+    "BinaryPattern",
+    "MapNotify",
+    "SwallowTitle",
+    "SwallowExec",
+    "SortOrder",
+    "FilePattern",
+];
+
+const BOOLEAN_KEYS: [&str; 7] = [
+    "NoDisplay",
+    "Hidden",
+    "DBusActivatable",
+    "Terminal",
+    "StartupNotify",
+    "PrefersNonDefaultGPU",
+    "SingleMainWindow",
+];
+
+const LIST_KEYS: [&str; 7] = [
+    "OnlyShowIn",
+    "NotShowIn",
+    "Actions",
+    "MimeType",
+    "Categories",
+    "Implements",
+    "Keywords",
+];
+
+const TYPES: [&str; 3] = ["Application", "Link", "Directory"];
+
+const VERSIONS: [&str; 6] = ["1.0", "1.1", "1.2", "1.3", "1.4", "1.5"];
+
+/// Main categories of the Desktop Menu specification
+const MAIN_CATEGORIES: [&str; 13] = [
+    "AudioVideo",
+    "Audio",
+    "Video",
+    "Development",
+    "Education",
+    "Game",
+    "Graphics",
+    "Network",
+    "Office",
+    "Science",
+    "Settings",
+    "System",
+    "Utility",
+];
+
+/// Additional and reserved categories of the Desktop Menu specification
+const ADDITIONAL_CATEGORIES: [&str; 131] = [
+    "Building",
+    "Debugger",
+    "IDE",
+    "GUIDesigner",
+    "Profiling",
+    "RevisionControl",
+    "Translation",
+    "Calendar",
+    "ContactManagement",
+    "Database",
+    "Dictionary",
+    "Chart",
+    "Email",
+    "Finance",
+    "FlowChart",
+    "PDA",
+    "ProjectManagement",
+    "Presentation",
+    "Spreadsheet",
+    "WordProcessor",
+    "2DGraphics",
+    "VectorGraphics",
+    "RasterGraphics",
+    "3DGraphics",
+    "Scanning",
+    "OCR",
+    "Photography",
+    "Publishing",
+    "Viewer",
+    "TextTools",
+    "DesktopSettings",
+    "HardwareSettings",
+    "Printing",
+    "PackageManager",
+    "Dialup",
+    "InstantMessaging",
+    "Chat",
+    "IRCClient",
+    "Feed",
+    "FileTransfer",
+    "HamRadio",
+    "News",
+    "P2P",
+    "RemoteAccess",
+    "Telephony",
+    "TelephonyTools",
+    "VideoConference",
+    "WebBrowser",
+    "WebDevelopment",
+    "Midi",
+    "Mixer",
+    "Sequencer",
+    "Tuner",
+    "TV",
+    "AudioVideoEditing",
+    "Player",
+    "Recorder",
+    "DiscBurning",
+    "ActionGame",
+    "AdventureGame",
+    "ArcadeGame",
+    "BoardGame",
+    "BlocksGame",
+    "CardGame",
+    "KidsGame",
+    "LogicGame",
+    "RolePlaying",
+    "Shooter",
+    "Simulation",
+    "SportsGame",
+    "StrategyGame",
+    "Art",
+    "Construction",
+    "Music",
+    "Languages",
+    "ArtificialIntelligence",
+    "Astronomy",
+    "Biology",
+    "Chemistry",
+    "ComputerScience",
+    "DataVisualization",
+    "Economy",
+    "Electricity",
+    "Geography",
+    "Geology",
+    "Geoscience",
+    "History",
+    "Humanities",
+    "ImageProcessing",
+    "Literature",
+    "Maps",
+    "Math",
+    "NumericalAnalysis",
+    "MedicalSoftware",
+    "Physics",
+    "Robotics",
+    "Spirituality",
+    "Sports",
+    "ParallelComputing",
+    "Amusement",
+    "Archiving",
+    "Compression",
+    "Electronics",
+    "Emulator",
+    "Engineering",
+    "FileTools",
+    "FileManager",
+    "TerminalEmulator",
+    "Filesystem",
+    "Monitor",
+    "Security",
+    "Accessibility",
+    "Calculator",
+    "Clock",
+    "TextEditor",
+    "Documentation",
+    "Adult",
+    "Core",
+    "KDE",
+    "GNOME",
+    "XFCE",
+    "DDE",
+    "GTK",
+    "Qt",
+    "Motif",
+    "Java",
+    "ConsoleOnly",
+    "Screensaver",
+    "TrayIcon",
+    "Applet",
+    "Shell",
+];
+
+/// Field codes of the Exec key
+const FIELD_CODES: [char; 7] = ['f', 'F', 'u', 'U', 'i', 'c', 'k'];
+
+/// Field codes which were part of older versions of the specification
+const DEPRECATED_FIELD_CODES: [char; 6] = ['d', 'D', 'n', 'N', 'v', 'm'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The entry cannot be installed
+    Error,
+    /// The entry works, but does not follow the specification
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A change which makes the entry follow the specification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fix {
+    RemoveKey {
+        group: String,
+        key: String,
+    },
+    /// Add the missing `;` at the end of a list
+    TerminateList {
+        group: String,
+        key: String,
+    },
+    RemoveCategories(Vec<String>),
+    RemoveDeprecatedFieldCodes,
+}
+
+/// A problem found in a desktop file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    /// Line of the problem, numbered from 1
+    pub line: Option<usize>,
+    pub message: String,
+    /// How AppHub fixes the problem, if it can
+    pub fix: Option<Fix>,
+}
+
+impl ValidationIssue {
+    fn error(line: Option<usize>, message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            line,
+            message,
+            fix: None,
+        }
+    }
+
+    fn warning(line: Option<usize>, message: String, fix: Option<Fix>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            line,
+            message,
+            fix,
+        }
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}: {}", line, self.severity, self.message),
+            None => write!(f, "{}: {}", self.severity, self.message),
+        }
+    }
+}
+
+/// Whether any of the issues prevents the installation
+pub fn has_errors(issues: &[ValidationIssue]) -> bool {
+    issues.iter().any(|issue| issue.severity == Severity::Error)
+}
+
+/// Check a desktop file against the rules of desktop-file-validate.
+/// Issues in the structure of the file come first, in line order, followed by the checks of the values.
+pub fn validate_desktop_file(desktop_file: &DesktopFile) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+
+    // Structure: groups, duplicates, and the line of each key of the main group
+    let mut current_group: Option<&str> = None;
+    let mut groups: HashSet<&str> = HashSet::new();
+    let mut keys: HashSet<(&str, Option<&str>)> = HashSet::new();
+    let mut main_group_line = None;
+    let mut key_lines: HashMap<&str, usize> = HashMap::new();
+    for (line, content) in desktop_file.numbered_lines() {
+        match content {
+            LineContent::Blank => {}
+            LineContent::Invalid(text) => {
+                issues.push(ValidationIssue::error(
+                    Some(line),
+                    format!("Invalid line: {}", text),
+                ));
+            }
+            LineContent::Group(name) => {
+                if current_group.is_none() && name != DESKTOP_ENTRY_GROUP {
+                    issues.push(ValidationIssue::error(
+                        Some(line),
+                        format!("The first group must be [{}]", DESKTOP_ENTRY_GROUP),
+                    ));
+                }
+                if !groups.insert(name) {
+                    issues.push(ValidationIssue::error(
+                        Some(line),
+                        format!("Duplicate group [{}]", name),
+                    ));
+                }
+                if name == DESKTOP_ENTRY_GROUP && main_group_line.is_none() {
+                    main_group_line = Some(line);
+                }
+                current_group = Some(name);
+                keys.clear();
+            }
+            LineContent::Entry { key, locale, .. } => {
+                let group = match current_group {
+                    Some(group) => group,
+                    None => {
+                        issues.push(ValidationIssue::error(
+                            Some(line),
+                            format!("Key {} is outside of a group", key),
+                        ));
+                        continue;
+                    }
+                };
+                if !keys.insert((key, locale)) {
+                    issues.push(ValidationIssue::error(
+                        Some(line),
+                        format!("Duplicate key {} in group [{}]", key, group),
+                    ));
+                }
+                if group == DESKTOP_ENTRY_GROUP && locale.is_none() {
+                    key_lines.entry(key).or_insert(line);
+                    if DEPRECATED_KEYS.contains(&key) {
+                        issues.push(ValidationIssue::warning(
+                            Some(line),
+                            format!("Key {} is deprecated", key),
+                            Some(Fix::RemoveKey {
+                                group: group.to_string(),
+                                key: key.to_string(),
+                            }),
+                        ));
+                    } else if !KNOWN_KEYS.contains(&key) && !key.starts_with("X-") {
+                        issues.push(ValidationIssue::warning(
+                            Some(line),
+                            format!("Unknown key {}, extensions must start with X-", key),
+                            None,
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    if main_group_line.is_none() {
+        issues.push(ValidationIssue::error(
+            None,
+            format!("Missing [{}] group", DESKTOP_ENTRY_GROUP),
+        ));
+        return issues;
+    }
+
+    let group = DESKTOP_ENTRY_GROUP;
+    let line_of = |key: &str| key_lines.get(key).copied();
+
+    for key in BOOLEAN_KEYS {
+        if let Some(value) = desktop_file.get_raw(group, key, None) {
+            if desktop_file.get_bool(group, key).is_none() {
+                issues.push(ValidationIssue::error(
+                    line_of(key),
+                    format!("Value of {} must be true or false, found {}", key, value),
+                ));
+            }
+        }
+    }
+
+    for key in LIST_KEYS {
+        if let Some(value) = desktop_file.get_raw(group, key, None) {
+            if !value.is_empty() && !value.ends_with(';') {
+                issues.push(ValidationIssue::warning(
+                    line_of(key),
+                    format!("List {} must end with a semicolon", key),
+                    Some(Fix::TerminateList {
+                        group: group.to_string(),
+                        key: key.to_string(),
+                    }),
+                ));
+            }
+        }
+    }
+
+    if let Some(version) = desktop_file.get(group, "Version") {
+        if !VERSIONS.contains(&version.as_str()) {
+            issues.push(ValidationIssue::warning(
+                line_of("Version"),
+                format!("Unknown specification version {}", version),
+                None,
+            ));
+        }
+    }
+
+    let type_ = desktop_file.get(group, "Type");
+    match &type_ {
+        None => issues.push(ValidationIssue::error(
+            main_group_line,
+            "Missing required key Type".to_string(),
+        )),
+        Some(type_) if !TYPES.contains(&type_.as_str()) => {
+            issues.push(ValidationIssue::error(
+                line_of("Type"),
+                format!("Unknown type {}", type_),
+            ));
+        }
+        Some(_) => {}
+    }
+
+    if desktop_file.get(group, "Name").is_none() {
+        issues.push(ValidationIssue::error(
+            main_group_line,
+            "Missing required key Name".to_string(),
+        ));
+    }
+
+    match desktop_file.get(group, "Exec") {
+        Some(exec) => issues.extend(validate_exec(&exec, line_of("Exec"))),
+        None if type_.as_deref() == Some("Application")
+            && desktop_file.get_bool(group, "DBusActivatable") != Some(true) =>
+        {
+            issues.push(ValidationIssue::error(
+                main_group_line,
+                "Missing key Exec, required by applications".to_string(),
+            ));
+        }
+        None => {}
+    }
+
+    if type_.as_deref() == Some("Link") && desktop_file.get(group, "URL").is_none() {
+        issues.push(ValidationIssue::error(
+            main_group_line,
+            "Missing key URL, required by links".to_string(),
+        ));
+    }
+
+    if let Some(categories) = desktop_file.get_list(group, "Categories") {
+        let unregistered: Vec<String> = categories
+            .iter()
+            .filter(|category| !is_registered_category(category))
+            .cloned()
+            .collect();
+        if !unregistered.is_empty() {
+            issues.push(ValidationIssue::warning(
+                line_of("Categories"),
+                format!("Unregistered categories: {}", unregistered.join(", ")),
+                Some(Fix::RemoveCategories(unregistered)),
+            ));
+        }
+        if !categories
+            .iter()
+            .any(|category| MAIN_CATEGORIES.contains(&category.as_str()))
+        {
+            issues.push(ValidationIssue::warning(
+                line_of("Categories"),
+                "No main category, the app may be hidden from menus".to_string(),
+                None,
+            ));
+        }
+    }
+
+    if let Some(icon) = desktop_file.get(group, "Icon") {
+        let has_extension = [".png", ".svg", ".xpm"]
+            .iter()
+            .any(|extension| icon.ends_with(extension));
+        if !icon.starts_with('/') && has_extension {
+            issues.push(ValidationIssue::warning(
+                line_of("Icon"),
+                format!(
+                    "Icon {} is a theme name and should not have an extension",
+                    icon
+                ),
+                None,
+            ));
+        }
+    }
+
+    for action in desktop_file.get_list(group, "Actions").unwrap_or_default() {
+        let action_group = format!("Desktop Action {}", action);
+        if !desktop_file.has_group(&action_group) {
+            issues.push(ValidationIssue::warning(
+                line_of("Actions"),
+                format!("Action {} has no [{}] group", action, action_group),
+                None,
+            ));
+        } else if desktop_file.get(&action_group, "Name").is_none() {
+            issues.push(ValidationIssue::warning(
+                line_of("Actions"),
+                format!("Action {} has no name", action),
+                None,
+            ));
+        }
+    }
+
+    issues
+}
+
+/// Apply the fixes of the given issues to the desktop file.
+/// Returns the number of applied fixes.
+pub fn fix_desktop_file(desktop_file: &mut DesktopFile, issues: &[ValidationIssue]) -> usize {
+    let mut fixed = 0;
+    for fix in issues.iter().filter_map(|issue| issue.fix.as_ref()) {
+        match fix {
+            Fix::RemoveKey { group, key } => {
+                desktop_file.remove(group, key);
+            }
+            Fix::TerminateList { group, key } => {
+                if let Some(value) = desktop_file.get_raw(group, key, None) {
+                    let value = format!("{};", value);
+                    desktop_file.set_raw(group, key, None, &value);
+                }
+            }
+            Fix::RemoveCategories(unregistered) => {
+                let group = DESKTOP_ENTRY_GROUP;
+                let categories: Vec<String> = desktop_file
+                    .get_list(group, "Categories")
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|category| !unregistered.contains(category))
+                    .collect();
+                if categories.is_empty() {
+                    desktop_file.remove(group, "Categories");
+                } else {
+                    desktop_file.set_list(group, "Categories", &categories);
+                }
+            }
+            Fix::RemoveDeprecatedFieldCodes => {
+                let group = DESKTOP_ENTRY_GROUP;
+                let command = desktop_file
+                    .get(group, "Exec")
+                    .and_then(|exec| ExecCommand::parse(&exec).ok());
+                if let Some(mut command) = command {
+                    command.arguments = command
+                        .arguments
+                        .iter()
+                        .map(|argument| remove_deprecated_field_codes(argument))
+                        .filter(|argument| !argument.is_empty())
+                        .collect();
+                    desktop_file.set(group, "Exec", &command.to_string());
+                }
+            }
+        }
+        fixed += 1;
+    }
+    fixed
+}
+
+fn validate_exec(exec: &str, line: Option<usize>) -> Vec<ValidationIssue> {
+    let command = match ExecCommand::parse(exec) {
+        Ok(command) => command,
+        Err(error) => return vec![ValidationIssue::error(line, error)],
+    };
+
+    let mut issues = Vec::new();
+    let mut file_codes = 0;
+    let mut deprecated = false;
+    for argument in &command.arguments {
+        let mut chars = argument.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                continue;
+            }
+            match chars.next() {
+                Some('%') => {}
+                Some(code) if FIELD_CODES.contains(&code) => {
+                    if matches!(code, 'f' | 'F' | 'u' | 'U') {
+                        file_codes += 1;
+                    }
+                }
+                Some(code) if DEPRECATED_FIELD_CODES.contains(&code) => deprecated = true,
+                Some(code) => issues.push(ValidationIssue::error(
+                    line,
+                    format!("Unknown field code %{} in Exec", code),
+                )),
+                None => issues.push(ValidationIssue::error(
+                    line,
+                    "Exec ends with an incomplete field code".to_string(),
+                )),
+            }
+        }
+    }
+
+    if file_codes > 1 {
+        issues.push(ValidationIssue::error(
+            line,
+            "Exec can contain only one of %f, %F, %u and %U".to_string(),
+        ));
+    }
+    if deprecated {
+        issues.push(ValidationIssue::warning(
+            line,
+            "Exec contains deprecated field codes".to_string(),
+            Some(Fix::RemoveDeprecatedFieldCodes),
+        ));
+    }
+    issues
+}
+
+fn remove_deprecated_field_codes(argument: &str) -> String {
+    let mut result = String::new();
+    let mut chars = argument.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '%' {
+            match chars.peek() {
+                Some(code) if DEPRECATED_FIELD_CODES.contains(code) => {
+                    chars.next();
+                    continue;
+                }
+                Some('%') => {
+                    result.push('%');
+                    chars.next();
+                }
+                _ => {}
+            }
+        }
+        result.push(c);
+    }
+    result
+}
+
+fn is_registered_category(category: &str) -> bool {
+    category.starts_with("X-")
+        || MAIN_CATEGORIES.contains(&category)
+        || ADDITIONAL_CATEGORIES.contains(&category)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issues_of(content: &str) -> Vec<String> {
+        validate_desktop_file(&DesktopFile::parse(content))
+            .iter()
+            .map(|issue| issue.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_entry() {
+        let content = "[Desktop Entry]\n\
+                       Type=Application\n\
+                       Name=Test\n\
+                       Exec=AppRun %U\n\
+                       Categories=Utility;X-Custom;\n\
+                       X-AppImage-Version=1.0\n";
+        assert!(issues_of(content).is_empty());
+    }
+
+    #[test]
+    fn test_errors() {
+        let content = "# comment\n\
+                       [Desktop Entry]\n\
+                       Name=Test\n\
+                       Name=Again\n\
+                       Terminal=yes\n\
+                       Exec=AppRun %f %U %z\n\
+                       not an entry\n";
+        let issues = issues_of(content);
+        assert_eq!(
+            issues,
+            vec![
+                "line 4: error: Duplicate key Name in group [Desktop Entry]",
+                "line 7: error: Invalid line: not an entry",
+                "line 5: error: Value of Terminal must be true or false, found yes",
+                "line 2: error: Missing required key Type",
+                "line 6: error: Unknown field code %z in Exec",
+                "line 6: error: Exec can contain only one of %f, %F, %u and %U",
+            ]
+        );
+        assert!(has_errors(&validate_desktop_file(&DesktopFile::parse(
+            content
+        ))));
+        assert_eq!(
+            issues_of("Name=Test\n"),
+            vec![
+                "line 1: error: Key Name is outside of a group",
+                "error: Missing [Desktop Entry] group",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fix_warnings() {
+        let content = "[Desktop Entry]\n\
+                       Type=Application\n\
+                       Encoding=UTF-8\n\
+                       Name=Test\n\
+                       Exec=AppRun %m %U\n\
+                       Categories=Application;Utility\n\
+                       MimeType=text/plain\n";
+        let mut desktop_file = DesktopFile::parse(content);
+        let issues = validate_desktop_file(&desktop_file);
+        assert!(!has_errors(&issues));
+        assert_eq!(issues.len(), 5);
+
+        assert_eq!(fix_desktop_file(&mut desktop_file, &issues), 5);
+        assert!(validate_desktop_file(&desktop_file).is_empty());
+        assert_eq!(
+            desktop_file.to_string(),
+            "[Desktop Entry]\n\
+             Type=Application\n\
+             Name=Test\n\
+             Exec=AppRun %U\n\
+             Categories=Utility;\n\
+             MimeType=text/plain;\n"
+        );
+    }
+}
@@ -0,0 +1,434 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use log::{debug, info, warn};
+use sha2::{Digest, Sha256};
+
+use crate::signature_helpers::app_image_digest;
+
+/// Largest AppImage downloaded when no other limit is given, 4 GiB
+pub const DEFAULT_MAX_DOWNLOAD_SIZE: u64 = 4 * 1024 * 1024 * 1024;
+
+/// Interval between two progress reports while downloading
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Extension of the partially downloaded files, resumed by the next download of the same URL
+const PARTIAL_EXTENSION: &str = "part";
+
+/// File name used when the URL does not end with one
+const DEFAULT_FILE_NAME: &str = "download.AppImage";
+
+/// Exit status of curl when the server does not support resuming the download
+const CURL_CANNOT_RESUME: i32 = 33;
+
+/// Exit status of curl when the announced size is over the `--max-filesize` limit
+const CURL_FILE_TOO_LARGE: i32 = 63;
+
+/// Limits and checks of a download
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DownloadOptions {
+    /// Largest accepted size, in bytes
+    pub max_size: u64,
+    /// Expected SHA-256 digest of the file, hex encoded
+    pub sha256: Option<String>,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            max_size: DEFAULT_MAX_DOWNLOAD_SIZE,
+            sha256: None,
+        }
+    }
+}
+
+/// Download an AppImage from an HTTP(S) URL into the staging directory, with curl.
+/// A previous interrupted download of the same URL is resumed when the server supports it.
+/// `progress` is called with the downloaded and the total size, when the server tells it.
+/// Returns the path of the downloaded file, after it is checked against the expected digest
+pub fn download_app_image(
+    url: &str,
+    download_dir: &Path,
+    options: &DownloadOptions,
+    mut progress: impl FnMut(u64, Option<u64>),
+) -> Result<PathBuf, String> {
+    let file_name = download_file_name(url)?;
+    check_curl()?;
+
+    // every URL has its own directory, so downloads of files with the same name do not mix
+    let url_digest = hex::encode(Sha256::digest(url.as_bytes()));
+    let staging_dir = download_dir.join(&url_digest[..16]);
+    fs::create_dir_all(&staging_dir)
+        .map_err(|e| format!("Failed to create download directory: {}", e))?;
+    let target = staging_dir.join(&file_name);
+    let partial = staging_dir.join(format!("{}.{}", file_name, PARTIAL_EXTENSION));
+    let headers = staging_dir.join(format!("{}.headers", file_name));
+
+    info!("Downloading {} to {:?}", url, target);
+    let result = match run_curl(url, &partial, &headers, options.max_size, &mut progress) {
+        Err(CurlError::CannotResume) => {
+            warn!("The server cannot resume the download, starting over");
+            let _ = fs::remove_file(&partial);
+            run_curl(url, &partial, &headers, options.max_size, &mut progress)
+        }
+        result => result,
+    };
+    let _ = fs::remove_file(&headers);
+    if let Err(err) = result {
+        // a file over the limit or refused by the server is not worth resuming
+        if !matches!(err, CurlError::Failed(_)) {
+            let _ = fs::remove_file(&partial);
+        }
+        return Err(err.to_string());
+    }
+
+    if let Some(expected) = &options.sha256 {
+        let actual = app_image_digest(&partial, &[])?;
+        if !actual.eq_ignore_ascii_case(expected.trim()) {
+            let _ = fs::remove_file(&partial);
+            return Err(format!(
+                "Checksum mismatch: expected {}, got {}",
+                expected, actual
+            ));
+        }
+        info!("Checksum verified: {}", actual);
+    }
+
+    fs::rename(&partial, &target).map_err(|e| format!("Failed to move download: {}", e))?;
+    info!("Downloaded {:?}", target);
+    Ok(target)
+}
+
+/// Remove a downloaded AppImage once installed, with its staging directory
+pub fn remove_download(path: &Path) -> Result<(), String> {
+    fs::remove_file(path).map_err(|e| format!("Failed to remove download: {}", e))?;
+    if let Some(staging_dir) = path.parent() {
+        // other files are left when a download of the same URL is running
+        let _ = fs::remove_dir(staging_dir);
+    }
+    Ok(())
+}
+
+/// Whether the string is an URL `download_app_image` accepts
+pub fn is_download_url(url: &str) -> bool {
+    url_path(url).is_some()
+}
+
+#[derive(Debug)]
+enum CurlError {
+    CannotResume,
+    TooLarge(u64),
+    /// The server refused the request or the connection failed, with the message of curl
+    Failed(String),
+}
+
+impl std::fmt::Display for CurlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CurlError::CannotResume => write!(f, "The server cannot resume the download"),
+            CurlError::TooLarge(max_size) => {
+                write!(f, "The file is larger than the limit of {} bytes", max_size)
+            }
+            CurlError::Failed(message) => write!(f, "Download failed: {}", message),
+        }
+    }
+}
+
+/// Fail early with a clear message when curl, which runs the downloads, is not installed
+fn check_curl() -> Result<(), String> {
+    let status = Command::new("curl")
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        _ => Err("curl is required to download AppImages, please install it".to_string()),
+    }
+}
+
+/// Run curl until the download completes, reporting the size of the partial file as it grows
+fn run_curl(
+    url: &str,
+    partial: &Path,
+    headers: &Path,
+    max_size: u64,
+    progress: &mut impl FnMut(u64, Option<u64>),
+) -> Result<(), CurlError> {
+    let mut child = Command::new("curl")
+        .args(["--fail", "--location", "--silent", "--show-error"])
+        .args(["--proto", "=http,https", "--proto-redir", "=http,https"])
+        .args(["--continue-at", "-"])
+        .arg("--max-filesize")
+        .arg(max_size.to_string())
+        .arg("--dump-header")
+        .arg(headers)
+        .arg("--output")
+        .arg(partial)
+        .arg("--")
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| CurlError::Failed(format!("failed to run curl: {}", e)))?;
+
+    let status = loop {
+        let downloaded = fs::metadata(partial).map(|m| m.len()).unwrap_or(0);
+        if downloaded > max_size {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(CurlError::TooLarge(max_size));
+        }
+        let total = fs::read_to_string(headers)
+            .ok()
+            .and_then(|headers| total_size(&headers));
+        if total.is_some_and(|total| total > max_size) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(CurlError::TooLarge(max_size));
+        }
+        progress(downloaded, total);
+
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) => thread::sleep(PROGRESS_INTERVAL),
+            Err(e) => return Err(CurlError::Failed(e.to_string())),
+        }
+    };
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| CurlError::Failed(e.to_string()))?;
+    let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    debug!("curl exited with {}: {}", status, message);
+    match status.code() {
+        Some(0) => {
+            let downloaded = fs::metadata(partial).map(|m| m.len()).unwrap_or(0);
+            progress(downloaded, Some(downloaded));
+            Ok(())
+        }
+        Some(CURL_CANNOT_RESUME) => Err(CurlError::CannotResume),
+        Some(CURL_FILE_TOO_LARGE) => Err(CurlError::TooLarge(max_size)),
+        _ => Err(CurlError::Failed(message)),
+    }
+}
+
+/// The size of the whole file from the headers of the last response,
+/// which follows the redirects: the total of `Content-Range`, else `Content-Length`
+fn total_size(headers: &str) -> Option<u64> {
+    let last_response = headers
+        .split("\r\n\r\n")
+        .filter(|response| !response.trim().is_empty())
+        .last()?;
+    let header = |name: &str| {
+        last_response.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim()
+                .eq_ignore_ascii_case(name)
+                .then(|| value.trim().to_string())
+        })
+    };
+
+    match header("Content-Range") {
+        Some(range) => range.rsplit_once('/')?.1.parse().ok(),
+        None => header("Content-Length")?.parse().ok(),
+    }
+}
+
+/// The path of an HTTP(S) URL, without its query and fragment
+fn url_path(url: &str) -> Option<&str> {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))?;
+    let rest = rest.split(['?', '#']).next().unwrap_or_default();
+    let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+    if host.is_empty() || host.chars().any(char::is_whitespace) {
+        return None;
+    }
+    Some(path)
+}
+
+/// The name of the downloaded file: the last segment of the URL path
+fn download_file_name(url: &str) -> Result<String, String> {
+    let path = url_path(url).ok_or(format!("Unsupported URL: {}", url))?;
+    let file_name = path
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches('.');
+    if file_name.is_empty() {
+        Ok(DEFAULT_FILE_NAME.to_string())
+    } else {
+        Ok(file_name.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    /// Serve the content over HTTP with support of `Range` requests,
+    /// returning the base URL and the ranges of the requests
+    fn serve(content: Vec<u8>, requests: usize) -> (String, Arc<Mutex<Vec<Option<u64>>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let ranges = Arc::new(Mutex::new(Vec::new()));
+        let served_ranges = ranges.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().take(requests) {
+                let mut stream = stream.unwrap();
+                let mut start = None;
+                for line in BufReader::new(&stream).lines() {
+                    let line = line.unwrap();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some(range) = line.strip_prefix("Range: bytes=") {
+                        start = range.trim_end_matches('-').parse::<u64>().ok();
+                    }
+                }
+                served_ranges.lock().unwrap().push(start);
+
+                let response = match start {
+                    Some(start) if start as usize >= content.len() => {
+                        "HTTP/1.1 416 Range Not Satisfiable\r\nContent-Length: 0\r\n\r\n"
+                            .as_bytes()
+                            .to_vec()
+                    }
+                    Some(start) => {
+                        let body = &content[start as usize..];
+                        let mut response = format!(
+                            "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\n\
+                             Content-Range: bytes {}-{}/{}\r\n\r\n",
+                            body.len(),
+                            start,
+                            content.len() - 1,
+                            content.len()
+                        )
+                        .into_bytes();
+                        response.extend_from_slice(body);
+                        response
+                    }
+                    None => {
+                        let mut response = format!(
+                            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n",
+                            content.len()
+                        )
+                        .into_bytes();
+                        response.extend_from_slice(&content);
+                        response
+                    }
+                };
+                let _ = stream.write_all(&response);
+            }
+        });
+        (url, ranges)
+    }
+
+    fn content() -> Vec<u8> {
+        (0..100_000u32).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_download_and_verify() {
+        let dir = tempfile::tempdir().unwrap();
+        let (url, _) = serve(content(), 1);
+        let options = DownloadOptions {
+            sha256: Some(hex::encode(Sha256::digest(content()))),
+            ..DownloadOptions::default()
+        };
+
+        let mut reports = Vec::new();
+        let path = download_app_image(
+            &format!("{}/releases/Tool.AppImage?raw=1", url),
+            dir.path(),
+            &options,
+            |downloaded, total| reports.push((downloaded, total)),
+        )
+        .unwrap();
+        assert_eq!(path.file_name().unwrap(), "Tool.AppImage");
+        assert_eq!(fs::read(&path).unwrap(), content());
+        assert_eq!(reports.last(), Some(&(100_000, Some(100_000))));
+
+        remove_download(&path).unwrap();
+        assert!(!path.parent().unwrap().exists());
+    }
+
+    #[test]
+    fn test_resume_download() {
+        let dir = tempfile::tempdir().unwrap();
+        let (url, ranges) = serve(content(), 1);
+        let url = format!("{}/Tool.AppImage", url);
+        let url_digest = hex::encode(Sha256::digest(url.as_bytes()));
+        let staging_dir = dir.path().join(&url_digest[..16]);
+        fs::create_dir_all(&staging_dir).unwrap();
+        fs::write(staging_dir.join("Tool.AppImage.part"), &content()[..40_000]).unwrap();
+
+        let path =
+            download_app_image(&url, dir.path(), &DownloadOptions::default(), |_, _| {}).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), content());
+        assert_eq!(*ranges.lock().unwrap(), vec![Some(40_000)]);
+    }
+
+    #[test]
+    fn test_download_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let (url, _) = serve(content(), 2);
+
+        let options = DownloadOptions {
+            max_size: 1000,
+            sha256: None,
+        };
+        let result = download_app_image(
+            &format!("{}/Big.AppImage", url),
+            dir.path(),
+            &options,
+            |_, _| {},
+        );
+        assert!(result.unwrap_err().contains("larger than the limit"));
+
+        let options = DownloadOptions {
+            sha256: Some("00".repeat(32)),
+            ..DownloadOptions::default()
+        };
+        let url = format!("{}/Tool.AppImage", url);
+        let result = download_app_image(&url, dir.path(), &options, |_, _| {});
+        assert!(result.unwrap_err().contains("Checksum mismatch"));
+
+        // nothing is left to resume
+        let url_digest = hex::encode(Sha256::digest(url.as_bytes()));
+        let staging_dir = dir.path().join(&url_digest[..16]);
+        assert!(!staging_dir.join("Tool.AppImage.part").exists());
+        assert!(!staging_dir.join("Tool.AppImage").exists());
+    }
+
+    #[test]
+    fn test_download_file_name() {
+        assert_eq!(
+            download_file_name("https://example.org/a/Tool-1.0-x86_64.AppImage#top").unwrap(),
+            "Tool-1.0-x86_64.AppImage"
+        );
+        assert_eq!(
+            download_file_name("https://example.org").unwrap(),
+            DEFAULT_FILE_NAME
+        );
+        assert_eq!(
+            download_file_name("http://example.org/..").unwrap(),
+            DEFAULT_FILE_NAME
+        );
+        assert!(download_file_name("ftp://example.org/Tool.AppImage").is_err());
+        assert!(!is_download_url("/home/user/Tool.AppImage"));
+        assert_eq!(
+            total_size("HTTP/1.1 302 Found\r\nContent-Length: 5\r\n\r\nHTTP/1.1 206 Partial Content\r\nContent-Range: bytes 10-99/100\r\n\r\n"),
+            Some(100)
+        );
+    }
+}
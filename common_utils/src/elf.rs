@@ -0,0 +1,286 @@
+use std::io::{Read, Seek, SeekFrom};
+
+const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+const ELF_CLASS_32: u8 = 1;
+const ELF_CLASS_64: u8 = 2;
+const ELF_DATA_LSB: u8 = 1;
+const ELF_DATA_MSB: u8 = 2;
+/// Smallest section header entries, which hold the fields read from them
+const SECTION_HEADER_SIZE_32: u64 = 40;
+const SECTION_HEADER_SIZE_64: u64 = 64;
+
+/// A named section of an ELF file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElfSection {
+    pub name: String,
+    pub offset: u64,
+    pub size: u64,
+}
+
+/// The parts of an ELF header AppHub needs to inspect an AppImage runtime
+#[derive(Debug, Clone)]
+pub struct ElfInfo {
+    /// The raw `e_ident` bytes, which also carry the AppImage magic at offset 8
+    pub ident: [u8; 16],
+    /// Offset of the first byte after the section header table.
+    /// For type-2 AppImages this is where the squashfs payload starts.
+    pub end_offset: u64,
+    pub sections: Vec<ElfSection>,
+}
+
+impl ElfInfo {
+    /// Parse the ELF header and section table from the start of the given reader
+    pub fn read<R: Read + Seek>(reader: &mut R) -> Result<Self, String> {
+        let mut ident = [0u8; 16];
+        reader
+            .seek(SeekFrom::Start(0))
+            .and_then(|_| reader.read_exact(&mut ident))
+            .map_err(|e| format!("Failed to read ELF header: {}", e))?;
+
+        if ident[0..4] != ELF_MAGIC {
+            return Err("Not an ELF file".to_string());
+        }
+        let is_64 = match ident[4] {
+            ELF_CLASS_32 => false,
+            ELF_CLASS_64 => true,
+            class => return Err(format!("Unsupported ELF class: {}", class)),
+        };
+        let little_endian = match ident[5] {
+            ELF_DATA_LSB => true,
+            ELF_DATA_MSB => false,
+            data => return Err(format!("Unsupported ELF data encoding: {}", data)),
+        };
+
+        // Read the remaining part of the file header
+        let header_size = if is_64 { 64 } else { 52 };
+        let mut header = vec![0u8; header_size];
+        reader
+            .seek(SeekFrom::Start(0))
+            .and_then(|_| reader.read_exact(&mut header))
+            .map_err(|e| format!("Failed to read ELF header: {}", e))?;
+        let fields = Fields { little_endian };
+
+        let (sh_offset, sh_entry_size, sh_count, sh_str_index) = if is_64 {
+            (
+                fields.u64(&header[0x28..]),
+                fields.u16(&header[0x3a..]) as u64,
+                fields.u16(&header[0x3c..]) as u64,
+                fields.u16(&header[0x3e..]) as usize,
+            )
+        } else {
+            (
+                fields.u32(&header[0x20..]) as u64,
+                fields.u16(&header[0x2e..]) as u64,
+                fields.u16(&header[0x30..]) as u64,
+                fields.u16(&header[0x32..]) as usize,
+            )
+        };
+
+        let min_entry_size = if is_64 {
+            SECTION_HEADER_SIZE_64
+        } else {
+            SECTION_HEADER_SIZE_32
+        };
+        if sh_count > 0 && sh_entry_size < min_entry_size {
+            return Err(format!(
+                "Invalid ELF section header size: {}",
+                sh_entry_size
+            ));
+        }
+        let file_len = stream_len(reader)?;
+        let end_offset = sh_entry_size
+            .checked_mul(sh_count)
+            .and_then(|table_size| sh_offset.checked_add(table_size))
+            .filter(|end_offset| *end_offset <= file_len)
+            .ok_or("ELF section header table is past the end of the file")?;
+
+        // Read the raw section headers
+        let mut raw_sections = Vec::with_capacity(sh_count as usize);
+        let mut entry = vec![0u8; sh_entry_size as usize];
+        for index in 0..sh_count {
+            reader
+                .seek(SeekFrom::Start(sh_offset + index * sh_entry_size))
+                .and_then(|_| reader.read_exact(&mut entry))
+                .map_err(|e| format!("Failed to read ELF section header: {}", e))?;
+            let (name, offset, size) = if is_64 {
+                (
+                    fields.u32(&entry[0x00..]),
+                    fields.u64(&entry[0x18..]),
+                    fields.u64(&entry[0x20..]),
+                )
+            } else {
+                (
+                    fields.u32(&entry[0x00..]),
+                    fields.u32(&entry[0x10..]) as u64,
+                    fields.u32(&entry[0x14..]) as u64,
+                )
+            };
+            raw_sections.push((name, offset, size));
+        }
+
+        // Resolve the section names through the section name string table
+        let names = match raw_sections.get(sh_str_index) {
+            Some(&(_, offset, size)) => {
+                check_in_file(offset, size, file_len)
+                    .map_err(|e| format!("Invalid ELF section names: {}", e))?;
+                let mut names = vec![0u8; size as usize];
+                reader
+                    .seek(SeekFrom::Start(offset))
+                    .and_then(|_| reader.read_exact(&mut names))
+                    .map_err(|e| format!("Failed to read ELF section names: {}", e))?;
+                names
+            }
+            None => Vec::new(),
+        };
+
+        let sections = raw_sections
+            .into_iter()
+            .map(|(name, offset, size)| ElfSection {
+                name: read_c_string(&names, name as usize),
+                offset,
+                size,
+            })
+            .collect();
+
+        Ok(Self {
+            ident,
+            end_offset,
+            sections,
+        })
+    }
+
+    /// Find a section by name
+    pub fn section(&self, name: &str) -> Option<&ElfSection> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+}
+
+/// Read the content of a section from the given reader
+pub fn read_section<R: Read + Seek>(
+    reader: &mut R,
+    section: &ElfSection,
+) -> Result<Vec<u8>, String> {
+    check_in_file(section.offset, section.size, stream_len(reader)?)
+        .map_err(|e| format!("Invalid ELF section {}: {}", section.name, e))?;
+    let mut content = vec![0u8; section.size as usize];
+    reader
+        .seek(SeekFrom::Start(section.offset))
+        .and_then(|_| reader.read_exact(&mut content))
+        .map_err(|e| format!("Failed to read ELF section {}: {}", section.name, e))?;
+    Ok(content)
+}
+
+/// The length of the file behind the reader
+fn stream_len<R: Seek>(reader: &mut R) -> Result<u64, String> {
+    reader
+        .seek(SeekFrom::End(0))
+        .map_err(|e| format!("Failed to read ELF file size: {}", e))
+}
+
+/// Refuse the ranges which do not fit in the file, before anything is allocated for them
+fn check_in_file(offset: u64, size: u64, file_len: u64) -> Result<(), String> {
+    match offset.checked_add(size) {
+        Some(end) if end <= file_len => Ok(()),
+        _ => Err(format!(
+            "{} bytes at offset {} are past the end of the file",
+            size, offset
+        )),
+    }
+}
+
+struct Fields {
+    little_endian: bool,
+}
+
+impl Fields {
+    fn u16(&self, bytes: &[u8]) -> u16 {
+        let raw = [bytes[0], bytes[1]];
+        if self.little_endian {
+            u16::from_le_bytes(raw)
+        } else {
+            u16::from_be_bytes(raw)
+        }
+    }
+
+    fn u32(&self, bytes: &[u8]) -> u32 {
+        let raw = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        if self.little_endian {
+            u32::from_le_bytes(raw)
+        } else {
+            u32::from_be_bytes(raw)
+        }
+    }
+
+    fn u64(&self, bytes: &[u8]) -> u64 {
+        let mut raw = [0u8; 8];
+        raw.copy_from_slice(&bytes[..8]);
+        if self.little_endian {
+            u64::from_le_bytes(raw)
+        } else {
+            u64::from_be_bytes(raw)
+        }
+    }
+}
+
+fn read_c_string(table: &[u8], start: usize) -> String {
+    if start >= table.len() {
+        return String::new();
+    }
+    let end = table[start..]
+        .iter()
+        .position(|&b| b == 0)
+        .map(|len| start + len)
+        .unwrap_or(table.len());
+    String::from_utf8_lossy(&table[start..end]).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::build_elf;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_read_sections_and_end_offset() {
+        let elf = build_elf(
+            *b"AI\x02",
+            &[(".upd_info", b"zsync|http://example.com/app.zsync")],
+        );
+        let info = ElfInfo::read(&mut Cursor::new(&elf)).unwrap();
+
+        assert_eq!(info.end_offset, elf.len() as u64);
+        assert_eq!(&info.ident[8..11], b"AI\x02");
+
+        let section = info.section(".upd_info").unwrap();
+        let content = read_section(&mut Cursor::new(&elf), section).unwrap();
+        assert_eq!(content, b"zsync|http://example.com/app.zsync");
+        assert!(info.section(".sig_key").is_none());
+    }
+
+    #[test]
+    fn test_rejects_malformed_headers() {
+        let elf = build_elf(*b"AI\x02", &[(".upd_info", b"zsync|http://example.com")]);
+
+        // section headers too small to hold their fields
+        let mut small_entries = elf.clone();
+        small_entries[0x3a..0x3c].copy_from_slice(&8u16.to_le_bytes());
+        assert!(ElfInfo::read(&mut Cursor::new(&small_entries)).is_err());
+
+        // section header table past the end of the file
+        let mut far_table = elf.clone();
+        far_table[0x28..0x30].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(ElfInfo::read(&mut Cursor::new(&far_table)).is_err());
+
+        // section larger than the file
+        let info = ElfInfo::read(&mut Cursor::new(&elf)).unwrap();
+        let mut section = info.section(".upd_info").unwrap().clone();
+        section.size = u64::MAX;
+        assert!(read_section(&mut Cursor::new(&elf), &section).is_err());
+    }
+
+    #[test]
+    fn test_rejects_non_elf() {
+        let result = ElfInfo::read(&mut Cursor::new(b"hsqs not an elf file".to_vec()));
+        assert!(result.is_err());
+    }
+}
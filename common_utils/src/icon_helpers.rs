@@ -0,0 +1,485 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use log::{debug, info};
+
+use crate::desktop_file_helpers::find_desktop_file_location;
+use crate::models::install_scope::InstallScope;
+
+/// Icon extensions, in order of preference
+const ICON_EXTENSIONS: [&str; 3] = ["svg", "png", "xpm"];
+
+/// Theme directory holding the icons of the application
+const HICOLOR_DIR: &str = "usr/share/icons/hicolor";
+
+/// Legacy icon directory
+const PIXMAPS_DIR: &str = "usr/share/pixmaps";
+
+/// Sizes of the bitmap directories of the hicolor theme
+const HICOLOR_SIZES: [u32; 13] = [16, 22, 24, 32, 36, 48, 64, 72, 96, 128, 192, 256, 512];
+
+/// Image formats AppHub can install as an application icon
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IconFormat {
+    Svg,
+    Png,
+    Xpm,
+}
+
+impl IconFormat {
+    /// Detect the format of an icon from its content
+    pub fn detect(content: &[u8]) -> Option<Self> {
+        if content.starts_with(b"\x89PNG\r\n\x1a\n") {
+            return Some(Self::Png);
+        }
+        let head = String::from_utf8_lossy(&content[..content.len().min(1024)]).to_string();
+        if head.contains("/* XPM */") {
+            Some(Self::Xpm)
+        } else if head.contains("<svg") {
+            Some(Self::Svg)
+        } else {
+            None
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Svg => "svg",
+            Self::Png => "png",
+            Self::Xpm => "xpm",
+        }
+    }
+}
+
+/// Why an icon candidate was chosen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IconReason {
+    /// The file named by the `Icon=` key, in the root of the AppImage
+    IconKey,
+    /// The `.DirIcon` of the AppImage
+    DirIcon,
+    /// A scalable icon from the hicolor theme
+    ThemeScalable,
+    /// The biggest bitmap icon from the hicolor theme
+    ThemeBitmap { size: u32 },
+    /// An icon from the legacy pixmaps directory
+    Pixmap,
+}
+
+impl fmt::Display for IconReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IconKey => write!(f, "matches the Icon= key of the desktop entry"),
+            Self::DirIcon => write!(f, "the AppImage .DirIcon"),
+            Self::ThemeScalable => write!(f, "scalable icon from the hicolor theme"),
+            Self::ThemeBitmap { size } => {
+                write!(f, "largest hicolor theme icon ({}x{} pixels)", size, size)
+            }
+            Self::Pixmap => write!(f, "icon from the pixmaps directory"),
+        }
+    }
+}
+
+/// The icon chosen for an application
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IconChoice {
+    pub path: PathBuf,
+    pub format: IconFormat,
+    pub reason: IconReason,
+}
+
+/// Find the icon of the application in the extracted AppImage.
+/// Candidates are, in order: the file named by the `Icon=` key in the root of the AppImage,
+/// the `.DirIcon`, the hicolor theme icons (scalable first, then the biggest bitmap in pixels)
+/// and the pixmaps directory.
+pub fn resolve_icon(
+    squashfs_root_path: &Path,
+    icon_name: Option<&str>,
+) -> Result<IconChoice, String> {
+    let icon_name = icon_name
+        .map(icon_base_name)
+        .filter(|name| !name.is_empty());
+
+    if let Some(icon_name) = &icon_name {
+        if let Some(choice) = find_named_icon(squashfs_root_path, icon_name, IconReason::IconKey) {
+            return Ok(choice);
+        }
+    }
+
+    let dir_icon = squashfs_root_path.join(".DirIcon");
+    if let Some(format) = read_format(&dir_icon) {
+        return Ok(IconChoice {
+            path: dir_icon,
+            format,
+            reason: IconReason::DirIcon,
+        });
+    }
+
+    if let Some(icon_name) = &icon_name {
+        if let Some(choice) = find_theme_icon(&squashfs_root_path.join(HICOLOR_DIR), icon_name) {
+            return Ok(choice);
+        }
+        if let Some(choice) = find_named_icon(
+            &squashfs_root_path.join(PIXMAPS_DIR),
+            icon_name,
+            IconReason::Pixmap,
+        ) {
+            return Ok(choice);
+        }
+    }
+
+    Err(format!(
+        "No icon found for {}",
+        icon_name.as_deref().unwrap_or("the AppImage")
+    ))
+}
+
+/// The `Icon=` key holds a name, but some desktop entries use a file name or a path
+pub fn icon_base_name(icon: &str) -> String {
+    let file_name = icon.rsplit('/').next().unwrap_or(icon);
+    match file_name.rsplit_once('.') {
+        Some((name, extension)) if ICON_EXTENSIONS.contains(&extension.to_lowercase().as_str()) => {
+            name.to_string()
+        }
+        _ => file_name.to_string(),
+    }
+}
+
+fn find_named_icon(dir: &Path, icon_name: &str, reason: IconReason) -> Option<IconChoice> {
+    ICON_EXTENSIONS.iter().find_map(|extension| {
+        let path = dir.join(format!("{}.{}", icon_name, extension));
+        let format = read_format(&path)?;
+        Some(IconChoice {
+            path,
+            format,
+            reason: reason.clone(),
+        })
+    })
+}
+
+/// Find the icon in the hicolor theme: the scalable one, or else the largest bitmap
+fn find_theme_icon(theme_dir: &Path, icon_name: &str) -> Option<IconChoice> {
+    let scalable_dir = theme_dir.join("scalable").join("apps");
+    if let Some(choice) = find_named_icon(&scalable_dir, icon_name, IconReason::ThemeScalable) {
+        if choice.format == IconFormat::Svg {
+            return Some(choice);
+        }
+    }
+
+    // other directories, like `symbolic`, hold icons which are not meant to represent the app
+    let mut size_dirs: Vec<String> = fs::read_dir(theme_dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|size_dir| size_dir != "scalable" && is_size_dir(size_dir))
+        .collect();
+    size_dirs.sort();
+
+    let mut best: Option<(u32, IconChoice)> = None;
+    for size_dir in size_dirs {
+        let apps_dir = theme_dir.join(size_dir).join("apps");
+        let choice = match find_named_icon(&apps_dir, icon_name, IconReason::ThemeScalable) {
+            Some(choice) => choice,
+            None => continue,
+        };
+
+        let size = match choice.format {
+            IconFormat::Png => png_size(&choice.path),
+            _ => None,
+        }
+        .unwrap_or(0);
+        debug!("Theme icon candidate {:?}: {} pixels", choice.path, size);
+        if best.as_ref().is_none_or(|(best_size, _)| size > *best_size) {
+            best = Some((size, choice));
+        }
+    }
+
+    best.map(|(size, choice)| IconChoice {
+        reason: IconReason::ThemeBitmap { size },
+        ..choice
+    })
+}
+
+fn read_format(path: &Path) -> Option<IconFormat> {
+    if !path.is_file() {
+        return None;
+    }
+    let content = fs::read(path).ok()?;
+    IconFormat::detect(&content)
+}
+
+/// Read the width of a PNG image from its header
+fn png_size(path: &Path) -> Option<u32> {
+    let content = fs::read(path).ok()?;
+    // The IHDR chunk follows the 8 bytes signature, its data starts with the width
+    let width = content.get(16..20)?;
+    Some(u32::from_be_bytes([width[0], width[1], width[2], width[3]]))
+}
+
+/// Read the width of a XPM image from its values line (`"<width> <height> <colors> <chars>"`)
+fn xpm_size(path: &Path) -> Option<u32> {
+    let content = fs::read_to_string(path).ok()?;
+    let values = content[content.find('{')?..].split('"').nth(1)?;
+    values.split_whitespace().next()?.parse().ok()
+}
+
+/// Find the hicolor icon theme directory next to the applications directory of the scope,
+/// `/usr/share/icons/hicolor` or `$XDG_DATA_HOME/icons/hicolor`
+pub fn find_icon_theme_location(scope: InstallScope) -> Result<PathBuf, &'static str> {
+    let applications_dir = find_desktop_file_location(scope)?;
+    match applications_dir.parent() {
+        Some(data_dir) => Ok(data_dir.join("icons").join("hicolor")),
+        None => Err("Failed to find the icon theme directory"),
+    }
+}
+
+/// An icon of the AppImage, with the theme directory it belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThemeIcon {
+    /// `scalable` or a `<size>x<size>` directory of the hicolor theme
+    pub size_dir: String,
+    pub path: PathBuf,
+    pub format: IconFormat,
+}
+
+impl ThemeIcon {
+    /// Path of the icon once installed in the given theme directory under the given name
+    pub fn installed_path(&self, theme_dir: &Path, icon_name: &str) -> PathBuf {
+        theme_dir.join(&self.size_dir).join("apps").join(format!(
+            "{}.{}",
+            icon_name,
+            self.format.extension()
+        ))
+    }
+}
+
+/// Collect every size of the app icon: all the sizes shipped in the hicolor theme of the AppImage
+/// for the icon name, plus the resolved icon when it comes from outside the theme
+pub fn collect_theme_icons(
+    squashfs_root_path: &Path,
+    icon_choice: &IconChoice,
+    icon_name: Option<&str>,
+) -> Vec<ThemeIcon> {
+    let mut icons: BTreeMap<String, ThemeIcon> = BTreeMap::new();
+
+    if let Some(icon_name) = icon_name.map(icon_base_name) {
+        let theme_dir = squashfs_root_path.join(HICOLOR_DIR);
+        let size_dirs = fs::read_dir(&theme_dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.file_name().to_string_lossy().to_string())
+                    .filter(|size_dir| is_size_dir(size_dir))
+                    .collect::<Vec<String>>()
+            })
+            .unwrap_or_default();
+        for size_dir in size_dirs {
+            let apps_dir = theme_dir.join(&size_dir).join("apps");
+            if let Some(icon) = find_named_icon(&apps_dir, &icon_name, IconReason::ThemeScalable) {
+                icons.insert(
+                    size_dir.clone(),
+                    ThemeIcon {
+                        size_dir,
+                        path: icon.path,
+                        format: icon.format,
+                    },
+                );
+            }
+        }
+    }
+
+    // The resolved icon is preferred over the theme icon of the same size
+    if !matches!(
+        icon_choice.reason,
+        IconReason::ThemeScalable | IconReason::ThemeBitmap { .. }
+    ) {
+        let size_dir = match icon_choice.format {
+            IconFormat::Svg => "scalable".to_string(),
+            IconFormat::Png => theme_size_dir(png_size(&icon_choice.path)),
+            IconFormat::Xpm => theme_size_dir(xpm_size(&icon_choice.path)),
+        };
+        icons.insert(
+            size_dir.clone(),
+            ThemeIcon {
+                size_dir,
+                path: icon_choice.path.clone(),
+                format: icon_choice.format,
+            },
+        );
+    }
+
+    icons.into_values().collect()
+}
+
+/// `scalable`, `<size>x<size>` or `<size>x<size>@<scale>`
+fn is_size_dir(name: &str) -> bool {
+    if name == "scalable" {
+        return true;
+    }
+    let size = name.split('@').next().unwrap_or(name);
+    match size.split_once('x') {
+        Some((width, height)) => {
+            width == height && !width.is_empty() && width.chars().all(|c| c.is_ascii_digit())
+        }
+        None => false,
+    }
+}
+
+/// The hicolor directory for a bitmap icon: the biggest standard size not bigger than the icon
+fn theme_size_dir(size: Option<u32>) -> String {
+    let size = size.unwrap_or(0);
+    let size = HICOLOR_SIZES
+        .iter()
+        .rev()
+        .find(|standard_size| **standard_size <= size)
+        .unwrap_or(&HICOLOR_SIZES[0]);
+    format!("{}x{}", size, size)
+}
+
+/// Refresh the icon cache of the theme, if the theme has one
+pub fn update_icon_cache(theme_dir: &Path) {
+    if !theme_dir.join("icon-theme.cache").exists() {
+        return;
+    }
+    match Command::new("gtk-update-icon-cache")
+        .arg("--force")
+        .arg("--ignore-theme-index")
+        .arg(theme_dir)
+        .output()
+    {
+        Ok(output) if output.status.success() => info!("Updated icon cache of {:?}", theme_dir),
+        Ok(output) => debug!(
+            "Failed to update icon cache: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ),
+        Err(e) => debug!("Failed to run gtk-update-icon-cache: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(size: u32, padding: usize) -> Vec<u8> {
+        let mut content = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        content.extend_from_slice(&size.to_be_bytes());
+        content.extend_from_slice(&size.to_be_bytes());
+        content.resize(content.len() + padding, 0);
+        content
+    }
+
+    fn write(root: &Path, path: &str, content: &[u8]) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_prefers_icon_key_over_bigger_files() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "splash.png", &png(1024, 100_000));
+        write(dir.path(), "app.png", &png(256, 0));
+        write(dir.path(), ".DirIcon", &png(128, 0));
+
+        let choice = resolve_icon(dir.path(), Some("app")).unwrap();
+        assert_eq!(choice.path, dir.path().join("app.png"));
+        assert_eq!(choice.reason, IconReason::IconKey);
+
+        let choice = resolve_icon(dir.path(), Some("missing")).unwrap();
+        assert_eq!(choice.path, dir.path().join(".DirIcon"));
+        assert_eq!(choice.format, IconFormat::Png);
+    }
+
+    #[test]
+    fn test_theme_icons_by_pixel_size() {
+        let dir = tempfile::tempdir().unwrap();
+        write(
+            dir.path(),
+            "usr/share/icons/hicolor/64x64/apps/app.png",
+            &png(64, 50_000),
+        );
+        write(
+            dir.path(),
+            "usr/share/icons/hicolor/256x256/apps/app.png",
+            &png(256, 0),
+        );
+
+        let choice = resolve_icon(dir.path(), Some("app")).unwrap();
+        assert_eq!(
+            choice.path,
+            dir.path()
+                .join("usr/share/icons/hicolor/256x256/apps/app.png")
+        );
+        assert_eq!(choice.reason, IconReason::ThemeBitmap { size: 256 });
+
+        // only the scalable directory holds scalable icons
+        write(
+            dir.path(),
+            "usr/share/icons/hicolor/symbolic/apps/app.svg",
+            b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>",
+        );
+        let choice = resolve_icon(dir.path(), Some("app")).unwrap();
+        assert_eq!(choice.reason, IconReason::ThemeBitmap { size: 256 });
+
+        write(
+            dir.path(),
+            "usr/share/icons/hicolor/scalable/apps/app.svg",
+            b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>",
+        );
+        let choice = resolve_icon(dir.path(), Some("app.png")).unwrap();
+        assert_eq!(choice.format, IconFormat::Svg);
+        assert_eq!(choice.reason, IconReason::ThemeScalable);
+    }
+
+    #[test]
+    fn test_collect_theme_icons() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "app.png", &png(200, 0));
+        write(
+            dir.path(),
+            "usr/share/icons/hicolor/32x32/apps/app.png",
+            &png(32, 0),
+        );
+        write(
+            dir.path(),
+            "usr/share/icons/hicolor/128x128/apps/app.png",
+            &png(128, 0),
+        );
+        write(
+            dir.path(),
+            "usr/share/icons/hicolor/128x128/apps/other.png",
+            &png(128, 0),
+        );
+        write(
+            dir.path(),
+            "usr/share/icons/hicolor/symbolic/apps/app.svg",
+            b"<svg/>",
+        );
+
+        let choice = resolve_icon(dir.path(), Some("app")).unwrap();
+        let icons = collect_theme_icons(dir.path(), &choice, Some("app"));
+        let installed: Vec<PathBuf> = icons
+            .iter()
+            .map(|icon| icon.installed_path(Path::new("/hicolor"), "apphub-app"))
+            .collect();
+        assert_eq!(
+            installed,
+            vec![
+                PathBuf::from("/hicolor/128x128/apps/apphub-app.png"),
+                PathBuf::from("/hicolor/192x192/apps/apphub-app.png"),
+                PathBuf::from("/hicolor/32x32/apps/apphub-app.png"),
+            ]
+        );
+        assert_eq!(icons[1].path, dir.path().join("app.png"));
+    }
+
+    #[test]
+    fn test_no_icon() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "splash.png", &png(1024, 0));
+        assert!(resolve_icon(dir.path(), Some("app")).is_err());
+    }
+}
@@ -0,0 +1,108 @@
+const MAX_SYMLINK_DEPTH: usize = 16;
+
+/// Largest file read out of an image: only desktop entries, icons and metadata are extracted
+pub const MAX_FILE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// The kind of an entry inside a file system image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    Directory,
+    File,
+    Symlink,
+    Other,
+}
+
+/// A file system entry of an AppImage payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageEntry {
+    /// Path relative to the image root, without leading slash
+    pub path: String,
+    pub kind: EntryKind,
+    /// File size for regular files, target length for symlinks
+    pub size: u64,
+}
+
+/// Read-only access to the file system embedded in an AppImage (squashfs or ISO 9660)
+pub trait ImageFs {
+    /// All entries of the image, in directory order
+    fn entries(&self) -> &[ImageEntry];
+
+    /// Return the kind of the given entry, without following symlinks
+    fn kind(&self, path: &str) -> Option<EntryKind>;
+
+    /// Return the target of a symlink, as stored in the image
+    fn read_link(&self, path: &str) -> Result<String, String>;
+
+    /// Read the content of a regular file, without following symlinks
+    fn read_regular_file(&mut self, path: &str) -> Result<Vec<u8>, String>;
+
+    /// Check whether the given path exists in the image
+    fn exists(&self, path: &str) -> bool {
+        self.kind(path).is_some()
+    }
+
+    /// Resolve symlinks until a non symlink entry is reached.
+    /// Targets are always resolved inside the image: absolute targets are relative to its root.
+    fn resolve(&self, path: &str) -> Result<String, String> {
+        let mut current = normalize(path);
+        for _ in 0..MAX_SYMLINK_DEPTH {
+            match self.kind(&current) {
+                Some(EntryKind::Symlink) => {
+                    let target = self.read_link(&current)?;
+                    current = if target.starts_with('/') {
+                        normalize(&target)
+                    } else {
+                        let parent = match current.rfind('/') {
+                            Some(position) => &current[..position],
+                            None => "",
+                        };
+                        normalize(&format!("{}/{}", parent, target))
+                    };
+                }
+                Some(_) => return Ok(current),
+                None => return Err(format!("File not found in image: {}", current)),
+            }
+        }
+        Err(format!("Too many levels of symbolic links: {}", path))
+    }
+
+    /// Read the whole content of a regular file, following symlinks
+    fn read_file(&mut self, path: &str) -> Result<Vec<u8>, String> {
+        let resolved = self.resolve(path)?;
+        match self.kind(&resolved) {
+            Some(EntryKind::File) => self.read_regular_file(&resolved),
+            _ => Err(format!("Not a regular file: {}", path)),
+        }
+    }
+}
+
+/// Normalize a path inside the image: no leading slash, no `.` and resolved `..` components
+pub fn normalize(path: &str) -> String {
+    let mut components: Vec<&str> = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+    components.join("/")
+}
+
+/// Check that a file name read from an image can be safely joined to a path
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains('/') && !name.contains('\0')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("/usr/./share//icons/../app"), "usr/share/app");
+        assert_eq!(normalize("../../etc/passwd"), "etc/passwd");
+    }
+}
@@ -0,0 +1,278 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use log::{debug, info, warn};
+
+use crate::app_id_helpers::legacy_app_id;
+use crate::desktop_file::DesktopFile;
+use crate::desktop_file_builder::DesktopFileBuilder;
+use crate::desktop_file_helpers::find_desktop_files;
+use crate::models::app_identity::AppIdentity;
+use crate::models::app_stream_metadata::AppStreamMetadata;
+use crate::models::install_record::InstallRecord;
+use crate::models::install_scope::InstallScope;
+use crate::models::signature_status::SignatureStatus;
+use crate::portable_helpers::existing_portable_dirs;
+use crate::signature_helpers::app_image_digest;
+use crate::version_helpers::app_version;
+
+/// Version of the database format
+const DATABASE_VERSION: u32 = 1;
+
+/// File of the database in the state directory of the scope
+const DATABASE_FILE: &str = "installed.json";
+
+/// The apps installed by AppHub in a scope
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct InstallDatabase {
+    pub version: u32,
+    pub apps: Vec<InstallRecord>,
+}
+
+impl Default for InstallDatabase {
+    fn default() -> Self {
+        Self {
+            version: DATABASE_VERSION,
+            apps: Vec::new(),
+        }
+    }
+}
+
+impl InstallDatabase {
+    /// Path of the database of the scope, in its state directory
+    pub fn path(scope: InstallScope) -> Result<PathBuf, &'static str> {
+        Ok(scope.state_dir()?.join(DATABASE_FILE))
+    }
+
+    /// Open the database of the scope.
+    /// The first time, the apps installed before the database existed are adopted from their desktop entries
+    pub fn open(scope: InstallScope) -> Result<Self, String> {
+        let path = Self::path(scope)?;
+        if path.exists() {
+            return Self::load(&path);
+        }
+
+        let entries = find_desktop_files(&[scope])
+            .into_iter()
+            .map(|(_, path)| path)
+            .collect::<Vec<PathBuf>>();
+        let database = Self::adopt(&entries, scope);
+        info!(
+            "Adopted {} installed apps in the {} database",
+            database.apps.len(),
+            scope
+        );
+        // the system database can only be written by root, it is adopted again until then
+        if let Err(e) = database.save(&path) {
+            warn!("Failed to save the {} database: {}", scope, e);
+        }
+        Ok(database)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content =
+            fs::read_to_string(path).map_err(|e| format!("Failed to read database: {}", e))?;
+        let database: Self =
+            serde_json::from_str(&content).map_err(|e| format!("Invalid database: {}", e))?;
+        if database.version > DATABASE_VERSION {
+            return Err(format!(
+                "Unsupported database version: {}",
+                database.version
+            ));
+        }
+        Ok(database)
+    }
+
+    /// Write the database, replacing the previous one atomically
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create database directory: {}", e))?;
+        }
+        let staged = path.with_extension("json.tmp");
+        fs::write(&staged, self.to_json()?)
+            .map_err(|e| format!("Failed to write database: {}", e))?;
+        fs::rename(&staged, path).map_err(|e| format!("Failed to write database: {}", e))
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("Invalid database: {}", e))
+    }
+
+    pub fn get(&self, id: &str) -> Option<&InstallRecord> {
+        self.apps.iter().find(|record| record.id == id)
+    }
+
+    /// Find the installed app an AppImage is a version of, by id,
+    /// or by name for the apps adopted from the desktop entries written before the ids existed
+    pub fn find_installed(&self, identity: &AppIdentity) -> Option<&InstallRecord> {
+        self.get(&identity.id).or_else(|| {
+            self.get(&legacy_app_id(&identity.name))
+                .filter(|record| record.name == identity.name)
+        })
+    }
+
+    /// Add the record of an app, replacing the previous record with the same id
+    pub fn upsert(&mut self, record: InstallRecord) {
+        match self.apps.iter_mut().find(|app| app.id == record.id) {
+            Some(app) => *app = record,
+            None => self.apps.push(record),
+        }
+    }
+
+    pub fn remove(&mut self, id: &str) -> Option<InstallRecord> {
+        let index = self.apps.iter().position(|record| record.id == id)?;
+        Some(self.apps.remove(index))
+    }
+
+    /// Build the records of the apps installed by AppHub from their desktop entries.
+    /// Entries which were not written by AppHub are skipped
+    pub fn adopt(desktop_entries: &[PathBuf], scope: InstallScope) -> Self {
+        let mut database = Self::default();
+        for entry_path in desktop_entries {
+            match adopt_entry(entry_path, scope) {
+                Some(record) if database.get(&record.id).is_none() => database.apps.push(record),
+                Some(record) => warn!("Ignoring duplicate app {:?}: {}", entry_path, record.id),
+                None => debug!("Skipping {:?}", entry_path),
+            }
+        }
+        database
+    }
+}
+
+fn adopt_entry(entry_path: &PathBuf, scope: InstallScope) -> Option<InstallRecord> {
+    let desktop_entry = DesktopFileBuilder::from_desktop_entry_path(entry_path, true).ok()?;
+    let name = desktop_entry.name()?;
+    let command = desktop_entry.exec_command()?;
+    let app_image_path = PathBuf::from(&command.program);
+    let split_paths = |paths: Option<String>| -> Vec<PathBuf> {
+        paths
+            .unwrap_or_default()
+            .split(';')
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .collect()
+    };
+
+    // older installs only know the path of their icon
+    let mut icon_paths = split_paths(desktop_entry.icons());
+    if icon_paths.is_empty() {
+        icon_paths.extend(
+            desktop_entry
+                .icon()
+                .map(PathBuf::from)
+                .filter(|icon| icon.is_absolute()),
+        );
+    }
+
+    // the installed entry keeps the version keys of the original one
+    let metainfo_path = desktop_entry.metainfo().map(PathBuf::from);
+    let metadata = metainfo_path
+        .as_ref()
+        .and_then(|path| AppStreamMetadata::from_path(path).ok());
+    let version = DesktopFile::from_path(entry_path)
+        .ok()
+        .and_then(|desktop_file| app_version(&desktop_file, metadata.as_ref()));
+    let portable = !existing_portable_dirs(&app_image_path).is_empty();
+
+    Some(InstallRecord {
+        id: legacy_app_id(&name),
+        name,
+        version,
+        scope,
+        sha256: app_image_digest(&app_image_path, &[]).ok(),
+        app_image_path,
+        desktop_entries: vec![entry_path.clone()],
+        icon_paths,
+        metainfo_path,
+        mime_packages: split_paths(desktop_entry.mime_packages()),
+        source_path: None,
+        installed_at: fs::metadata(entry_path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default(),
+        signature: desktop_entry
+            .signature()
+            .and_then(|raw| SignatureStatus::parse(&raw)),
+        no_sandbox: command.has_argument("--no-sandbox"),
+        portable,
+        other_versions: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adopt_and_save() {
+        let root = tempfile::tempdir().unwrap();
+        let app_hub_entry = root.path().join("my-app.desktop");
+        fs::write(
+            &app_hub_entry,
+            "[Desktop Entry]\n\
+             Type=Application\n\
+             Name=My App\n\
+             X-AppImage-Version=1.4\n\
+             Exec=/opt/AppImages/MyApp.AppImage --no-sandbox %U\n\
+             Icon=/opt/AppImages/icons/my-app.png\n\
+             X-AppHub-Signature=trusted;0123456789ABCDEF\n\
+             X-AppHub=true\n",
+        )
+        .unwrap();
+        let other_entry = root.path().join("other.desktop");
+        fs::write(
+            &other_entry,
+            "[Desktop Entry]\nType=Application\nName=Other\nExec=other\n",
+        )
+        .unwrap();
+
+        let mut database =
+            InstallDatabase::adopt(&[app_hub_entry.clone(), other_entry], InstallScope::System);
+        assert_eq!(database.apps.len(), 1);
+        let record = database.get("my-app").unwrap().clone();
+        assert_eq!(record.name, "My App");
+        assert_eq!(record.version.as_deref(), Some("1.4"));
+        assert_eq!(
+            record.app_image_path,
+            PathBuf::from("/opt/AppImages/MyApp.AppImage")
+        );
+        assert_eq!(record.desktop_entries, vec![app_hub_entry]);
+        assert_eq!(
+            record.icon_paths,
+            vec![PathBuf::from("/opt/AppImages/icons/my-app.png")]
+        );
+        assert_eq!(
+            record.signature,
+            Some(SignatureStatus::Trusted {
+                fingerprint: "0123456789ABCDEF".to_string()
+            })
+        );
+        assert!(record.no_sandbox);
+        assert!(record.installed_at > 0);
+
+        let path = root.path().join("state").join(DATABASE_FILE);
+        database.save(&path).unwrap();
+        assert_eq!(InstallDatabase::load(&path).unwrap(), database);
+
+        database.upsert(InstallRecord {
+            sha256: Some("00".to_string()),
+            ..record
+        });
+        assert_eq!(database.apps.len(), 1);
+        assert_eq!(database.apps[0].sha256.as_deref(), Some("00"));
+        let identity = AppIdentity {
+            id: "com.example.MyApp".to_string(),
+            name: "My App".to_string(),
+            version: None,
+            sha256: "00".to_string(),
+        };
+        assert_eq!(database.find_installed(&identity).unwrap().id, "my-app");
+        assert!(database.remove("my-app").is_some());
+        assert!(database.apps.is_empty());
+    }
+}
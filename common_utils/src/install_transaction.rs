@@ -0,0 +1,402 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{error, info, warn};
+
+use crate::models::install_scope::InstallScope;
+
+/// Suffix of the files written by a transaction, before they are moved at their target
+const STAGED_SUFFIX: &str = "apphub-staged";
+
+/// Suffix of the files replaced by a transaction, kept until it is committed
+const BACKUP_SUFFIX: &str = "apphub-backup";
+
+/// A file written next to its target, moved into place when the transaction is committed
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+struct StagedFile {
+    staged: PathBuf,
+    target: PathBuf,
+    /// Where the file previously at the target is moved during the commit
+    backup: Option<PathBuf>,
+    /// Whether the staged file was moved at its target
+    committed: bool,
+}
+
+/// The journal of a transaction, written to disk before each step
+/// so the transaction can be rolled back after a crash
+#[derive(serde::Serialize, serde::Deserialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Journal {
+    /// Process running the transaction
+    pid: u32,
+    /// Whether the staged files are being moved into place
+    committing: bool,
+    files: Vec<StagedFile>,
+    /// Working directories removed when the transaction ends, whatever the outcome
+    scratch_dirs: Vec<PathBuf>,
+}
+
+impl Journal {
+    fn read(path: &Path) -> Result<Self, String> {
+        let content =
+            fs::read_to_string(path).map_err(|e| format!("Failed to read journal: {}", e))?;
+        serde_json::from_str(&content).map_err(|e| format!("Invalid journal: {}", e))
+    }
+
+    fn write(&self, path: &Path) -> Result<(), String> {
+        let content =
+            serde_json::to_string_pretty(self).map_err(|e| format!("Invalid journal: {}", e))?;
+        // write the new journal beside the old one, so a crash never leaves it half written
+        let staged = sibling_path(path, STAGED_SUFFIX);
+        fs::write(&staged, content).map_err(|e| format!("Failed to write journal: {}", e))?;
+        fs::rename(&staged, path).map_err(|e| format!("Failed to write journal: {}", e))
+    }
+
+    /// Undo every step recorded in the journal, in reverse order
+    fn roll_back(&self) {
+        for file in self.files.iter().rev() {
+            // during the commit, a staged file which is gone was moved at its target
+            let moved = file.committed || (self.committing && !file.staged.exists());
+            if moved {
+                remove_path(&file.target);
+            }
+            remove_path(&file.staged);
+            if let Some(backup) = &file.backup {
+                if backup.exists() {
+                    match fs::rename(backup, &file.target) {
+                        Ok(_) => info!("Restored {:?}", file.target),
+                        Err(e) => error!("Failed to restore {:?}: {}", file.target, e),
+                    }
+                }
+            }
+        }
+        self.remove_scratch_dirs();
+    }
+
+    fn remove_scratch_dirs(&self) {
+        for dir in &self.scratch_dirs {
+            if dir.exists() {
+                if let Err(e) = fs::remove_dir_all(dir) {
+                    error!("Failed to remove {:?}: {}", dir, e);
+                }
+            }
+        }
+    }
+}
+
+/// The files of an installation, staged at temporary paths and atomically moved into place on commit.
+/// Each step is recorded in a journal: the transaction is rolled back when it is dropped without being
+/// committed, and by `recover_transactions` when the process stopped in between.
+pub struct InstallTransaction {
+    journal_path: PathBuf,
+    journal: Journal,
+    finished: bool,
+}
+
+impl InstallTransaction {
+    /// Start a transaction whose journal is written in the given directory
+    pub fn begin(journal_dir: &Path) -> Result<Self, String> {
+        fs::create_dir_all(journal_dir)
+            .map_err(|e| format!("Failed to create journal directory: {}", e))?;
+
+        let pid = process::id();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_nanos())
+            .unwrap_or_default();
+        let transaction = Self {
+            journal_path: journal_dir.join(format!("{}-{}.json", pid, timestamp)),
+            journal: Journal {
+                pid,
+                ..Default::default()
+            },
+            finished: false,
+        };
+        transaction.journal.write(&transaction.journal_path)?;
+        info!("Started transaction {:?}", transaction.journal_path);
+        Ok(transaction)
+    }
+
+    /// Remove the given working directory when the transaction ends
+    pub fn add_scratch_dir(&mut self, dir: &Path) -> Result<(), String> {
+        self.journal.scratch_dirs.push(dir.to_path_buf());
+        self.journal.write(&self.journal_path)
+    }
+
+    /// Stage a copy of the source file for the target.
+    /// Returns the temporary path of the copy, which can still be modified until the commit
+    pub fn stage_copy(&mut self, source: &Path, target: &Path) -> Result<PathBuf, String> {
+        let staged = self.stage(target)?;
+        fs::copy(source, &staged)
+            .map_err(|e| format!("Failed to copy {:?} to {:?}: {}", source, staged, e))?;
+        Ok(staged)
+    }
+
+    /// Stage the content of the target file
+    pub fn stage_write(&mut self, target: &Path, content: &[u8]) -> Result<PathBuf, String> {
+        let staged = self.stage(target)?;
+        fs::write(&staged, content).map_err(|e| format!("Failed to write {:?}: {}", staged, e))?;
+        Ok(staged)
+    }
+
+    /// Move the staged files at their targets, replacing the existing files.
+    /// On failure the files already moved are restored and the transaction is rolled back
+    pub fn commit(mut self) -> Result<(), String> {
+        self.journal.committing = true;
+        self.journal.write(&self.journal_path)?;
+        for index in 0..self.journal.files.len() {
+            // dropping the transaction on error rolls it back
+            self.commit_file(index)?;
+        }
+
+        self.finished = true;
+        for file in &self.journal.files {
+            if let Some(backup) = &file.backup {
+                remove_path(backup);
+            }
+        }
+        self.journal.remove_scratch_dirs();
+        remove_path(&self.journal_path);
+        info!("Committed transaction {:?}", self.journal_path);
+        Ok(())
+    }
+
+    /// Remove the staged files and restore the replaced ones
+    pub fn rollback(mut self) {
+        self.roll_back();
+    }
+
+    fn stage(&mut self, target: &Path) -> Result<PathBuf, String> {
+        if self.journal.files.iter().any(|file| file.target == target) {
+            return Err(format!("{:?} is already staged", target));
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create directory {:?}: {}", parent, e))?;
+        }
+
+        // record the file before writing it, so a crash never leaves an unknown file
+        let staged = sibling_path(target, STAGED_SUFFIX);
+        self.journal.files.push(StagedFile {
+            staged: staged.clone(),
+            target: target.to_path_buf(),
+            backup: None,
+            committed: false,
+        });
+        self.journal.write(&self.journal_path)?;
+        Ok(staged)
+    }
+
+    fn commit_file(&mut self, index: usize) -> Result<(), String> {
+        let file = self.journal.files[index].clone();
+        if file.target.symlink_metadata().is_ok() {
+            let backup = sibling_path(&file.target, BACKUP_SUFFIX);
+            self.journal.files[index].backup = Some(backup.clone());
+            self.journal.write(&self.journal_path)?;
+            fs::rename(&file.target, &backup)
+                .map_err(|e| format!("Failed to replace {:?}: {}", file.target, e))?;
+        }
+
+        fs::rename(&file.staged, &file.target)
+            .map_err(|e| format!("Failed to move {:?} into place: {}", file.target, e))?;
+        self.journal.files[index].committed = true;
+        self.journal.write(&self.journal_path)?;
+        info!("Installed {:?}", file.target);
+        Ok(())
+    }
+
+    fn roll_back(&mut self) {
+        self.finished = true;
+        warn!("Rolling back transaction {:?}", self.journal_path);
+        self.journal.roll_back();
+        remove_path(&self.journal_path);
+    }
+}
+
+impl Drop for InstallTransaction {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.roll_back();
+        }
+    }
+}
+
+/// The directory of the journals of the transactions of the scope
+pub fn transactions_dir(scope: InstallScope) -> Result<PathBuf, &'static str> {
+    Ok(scope.state_dir()?.join("transactions"))
+}
+
+/// Roll back the transactions left behind by processes which stopped before finishing them.
+/// Returns the number of transactions rolled back
+pub fn recover_transactions(journal_dir: &Path) -> usize {
+    let entries = match fs::read_dir(journal_dir) {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+
+    let mut recovered = 0;
+    for journal_path in entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        })
+    {
+        let journal = match Journal::read(&journal_path) {
+            Ok(journal) => journal,
+            Err(e) => {
+                error!("Ignoring journal {:?}: {}", journal_path, e);
+                continue;
+            }
+        };
+        if journal.pid != process::id() && is_running(journal.pid) {
+            info!("Transaction {:?} is still running", journal_path);
+            continue;
+        }
+
+        warn!("Recovering interrupted transaction {:?}", journal_path);
+        journal.roll_back();
+        remove_path(&journal_path);
+        recovered += 1;
+    }
+    recovered
+}
+
+fn is_running(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+/// A hidden path next to the given one, on the same file system so it can be renamed atomically
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.{}", file_name, suffix))
+}
+
+fn remove_path(path: &Path) {
+    if path.symlink_metadata().is_ok() {
+        if let Err(e) = fs::remove_file(path) {
+            error!("Failed to remove {:?}: {}", path, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dir_entries(dir: &Path) -> Vec<String> {
+        let mut entries: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    #[test]
+    fn test_commit() {
+        let root = tempfile::tempdir().unwrap();
+        let journal_dir = root.path().join("transactions");
+        let apps_dir = root.path().join("apps");
+        let scratch_dir = root.path().join("squashfs-root");
+        fs::create_dir_all(&apps_dir).unwrap();
+        fs::create_dir_all(&scratch_dir).unwrap();
+        fs::write(apps_dir.join("app.desktop"), "old").unwrap();
+        fs::write(scratch_dir.join("app.AppImage"), "image").unwrap();
+
+        let mut transaction = InstallTransaction::begin(&journal_dir).unwrap();
+        transaction.add_scratch_dir(&scratch_dir).unwrap();
+        transaction
+            .stage_copy(
+                &scratch_dir.join("app.AppImage"),
+                &apps_dir.join("app.AppImage"),
+            )
+            .unwrap();
+        transaction
+            .stage_write(&apps_dir.join("app.desktop"), b"new")
+            .unwrap();
+        assert!(transaction
+            .stage_write(&apps_dir.join("app.desktop"), b"again")
+            .is_err());
+        transaction.commit().unwrap();
+
+        assert_eq!(dir_entries(&apps_dir), vec!["app.AppImage", "app.desktop"]);
+        assert_eq!(
+            fs::read_to_string(apps_dir.join("app.desktop")).unwrap(),
+            "new"
+        );
+        assert!(!scratch_dir.exists());
+        assert!(dir_entries(&journal_dir).is_empty());
+    }
+
+    #[test]
+    fn test_rollback_on_drop() {
+        let root = tempfile::tempdir().unwrap();
+        let journal_dir = root.path().join("transactions");
+        let apps_dir = root.path().join("apps");
+        fs::create_dir_all(&apps_dir).unwrap();
+        fs::write(apps_dir.join("app.desktop"), "old").unwrap();
+
+        let mut transaction = InstallTransaction::begin(&journal_dir).unwrap();
+        transaction
+            .stage_write(&apps_dir.join("app.AppImage"), b"image")
+            .unwrap();
+        transaction
+            .stage_write(&apps_dir.join("app.desktop"), b"new")
+            .unwrap();
+        // the AppImage is moved into place, then the commit of the entry fails
+        transaction.journal.committing = true;
+        transaction.commit_file(0).unwrap();
+        drop(transaction);
+
+        assert_eq!(dir_entries(&apps_dir), vec!["app.desktop"]);
+        assert_eq!(
+            fs::read_to_string(apps_dir.join("app.desktop")).unwrap(),
+            "old"
+        );
+        assert!(dir_entries(&journal_dir).is_empty());
+    }
+
+    #[test]
+    fn test_recover_interrupted_commit() {
+        let root = tempfile::tempdir().unwrap();
+        let journal_dir = root.path().join("transactions");
+        let apps_dir = root.path().join("apps");
+        fs::create_dir_all(&apps_dir).unwrap();
+        fs::write(apps_dir.join("app.AppImage"), "old image").unwrap();
+        fs::write(apps_dir.join("app.desktop"), "old").unwrap();
+
+        let mut transaction = InstallTransaction::begin(&journal_dir).unwrap();
+        transaction
+            .stage_write(&apps_dir.join("app.AppImage"), b"new image")
+            .unwrap();
+        transaction
+            .stage_write(&apps_dir.join("app.desktop"), b"new")
+            .unwrap();
+        transaction.journal.committing = true;
+        transaction.commit_file(0).unwrap();
+        // the process stops in the middle of the commit
+        std::mem::forget(transaction);
+        assert_eq!(dir_entries(&journal_dir).len(), 1);
+
+        assert_eq!(recover_transactions(&journal_dir), 1);
+        assert_eq!(dir_entries(&apps_dir), vec!["app.AppImage", "app.desktop"]);
+        assert_eq!(
+            fs::read_to_string(apps_dir.join("app.AppImage")).unwrap(),
+            "old image"
+        );
+        assert_eq!(
+            fs::read_to_string(apps_dir.join("app.desktop")).unwrap(),
+            "old"
+        );
+        assert!(dir_entries(&journal_dir).is_empty());
+        assert_eq!(recover_transactions(&journal_dir), 0);
+    }
+}
@@ -0,0 +1,548 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Seek, SeekFrom};
+
+use log::debug;
+
+use crate::image_fs::{is_valid_name, normalize, EntryKind, ImageEntry, ImageFs, MAX_FILE_SIZE};
+
+const SECTOR_SIZE: u64 = 2048;
+const VOLUME_DESCRIPTORS_START: u64 = 16;
+const MAX_VOLUME_DESCRIPTORS: u64 = 64;
+const MAX_DIRECTORY_DEPTH: usize = 64;
+const MAX_CONTINUATION_AREAS: usize = 16;
+const DIRECTORY_FLAG: u8 = 0x02;
+const POSIX_FILE_TYPE_MASK: u32 = 0o170000;
+const POSIX_SYMLINK: u32 = 0o120000;
+const ZISOFS_MAGIC: [u8; 8] = [0x37, 0xE4, 0x53, 0x96, 0xC9, 0xDB, 0xD6, 0x07];
+/// zisofs only defines blocks of 32, 64 and 128 KiB
+const ZISOFS_BLOCK_SIZE_EXPONENTS: std::ops::RangeInclusive<u8> = 15..=17;
+
+/// Rock Ridge `ZF` parameters of a zisofs compressed file
+#[derive(Debug, Clone, Copy)]
+struct Zisofs {
+    uncompressed_size: u64,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Directory,
+    File {
+        position: u64,
+        size: u64,
+        zisofs: Option<Zisofs>,
+    },
+    Symlink {
+        target: String,
+    },
+}
+
+/// A raw ISO 9660 directory record with its Rock Ridge information
+struct Record {
+    extent: u64,
+    data_length: u64,
+    is_directory: bool,
+    iso_name: Vec<u8>,
+    rock_ridge: RockRidge,
+}
+
+#[derive(Default)]
+struct RockRidge {
+    name: Option<String>,
+    symlink: Option<String>,
+    mode: Option<u32>,
+    zisofs: Option<Zisofs>,
+}
+
+/// Read-only ISO 9660 reader with Rock Ridge and zisofs support, as used by type-1 AppImages
+pub struct Iso9660<R: Read + Seek> {
+    reader: R,
+    image_len: u64,
+    entries: Vec<ImageEntry>,
+    index: HashMap<String, Node>,
+}
+
+impl<R: Read + Seek> Iso9660<R> {
+    /// Open the ISO 9660 image of the reader and index all its entries
+    pub fn open(mut reader: R) -> Result<Self, String> {
+        let (block_size, root) = read_primary_volume_descriptor(&mut reader)?;
+        debug!("ISO 9660 logical block size: {}", block_size);
+        let image_len = reader
+            .seek(SeekFrom::End(0))
+            .map_err(|e| format!("Failed to read ISO 9660 image size: {}", e))?;
+
+        let mut iso = Self {
+            reader,
+            image_len,
+            entries: Vec::new(),
+            index: HashMap::new(),
+        };
+        iso.build_index(block_size, root)?;
+        Ok(iso)
+    }
+
+    fn build_index(&mut self, block_size: u64, root: Record) -> Result<(), String> {
+        let mut visited = HashSet::new();
+        let mut pending = vec![(String::new(), root, 0)];
+
+        while let Some((dir_path, dir, depth)) = pending.pop() {
+            if depth > MAX_DIRECTORY_DEPTH || !visited.insert(dir.extent) {
+                continue;
+            }
+
+            for record in self.read_directory(block_size, &dir)? {
+                let name = match &record.rock_ridge.name {
+                    Some(name) => name.clone(),
+                    None => iso_file_name(&record.iso_name),
+                };
+                if !is_valid_name(&name) {
+                    return Err(format!("Invalid file name in ISO 9660 image: {:?}", name));
+                }
+                let path = if dir_path.is_empty() {
+                    name
+                } else {
+                    format!("{}/{}", dir_path, name)
+                };
+
+                let is_symlink = record
+                    .rock_ridge
+                    .mode
+                    .is_some_and(|mode| mode & POSIX_FILE_TYPE_MASK == POSIX_SYMLINK)
+                    || record.rock_ridge.symlink.is_some();
+                let (node, entry) = if record.is_directory {
+                    (Node::Directory, (EntryKind::Directory, 0))
+                } else if is_symlink {
+                    let target = record.rock_ridge.symlink.clone().unwrap_or_default();
+                    let size = target.len() as u64;
+                    (Node::Symlink { target }, (EntryKind::Symlink, size))
+                } else {
+                    let size = record
+                        .rock_ridge
+                        .zisofs
+                        .map(|zisofs| zisofs.uncompressed_size)
+                        .unwrap_or(record.data_length);
+                    (
+                        Node::File {
+                            position: record.extent * block_size,
+                            size: record.data_length,
+                            zisofs: record.rock_ridge.zisofs,
+                        },
+                        (EntryKind::File, size),
+                    )
+                };
+
+                self.entries.push(ImageEntry {
+                    path: path.clone(),
+                    kind: entry.0,
+                    size: entry.1,
+                });
+                self.index.insert(path.clone(), node);
+                if record.is_directory {
+                    pending.push((path, record, depth + 1));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Read all records of a directory, skipping the `.` and `..` entries
+    fn read_directory(&mut self, block_size: u64, dir: &Record) -> Result<Vec<Record>, String> {
+        let data = self.read_at(dir.extent * block_size, dir.data_length)?;
+        let mut records = Vec::new();
+        let mut position = 0;
+        while position < data.len() {
+            let length = data[position] as usize;
+            if length == 0 {
+                // Records never cross sector boundaries: continue with the next sector
+                position = (position / SECTOR_SIZE as usize + 1) * SECTOR_SIZE as usize;
+                continue;
+            }
+            if position + length > data.len() {
+                return Err("Invalid ISO 9660 directory record".to_string());
+            }
+            let record = self.parse_record(block_size, &data[position..position + length])?;
+            if record.iso_name != [0] && record.iso_name != [1] {
+                records.push(record);
+            }
+            position += length;
+        }
+        Ok(records)
+    }
+
+    fn parse_record(&mut self, block_size: u64, raw: &[u8]) -> Result<Record, String> {
+        if raw.len() < 34 {
+            return Err("ISO 9660 directory record too short".to_string());
+        }
+        let name_length = raw[32] as usize;
+        if 33 + name_length > raw.len() {
+            return Err("Invalid ISO 9660 file name length".to_string());
+        }
+        let iso_name = raw[33..33 + name_length].to_vec();
+        // The system use area starts after the name and its padding byte
+        let system_use_start = 33 + name_length + (1 - name_length % 2);
+
+        let mut rock_ridge = RockRidge::default();
+        if system_use_start < raw.len() {
+            self.parse_system_use(block_size, &raw[system_use_start..], &mut rock_ridge, 0)?;
+        }
+
+        Ok(Record {
+            extent: le_u32(&raw[2..]) as u64,
+            data_length: le_u32(&raw[10..]) as u64,
+            is_directory: raw[25] & DIRECTORY_FLAG != 0,
+            iso_name,
+            rock_ridge,
+        })
+    }
+
+    /// Parse the SUSP entries of a system use area, following continuation areas
+    fn parse_system_use(
+        &mut self,
+        block_size: u64,
+        area: &[u8],
+        rock_ridge: &mut RockRidge,
+        continuations: usize,
+    ) -> Result<(), String> {
+        let mut position = 0;
+        let mut symlink_continues = false;
+        while position + 4 <= area.len() {
+            let signature = &area[position..position + 2];
+            let length = area[position + 2] as usize;
+            if length < 4 || position + length > area.len() {
+                break;
+            }
+            let data = &area[position + 4..position + length];
+            match signature {
+                b"NM" if !data.is_empty() => {
+                    let part = String::from_utf8_lossy(&data[1..]);
+                    rock_ridge
+                        .name
+                        .get_or_insert_with(String::new)
+                        .push_str(&part);
+                }
+                b"SL" if !data.is_empty() => {
+                    let target = rock_ridge.symlink.get_or_insert_with(String::new);
+                    symlink_continues =
+                        parse_symlink_components(&data[1..], target, symlink_continues);
+                }
+                b"PX" if data.len() >= 4 => {
+                    rock_ridge.mode = Some(le_u32(data));
+                }
+                b"ZF" if data.len() >= 12 && &data[0..2] == b"pz" => {
+                    rock_ridge.zisofs = Some(Zisofs {
+                        uncompressed_size: le_u32(&data[4..]) as u64,
+                    });
+                }
+                b"CE" if data.len() >= 24 && continuations < MAX_CONTINUATION_AREAS => {
+                    let block = le_u32(&data[0..]) as u64;
+                    let offset = le_u32(&data[8..]) as u64;
+                    let length = le_u32(&data[16..]) as u64;
+                    let continuation = self.read_at(block * block_size + offset, length)?;
+                    self.parse_system_use(
+                        block_size,
+                        &continuation,
+                        rock_ridge,
+                        continuations + 1,
+                    )?;
+                }
+                b"ST" => break,
+                _ => {}
+            }
+            position += length;
+        }
+        Ok(())
+    }
+
+    /// Read a range of the image, refusing ranges past its end before anything is allocated
+    fn read_at(&mut self, position: u64, length: u64) -> Result<Vec<u8>, String> {
+        match position.checked_add(length) {
+            Some(end) if end <= self.image_len => {}
+            _ => {
+                return Err(format!(
+                    "Invalid ISO 9660 image: {} bytes at offset {} are past its end",
+                    length, position
+                ))
+            }
+        }
+        let mut data = vec![0u8; length as usize];
+        self.reader
+            .seek(SeekFrom::Start(position))
+            .and_then(|_| self.reader.read_exact(&mut data))
+            .map_err(|e| format!("Failed to read ISO 9660 image: {}", e))?;
+        Ok(data)
+    }
+}
+
+impl<R: Read + Seek> ImageFs for Iso9660<R> {
+    fn entries(&self) -> &[ImageEntry] {
+        &self.entries
+    }
+
+    fn kind(&self, path: &str) -> Option<EntryKind> {
+        self.index
+            .get(normalize(path).as_str())
+            .map(|node| match node {
+                Node::Directory => EntryKind::Directory,
+                Node::File { .. } => EntryKind::File,
+                Node::Symlink { .. } => EntryKind::Symlink,
+            })
+    }
+
+    fn read_link(&self, path: &str) -> Result<String, String> {
+        match self.index.get(normalize(path).as_str()) {
+            Some(Node::Symlink { target }) => Ok(target.clone()),
+            Some(_) => Err(format!("Not a symlink: {}", path)),
+            None => Err(format!("File not found in ISO 9660 image: {}", path)),
+        }
+    }
+
+    fn read_regular_file(&mut self, path: &str) -> Result<Vec<u8>, String> {
+        let Some(Node::File {
+            position,
+            size,
+            zisofs,
+        }) = self.index.get(normalize(path).as_str()).cloned()
+        else {
+            return Err(format!("Not a regular file: {}", path));
+        };
+
+        let data = self.read_at(position, size)?;
+        match zisofs {
+            Some(zisofs) => decompress_zisofs(&data, zisofs.uncompressed_size),
+            None => Ok(data),
+        }
+    }
+}
+
+fn read_primary_volume_descriptor<R: Read + Seek>(reader: &mut R) -> Result<(u64, Record), String> {
+    let mut descriptor = [0u8; SECTOR_SIZE as usize];
+    for sector in VOLUME_DESCRIPTORS_START..VOLUME_DESCRIPTORS_START + MAX_VOLUME_DESCRIPTORS {
+        reader
+            .seek(SeekFrom::Start(sector * SECTOR_SIZE))
+            .and_then(|_| reader.read_exact(&mut descriptor))
+            .map_err(|e| format!("Failed to read ISO 9660 volume descriptor: {}", e))?;
+        if &descriptor[1..6] != b"CD001" {
+            return Err("ISO 9660 signature not found".to_string());
+        }
+        match descriptor[0] {
+            // Primary volume descriptor
+            1 => {
+                let block_size = le_u16(&descriptor[128..]) as u64;
+                if block_size == 0 {
+                    return Err("Invalid ISO 9660 logical block size".to_string());
+                }
+                let root = &descriptor[156..190];
+                return Ok((
+                    block_size,
+                    Record {
+                        extent: le_u32(&root[2..]) as u64,
+                        data_length: le_u32(&root[10..]) as u64,
+                        is_directory: true,
+                        iso_name: vec![0],
+                        rock_ridge: RockRidge::default(),
+                    },
+                ));
+            }
+            // Volume descriptor set terminator
+            255 => break,
+            _ => {}
+        }
+    }
+    Err("ISO 9660 primary volume descriptor not found".to_string())
+}
+
+/// Append the components of a Rock Ridge `SL` entry to the symlink target.
+/// Returns whether the last component continues in the next entry.
+fn parse_symlink_components(mut data: &[u8], target: &mut String, mut continues: bool) -> bool {
+    while data.len() >= 2 {
+        let flags = data[0];
+        let length = data[1] as usize;
+        if data.len() < 2 + length {
+            break;
+        }
+        let content = &data[2..2 + length];
+        data = &data[2 + length..];
+
+        if !continues && !target.is_empty() && !target.ends_with('/') {
+            target.push('/');
+        }
+        if flags & 0x02 != 0 {
+            target.push('.');
+        } else if flags & 0x04 != 0 {
+            target.push_str("..");
+        } else if flags & 0x08 != 0 {
+            target.push('/');
+        } else {
+            target.push_str(&String::from_utf8_lossy(content));
+        }
+        continues = flags & 0x01 != 0;
+    }
+    continues
+}
+
+/// Convert a plain ISO 9660 name: drop the version suffix and a trailing dot
+fn iso_file_name(raw: &[u8]) -> String {
+    let name = String::from_utf8_lossy(raw).to_string();
+    let name = match name.rsplit_once(';') {
+        Some((name, _version)) => name.to_string(),
+        None => name,
+    };
+    name.strip_suffix('.').map(str::to_string).unwrap_or(name)
+}
+
+/// Decompress a zisofs file: a header, a table of block pointers and zlib compressed blocks
+fn decompress_zisofs(data: &[u8], uncompressed_size: u64) -> Result<Vec<u8>, String> {
+    if data.len() < 16 || data[0..8] != ZISOFS_MAGIC {
+        return Err("Invalid zisofs header".to_string());
+    }
+    if uncompressed_size > MAX_FILE_SIZE {
+        return Err(format!(
+            "zisofs file too large: {} bytes",
+            uncompressed_size
+        ));
+    }
+    if !ZISOFS_BLOCK_SIZE_EXPONENTS.contains(&data[13]) {
+        return Err(format!("Invalid zisofs block size exponent: {}", data[13]));
+    }
+    let header_size = data[12] as usize * 4;
+    let block_size = 1u64 << data[13];
+    let block_count = uncompressed_size.div_ceil(block_size) as usize;
+    if header_size + (block_count + 1) * 4 > data.len() {
+        return Err("Invalid zisofs block table".to_string());
+    }
+
+    let pointer = |index: usize| le_u32(&data[header_size + index * 4..]) as usize;
+    // bounded by MAX_FILE_SIZE above, sparse blocks included
+    let mut content = Vec::with_capacity(uncompressed_size as usize);
+    for index in 0..block_count {
+        let expected = block_size.min(uncompressed_size - content.len() as u64) as usize;
+        let (start, end) = (pointer(index), pointer(index + 1));
+        if start == end {
+            content.resize(content.len() + expected, 0);
+            continue;
+        }
+        if start > end || end > data.len() {
+            return Err("Invalid zisofs block pointer".to_string());
+        }
+        let mut block = Vec::with_capacity(expected);
+        flate2::read::ZlibDecoder::new(&data[start..end])
+            .take(expected as u64)
+            .read_to_end(&mut block)
+            .map_err(|e| format!("Failed to decompress zisofs block: {}", e))?;
+        content.extend_from_slice(&block);
+    }
+
+    if content.len() as u64 != uncompressed_size {
+        return Err("Truncated zisofs file".to_string());
+    }
+    Ok(content)
+}
+
+fn le_u16(bytes: &[u8]) -> u16 {
+    u16::from_le_bytes([bytes[0], bytes[1]])
+}
+
+fn le_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{build_iso9660, TestNode};
+    use std::io::Cursor;
+
+    fn sample_image() -> Vec<u8> {
+        build_iso9660(
+            &[],
+            &[
+                (
+                    "app.desktop",
+                    TestNode::File(b"[Desktop Entry]\nName=Old App\n"),
+                ),
+                (".DirIcon", TestNode::Symlink("usr/share/pixmaps/app.png")),
+                ("usr/share/pixmaps/app.png", TestNode::File(b"\x89PNG old")),
+                (
+                    "usr/share/doc/README",
+                    TestNode::Compressed(b"compressed readme"),
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_lists_rock_ridge_names() {
+        let iso = Iso9660::open(Cursor::new(sample_image())).unwrap();
+        let mut paths: Vec<&str> = iso.entries().iter().map(|e| e.path.as_str()).collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![
+                ".DirIcon",
+                "app.desktop",
+                "usr",
+                "usr/share",
+                "usr/share/doc",
+                "usr/share/doc/README",
+                "usr/share/pixmaps",
+                "usr/share/pixmaps/app.png"
+            ]
+        );
+        assert_eq!(iso.kind(".DirIcon"), Some(EntryKind::Symlink));
+    }
+
+    #[test]
+    fn test_reads_files_symlinks_and_zisofs() {
+        let mut iso = Iso9660::open(Cursor::new(sample_image())).unwrap();
+        assert_eq!(
+            iso.read_file("app.desktop").unwrap(),
+            b"[Desktop Entry]\nName=Old App\n"
+        );
+        assert_eq!(
+            iso.read_link(".DirIcon").unwrap(),
+            "usr/share/pixmaps/app.png"
+        );
+        assert_eq!(iso.read_file(".DirIcon").unwrap(), b"\x89PNG old");
+        assert_eq!(
+            iso.read_file("usr/share/doc/README").unwrap(),
+            b"compressed readme"
+        );
+    }
+
+    #[test]
+    fn test_rejects_malformed_headers() {
+        let image = sample_image();
+        let root_length = 16 * 2048 + 156 + 10;
+
+        // root directory larger than the image
+        let mut large_root = image.clone();
+        large_root[root_length..root_length + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Iso9660::open(Cursor::new(large_root)).is_err());
+
+        // root directory past the end of the image
+        let mut far_root = image.clone();
+        far_root[root_length - 8..root_length - 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Iso9660::open(Cursor::new(far_root)).is_err());
+
+        let zisofs = |size: u32, exponent: u8| {
+            let mut data = ZISOFS_MAGIC.to_vec();
+            data.extend_from_slice(&size.to_le_bytes());
+            data.extend_from_slice(&[4, exponent, 0, 0]);
+            // a single sparse block
+            data.extend_from_slice(&24u32.to_le_bytes());
+            data.extend_from_slice(&24u32.to_le_bytes());
+            data
+        };
+        assert_eq!(decompress_zisofs(&zisofs(16, 15), 16).unwrap(), vec![0; 16]);
+        // block size exponents zisofs does not define, or which overflow
+        assert!(decompress_zisofs(&zisofs(16, 14), 16).is_err());
+        assert!(decompress_zisofs(&zisofs(16, 64), 16).is_err());
+        // sparse file larger than anything read out of an image
+        assert!(decompress_zisofs(&zisofs(u32::MAX, 17), u32::MAX as u64).is_err());
+    }
+
+    #[test]
+    fn test_iso_file_name() {
+        assert_eq!(iso_file_name(b"APP.DESKTOP;1"), "APP.DESKTOP");
+        assert_eq!(iso_file_name(b"README.;1"), "README");
+    }
+}
@@ -1,6 +1,25 @@
 pub mod desktop_file_builder;
 pub mod file_system_helpers;
 pub mod app_images_helpers;
+pub mod app_id_helpers;
+pub mod app_image_reader;
 pub mod desktop_file_helpers;
-pub mod desktop_entry;
+pub mod desktop_file;
+pub mod desktop_file_validator;
+pub mod download_helpers;
+pub mod elf;
+pub mod icon_helpers;
+pub mod install_database;
+pub mod install_transaction;
+pub mod image_fs;
+pub mod iso9660;
+pub mod mime_helpers;
 pub mod models;
+pub mod portable_helpers;
+pub mod signature_helpers;
+pub mod squashfs;
+pub mod version_helpers;
+pub mod xdg_helpers;
+
+#[cfg(test)]
+mod test_support;
@@ -0,0 +1,140 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use log::{debug, error, info};
+use roxmltree::Document;
+
+use crate::desktop_file_helpers::find_desktop_file_location;
+use crate::models::install_scope::InstallScope;
+
+/// Directory of the AppImage holding shared-mime-info packages
+const MIME_PACKAGES_DIR: &str = "usr/share/mime/packages";
+
+/// Namespace of the shared-mime-info packages
+const MIME_INFO_NAMESPACE: &str = "http://www.freedesktop.org/standards/shared-mime-info";
+
+/// Find the MIME database next to the applications directory of the scope,
+/// `/usr/share/mime` or `$XDG_DATA_HOME/mime`
+pub fn find_mime_location(scope: InstallScope) -> Result<PathBuf, &'static str> {
+    let applications_dir = find_desktop_file_location(scope)?;
+    match applications_dir.parent() {
+        Some(data_dir) => Ok(data_dir.join("mime")),
+        None => Err("Failed to find the MIME database directory"),
+    }
+}
+
+/// Find the shared-mime-info packages shipped in the extracted AppImage.
+/// Files which are not MIME packages are skipped.
+pub fn find_mime_packages(squashfs_root_path: &Path) -> Vec<PathBuf> {
+    let entries = match fs::read_dir(squashfs_root_path.join(MIME_PACKAGES_DIR)) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut packages: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "xml"))
+        .filter(|path| match fs::read_to_string(path) {
+            Ok(content) if is_mime_package(&content) => true,
+            _ => {
+                error!("Ignoring invalid MIME package: {:?}", path);
+                false
+            }
+        })
+        .collect();
+    packages.sort();
+    packages
+}
+
+/// Path of a MIME package once installed in the database under the given prefix,
+/// so packages of different apps never collide
+pub fn installed_mime_package_path(mime_dir: &Path, prefix: &str, package_path: &Path) -> PathBuf {
+    let file_name = package_path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    mime_dir
+        .join("packages")
+        .join(format!("{}-{}", prefix, file_name))
+}
+
+/// Rebuild the MIME database from its packages
+pub fn update_mime_database(mime_dir: &Path) {
+    match Command::new("update-mime-database").arg(mime_dir).output() {
+        Ok(output) if output.status.success() => info!("Updated MIME database {:?}", mime_dir),
+        Ok(output) => debug!(
+            "Failed to update MIME database: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ),
+        Err(e) => debug!("Failed to run update-mime-database: {}", e),
+    }
+}
+
+/// Refresh the cache associating MIME types with the desktop entries of the directory
+pub fn update_desktop_database(applications_dir: &Path) {
+    match Command::new("update-desktop-database")
+        .arg(applications_dir)
+        .output()
+    {
+        Ok(output) if output.status.success() => {
+            info!("Updated desktop database {:?}", applications_dir)
+        }
+        Ok(output) => debug!(
+            "Failed to update desktop database: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ),
+        Err(e) => debug!("Failed to run update-desktop-database: {}", e),
+    }
+}
+
+fn is_mime_package(content: &str) -> bool {
+    match Document::parse(content) {
+        Ok(document) => {
+            let root = document.root_element();
+            root.tag_name().name() == "mime-info"
+                && root.tag_name().namespace() == Some(MIME_INFO_NAMESPACE)
+        }
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACKAGE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<mime-info xmlns="http://www.freedesktop.org/standards/shared-mime-info">
+  <mime-type type="application/x-example">
+    <comment>Example document</comment>
+    <glob pattern="*.example"/>
+  </mime-type>
+</mime-info>"#;
+
+    #[test]
+    fn test_find_mime_packages() {
+        let root = tempfile::tempdir().unwrap();
+        assert!(find_mime_packages(root.path()).is_empty());
+
+        let packages_dir = root.path().join(MIME_PACKAGES_DIR);
+        fs::create_dir_all(&packages_dir).unwrap();
+        fs::write(packages_dir.join("example.xml"), PACKAGE).unwrap();
+        fs::write(packages_dir.join("broken.xml"), "<mime-info>").unwrap();
+        fs::write(packages_dir.join("other.xml"), "<component/>").unwrap();
+        fs::write(packages_dir.join("README"), PACKAGE).unwrap();
+
+        assert_eq!(
+            find_mime_packages(root.path()),
+            vec![packages_dir.join("example.xml")]
+        );
+        assert_eq!(
+            installed_mime_package_path(
+                Path::new("/usr/share/mime"),
+                "apphub-example",
+                &packages_dir.join("example.xml")
+            ),
+            PathBuf::from("/usr/share/mime/packages/apphub-example-example.xml")
+        );
+    }
+}
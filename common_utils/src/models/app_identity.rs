@@ -0,0 +1,10 @@
+/// What identifies the app packaged in an AppImage, read before it is installed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AppIdentity {
+    /// Stable identifier of the app, see `app_id_helpers::app_id`
+    pub id: String,
+    pub name: String,
+    pub version: Option<String>,
+    /// SHA-256 digest of the AppImage, hex encoded
+    pub sha256: String,
+}
@@ -0,0 +1,306 @@
+use std::path::Path;
+
+use roxmltree::{Document, Node};
+
+/// Namespace of the `xml:lang` attribute
+const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// Metadata of an application, read from the AppStream metainfo file shipped in the AppImage.
+/// See https://www.freedesktop.org/software/appstream/docs/
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AppStreamMetadata {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub summary: Option<String>,
+    /// The long description, converted to plain text paragraphs
+    pub description: Option<String>,
+    pub project_license: Option<String>,
+    pub metadata_license: Option<String>,
+    pub homepage: Option<String>,
+    pub screenshots: Vec<Screenshot>,
+    /// Releases, newest first
+    pub releases: Vec<Release>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Screenshot {
+    /// URL of the source image
+    pub image: String,
+    pub caption: Option<String>,
+    pub default: bool,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Release {
+    pub version: String,
+    /// Release date, formatted as `YYYY-MM-DD`
+    pub date: Option<String>,
+    pub description: Option<String>,
+}
+
+impl AppStreamMetadata {
+    /// Read and parse an AppStream metainfo file
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read metainfo file {:?}: {}", path, e))?;
+        Self::parse(&content)
+    }
+
+    /// Parse the content of an AppStream metainfo file.
+    /// Only the untranslated values are kept.
+    pub fn parse(xml: &str) -> Result<Self, String> {
+        let document =
+            Document::parse(xml).map_err(|e| format!("Failed to parse metainfo file: {}", e))?;
+        let component = document.root_element();
+        // `application` is the root element of legacy appdata files
+        if !matches!(component.tag_name().name(), "component" | "application") {
+            return Err(format!(
+                "Unexpected metainfo root element: {}",
+                component.tag_name().name()
+            ));
+        }
+
+        let screenshots = child(component, "screenshots")
+            .map(|screenshots| {
+                children(screenshots, "screenshot")
+                    .filter_map(|screenshot| {
+                        let image = children(screenshot, "image")
+                            .find(|image| image.attribute("type") != Some("thumbnail"))
+                            .or_else(|| {
+                                // Legacy format: the URL is the text of the screenshot element
+                                Some(screenshot).filter(|s| child(*s, "image").is_none())
+                            })
+                            .and_then(text)?;
+                        Some(Screenshot {
+                            image,
+                            caption: untranslated_child(screenshot, "caption").and_then(text),
+                            default: screenshot.attribute("type") == Some("default"),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut releases: Vec<Release> = child(component, "releases")
+            .map(|releases| {
+                children(releases, "release")
+                    .filter_map(|release| {
+                        Some(Release {
+                            version: release.attribute("version")?.to_string(),
+                            date: release
+                                .attribute("date")
+                                .map(|date| date.chars().take(10).collect())
+                                .or_else(|| {
+                                    release
+                                        .attribute("timestamp")
+                                        .and_then(|timestamp| timestamp.parse().ok())
+                                        .map(format_timestamp)
+                                }),
+                            description: untranslated_child(release, "description")
+                                .and_then(description_text),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        // Dates are ISO 8601, so they sort as strings
+        releases.sort_by(|a, b| b.date.cmp(&a.date));
+
+        let homepage = children(component, "url")
+            .find(|url| url.attribute("type") == Some("homepage"))
+            .and_then(text);
+
+        Ok(Self {
+            id: child(component, "id").and_then(text),
+            name: untranslated_child(component, "name").and_then(text),
+            summary: untranslated_child(component, "summary").and_then(text),
+            description: untranslated_child(component, "description").and_then(description_text),
+            project_license: child(component, "project_license").and_then(text),
+            metadata_license: child(component, "metadata_license").and_then(text),
+            homepage,
+            screenshots,
+            releases,
+        })
+    }
+}
+
+fn children<'a, 'input: 'a>(
+    node: Node<'a, 'input>,
+    name: &'static str,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.children()
+        .filter(move |child| child.is_element() && child.tag_name().name() == name)
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &'static str) -> Option<Node<'a, 'input>> {
+    children(node, name).next()
+}
+
+/// Find the child element without an `xml:lang` attribute
+fn untranslated_child<'a, 'input>(
+    node: Node<'a, 'input>,
+    name: &'static str,
+) -> Option<Node<'a, 'input>> {
+    children(node, name).find(|child| child.attribute((XML_NAMESPACE, "lang")).is_none())
+}
+
+/// The text content of an element, with whitespace collapsed
+fn text(node: Node) -> Option<String> {
+    let text = node
+        .descendants()
+        .filter(|descendant| descendant.is_text())
+        .filter_map(|descendant| descendant.text())
+        .collect::<Vec<&str>>()
+        .join(" ");
+    let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if text.is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+/// Convert an AppStream description markup (`p`, `ul`, `ol` and `li`) into plain text
+fn description_text(node: Node) -> Option<String> {
+    let mut paragraphs = Vec::new();
+    for element in node.children().filter(|child| child.is_element()) {
+        match element.tag_name().name() {
+            "ul" | "ol" => {
+                let items: Vec<String> = children(element, "li")
+                    .filter(|item| item.attribute((XML_NAMESPACE, "lang")).is_none())
+                    .filter_map(text)
+                    .map(|item| format!("• {}", item))
+                    .collect();
+                if !items.is_empty() {
+                    paragraphs.push(items.join("\n"));
+                }
+            }
+            _ if element.attribute((XML_NAMESPACE, "lang")).is_none() => {
+                paragraphs.extend(text(element));
+            }
+            _ => {}
+        }
+    }
+    if paragraphs.is_empty() {
+        // Some files use plain text instead of markup
+        text(node)
+    } else {
+        Some(paragraphs.join("\n\n"))
+    }
+}
+
+/// Format a UNIX timestamp as `YYYY-MM-DD`
+fn format_timestamp(timestamp: i64) -> String {
+    // Convert days since the epoch to a civil date (Howard Hinnant's algorithm)
+    let days = timestamp.div_euclid(86400) + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METAINFO: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<component type="desktop-application">
+  <id>org.example.App</id>
+  <metadata_license>CC0-1.0</metadata_license>
+  <project_license>GPL-3.0-or-later</project_license>
+  <name>Example</name>
+  <name xml:lang="it">Esempio</name>
+  <summary>Do   things
+    quickly</summary>
+  <description>
+    <p>Example does things.</p>
+    <p xml:lang="it">Esempio fa cose.</p>
+    <ul>
+      <li>Fast</li>
+      <li>Small</li>
+    </ul>
+  </description>
+  <url type="bugtracker">https://example.org/issues</url>
+  <url type="homepage">https://example.org</url>
+  <screenshots>
+    <screenshot type="default">
+      <caption>Main window</caption>
+      <image type="thumbnail" width="224">https://example.org/thumb.png</image>
+      <image type="source">https://example.org/main.png</image>
+    </screenshot>
+    <screenshot>https://example.org/legacy.png</screenshot>
+  </screenshots>
+  <releases>
+    <release version="1.0" timestamp="1704067200"/>
+    <release version="1.1" date="2024-03-01">
+      <description><p>Bug fixes</p></description>
+    </release>
+  </releases>
+</component>"#;
+
+    #[test]
+    fn test_parse() {
+        let metadata = AppStreamMetadata::parse(METAINFO).unwrap();
+        assert_eq!(metadata.id.as_deref(), Some("org.example.App"));
+        assert_eq!(metadata.name.as_deref(), Some("Example"));
+        assert_eq!(metadata.summary.as_deref(), Some("Do things quickly"));
+        assert_eq!(
+            metadata.description.as_deref(),
+            Some("Example does things.\n\n• Fast\n• Small")
+        );
+        assert_eq!(
+            metadata.project_license.as_deref(),
+            Some("GPL-3.0-or-later")
+        );
+        assert_eq!(metadata.homepage.as_deref(), Some("https://example.org"));
+        assert_eq!(
+            metadata.screenshots,
+            vec![
+                Screenshot {
+                    image: "https://example.org/main.png".to_string(),
+                    caption: Some("Main window".to_string()),
+                    default: true,
+                },
+                Screenshot {
+                    image: "https://example.org/legacy.png".to_string(),
+                    caption: None,
+                    default: false,
+                },
+            ]
+        );
+        assert_eq!(
+            metadata.releases,
+            vec![
+                Release {
+                    version: "1.1".to_string(),
+                    date: Some("2024-03-01".to_string()),
+                    description: Some("Bug fixes".to_string()),
+                },
+                Release {
+                    version: "1.0".to_string(),
+                    date: Some("2024-01-01".to_string()),
+                    description: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rejects_other_documents() {
+        assert!(AppStreamMetadata::parse("<html></html>").is_err());
+        assert!(AppStreamMetadata::parse("not xml").is_err());
+    }
+}
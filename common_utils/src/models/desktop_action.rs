@@ -0,0 +1,11 @@
+/// An additional action of an application, defined by a `[Desktop Action <id>]` group
+/// and listed in the `Actions=` key of the desktop entry
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DesktopAction {
+    /// The action identifier, as listed in `Actions=`
+    pub id: String,
+    pub name: String,
+    pub exec: Option<String>,
+    pub icon: Option<String>,
+}
@@ -0,0 +1,180 @@
+use std::fmt;
+use std::path::Path;
+
+/// Field codes which expand to files, URLs or entry properties when an app is launched
+const FIELD_CODES: [&str; 13] = [
+    "%f", "%F", "%u", "%U", "%i", "%c", "%k", "%d", "%D", "%n", "%N", "%v", "%m",
+];
+
+/// The command line of an `Exec=` key, parsed following the quoting rules
+/// of the Desktop Entry specification.
+/// Arguments are kept unquoted, field codes are kept as written.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecCommand {
+    pub program: String,
+    pub arguments: Vec<String>,
+}
+
+impl ExecCommand {
+    pub fn new(program: String) -> Self {
+        Self {
+            program,
+            arguments: Vec::new(),
+        }
+    }
+
+    /// Parse the value of an `Exec=` key
+    pub fn parse(exec: &str) -> Result<Self, String> {
+        let mut arguments = Vec::new();
+        let mut current: Option<String> = None;
+        let mut quoted = false;
+        let mut chars = exec.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => {
+                    quoted = !quoted;
+                    current.get_or_insert_with(String::new);
+                }
+                '\\' if quoted => match chars.next() {
+                    Some(escaped) => current.get_or_insert_with(String::new).push(escaped),
+                    None => return Err(format!("Unterminated escape in Exec value: {}", exec)),
+                },
+                c if c.is_whitespace() && !quoted => {
+                    arguments.extend(current.take());
+                }
+                c => current.get_or_insert_with(String::new).push(c),
+            }
+        }
+        if quoted {
+            return Err(format!("Unterminated quote in Exec value: {}", exec));
+        }
+        arguments.extend(current);
+
+        if arguments.is_empty() {
+            return Err("Empty Exec value".to_string());
+        }
+        let program = arguments.remove(0);
+        Ok(Self { program, arguments })
+    }
+
+    /// Replace the program, keeping the arguments and field codes
+    pub fn with_program(mut self, program: String) -> Self {
+        self.program = program;
+        self
+    }
+
+    /// Append an argument, unless it is already present
+    pub fn add_argument(&mut self, argument: &str) -> &mut Self {
+        if !self.has_argument(argument) {
+            self.arguments.push(argument.to_string());
+        }
+        self
+    }
+
+    pub fn has_argument(&self, argument: &str) -> bool {
+        self.arguments.iter().any(|a| a == argument)
+    }
+
+    /// Move the program to another directory, if it is inside `from`.
+    /// Returns whether the program was moved.
+    pub fn relocate(&mut self, from: &Path, to: &Path) -> bool {
+        match Path::new(&self.program).strip_prefix(from) {
+            Ok(relative) => {
+                self.program = to.join(relative).to_string_lossy().to_string();
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// The program and the arguments to launch the app without files or URLs:
+    /// field codes are dropped and `%%` is unescaped
+    pub fn launch_arguments(&self) -> Vec<String> {
+        std::iter::once(&self.program)
+            .chain(
+                self.arguments
+                    .iter()
+                    .filter(|argument| !FIELD_CODES.contains(&argument.as_str())),
+            )
+            .map(|argument| argument.replace("%%", "%"))
+            .collect()
+    }
+}
+
+/// Format the command as an `Exec=` value, quoting the arguments which need it
+impl fmt::Display for ExecCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", quote_argument(&self.program))?;
+        for argument in &self.arguments {
+            write!(f, " {}", quote_argument(argument))?;
+        }
+        Ok(())
+    }
+}
+
+/// Quote an argument, if it is empty or contains reserved characters
+fn quote_argument(argument: &str) -> String {
+    let reserved = |c: char| c.is_whitespace() || "\"'\\><~|&;$*?#()`".contains(c);
+    if !argument.is_empty() && !argument.contains(reserved) {
+        return argument.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for c in argument.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let command =
+            ExecCommand::parse(r#""/opt/My Apps/app" --new-window %U "a \"b\"" 100%%"#).unwrap();
+        assert_eq!(command.program, "/opt/My Apps/app");
+        assert_eq!(
+            command.arguments,
+            vec!["--new-window", "%U", "a \"b\"", "100%%"]
+        );
+        assert_eq!(
+            command.launch_arguments(),
+            vec!["/opt/My Apps/app", "--new-window", "a \"b\"", "100%"]
+        );
+
+        assert!(ExecCommand::parse("").is_err());
+        assert!(ExecCommand::parse(r#""/opt/app"#).is_err());
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        let command = ExecCommand::parse("firefox --new-window %u")
+            .unwrap()
+            .with_program("/home/me/My Apps/Firefox.AppImage".to_string());
+        assert_eq!(
+            command.to_string(),
+            r#""/home/me/My Apps/Firefox.AppImage" --new-window %u"#
+        );
+        assert_eq!(ExecCommand::parse(&command.to_string()).unwrap(), command);
+
+        let mut command = ExecCommand::new("/opt/app".to_string());
+        command
+            .add_argument("--no-sandbox")
+            .add_argument("--no-sandbox");
+        assert_eq!(command.to_string(), "/opt/app --no-sandbox");
+    }
+
+    #[test]
+    fn test_relocate() {
+        let mut command = ExecCommand::parse("/opt/apps/app.AppImage %F").unwrap();
+        assert!(!command.relocate(Path::new("/opt/app"), Path::new("/srv")));
+        assert!(command.relocate(Path::new("/opt/apps/"), Path::new("/srv/apps")));
+        assert_eq!(command.to_string(), "/srv/apps/app.AppImage %F");
+    }
+}
@@ -0,0 +1,44 @@
+use std::fmt;
+
+/// What to do when the AppImage being installed is an app which is already installed
+#[derive(
+    serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExistingAppPolicy {
+    /// Replace the installed app with the same or a newer version, refuse older versions
+    #[default]
+    Upgrade,
+    /// Replace the installed app whatever its version, including with an older one
+    Downgrade,
+    /// Keep the installed version next to the new one, which becomes the version the desktop entry launches
+    SideBySide,
+}
+
+impl ExistingAppPolicy {
+    pub const ALL: [ExistingAppPolicy; 3] = [
+        ExistingAppPolicy::Upgrade,
+        ExistingAppPolicy::Downgrade,
+        ExistingAppPolicy::SideBySide,
+    ];
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim() {
+            "upgrade" => Some(Self::Upgrade),
+            "downgrade" => Some(Self::Downgrade),
+            "side-by-side" => Some(Self::SideBySide),
+            _ => None,
+        }
+    }
+}
+
+/// Format the policy as accepted by `parse` and the backend `--existing` argument
+impl fmt::Display for ExistingAppPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Upgrade => write!(f, "upgrade"),
+            Self::Downgrade => write!(f, "downgrade"),
+            Self::SideBySide => write!(f, "side-by-side"),
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use std::fmt;
+use std::path::PathBuf;
+
+/// Result of the installation of one AppImage of a batch,
+/// which the backend reports on its own line of its standard output
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InstallOutcome {
+    pub file_path: PathBuf,
+    /// Why the installation failed, `None` when the AppImage was installed
+    pub error: Option<String>,
+}
+
+impl InstallOutcome {
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        if let Some(file_path) = line.strip_prefix("installed\t") {
+            return Some(Self {
+                file_path: PathBuf::from(file_path),
+                error: None,
+            });
+        }
+        // the path comes last, since it may contain tabs
+        let (error, file_path) = line.strip_prefix("failed\t")?.split_once('\t')?;
+        Some(Self {
+            file_path: PathBuf::from(file_path),
+            error: Some(error.to_string()),
+        })
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Format the outcome as a single line accepted by `parse`
+impl fmt::Display for InstallOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.error {
+            None => write!(f, "installed\t{}", self.file_path.display()),
+            Some(error) => {
+                let error = error.replace(['\t', '\r', '\n'], " ");
+                write!(f, "failed\t{}\t{}", error, self.file_path.display())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_backend_output() {
+        let installed = InstallOutcome {
+            file_path: PathBuf::from("/home/user/Downloads/App\t1.AppImage"),
+            error: None,
+        };
+        let failed = InstallOutcome {
+            file_path: PathBuf::from("/tmp/Broken.AppImage"),
+            error: Some("Invalid desktop file:\n\tno Exec key".to_string()),
+        };
+        // the outcomes are read back from the output of the backend, among its other lines
+        let output = format!(
+            "Installing 2 AppImages\n{}\r\n{}\nInstalled 1 of 2 AppImages\n",
+            installed, failed
+        );
+
+        let outcomes: Vec<InstallOutcome> =
+            output.lines().filter_map(InstallOutcome::parse).collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                installed,
+                InstallOutcome {
+                    error: Some("Invalid desktop file:  no Exec key".to_string()),
+                    ..failed
+                },
+            ]
+        );
+        assert!(outcomes[0].is_success());
+        assert!(!outcomes[1].is_success());
+    }
+}
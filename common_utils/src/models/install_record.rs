@@ -0,0 +1,157 @@
+use std::path::PathBuf;
+
+use crate::models::install_scope::InstallScope;
+use crate::models::installed_version::InstalledVersion;
+use crate::models::signature_status::SignatureStatus;
+
+/// What AppHub installed for an app, as recorded in the install database
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct InstallRecord {
+    /// Stable identifier of the app, from its desktop file id, its AppStream id or else its content hash.
+    /// Apps recorded before the ids existed keep a slug of their name
+    pub id: String,
+    pub name: String,
+    /// Version of the app, when the AppImage tells it
+    #[serde(default)]
+    pub version: Option<String>,
+    pub scope: InstallScope,
+    /// Path of the installed AppImage
+    pub app_image_path: PathBuf,
+    /// SHA-256 digest of the installed AppImage, hex encoded
+    pub sha256: Option<String>,
+    pub desktop_entries: Vec<PathBuf>,
+    /// Icon files installed in the icon theme
+    pub icon_paths: Vec<PathBuf>,
+    pub metainfo_path: Option<PathBuf>,
+    /// Shared-mime-info packages installed in the MIME database
+    pub mime_packages: Vec<PathBuf>,
+    /// The AppImage file the app was installed from
+    pub source_path: Option<PathBuf>,
+    /// Installation time, in seconds since the Unix epoch
+    pub installed_at: u64,
+    pub signature: Option<SignatureStatus>,
+    pub no_sandbox: bool,
+    /// Whether the app keeps its data in portable home and config directories next to its AppImage
+    #[serde(default)]
+    pub portable: bool,
+    /// The other versions of the app installed side by side, which the desktop entry does not launch
+    #[serde(default)]
+    pub other_versions: Vec<InstalledVersion>,
+}
+
+impl InstallRecord {
+    /// The version the desktop entry launches
+    pub fn active_version(&self) -> InstalledVersion {
+        InstalledVersion {
+            version: self.version.clone(),
+            app_image_path: self.app_image_path.clone(),
+            sha256: self.sha256.clone(),
+            installed_at: self.installed_at,
+            signature: self.signature.clone(),
+        }
+    }
+
+    /// All the installed versions, the active one first
+    pub fn versions(&self) -> Vec<InstalledVersion> {
+        let mut versions = vec![self.active_version()];
+        versions.extend(self.other_versions.iter().cloned());
+        versions
+    }
+
+    /// Make one of the other versions the active one, the active version joins the other ones
+    pub fn activate_version(&mut self, label: &str) -> Result<(), String> {
+        let index = self
+            .other_versions
+            .iter()
+            .position(|version| version.label() == label)
+            .ok_or(format!(
+                "{} {} is not installed side by side",
+                self.name, label
+            ))?;
+        let version = self.other_versions.remove(index);
+        self.other_versions.push(self.active_version());
+
+        self.version = version.version;
+        self.app_image_path = version.app_image_path;
+        self.sha256 = version.sha256;
+        self.installed_at = version.installed_at;
+        self.signature = version.signature;
+        Ok(())
+    }
+
+    /// Forget one of the other versions, the active version cannot be removed this way
+    pub fn remove_version(&mut self, label: &str) -> Result<InstalledVersion, String> {
+        if self.active_version().label() == label {
+            return Err(format!("{} {} is the active version", self.name, label));
+        }
+        let index = self
+            .other_versions
+            .iter()
+            .position(|version| version.label() == label)
+            .ok_or(format!("{} {} is not installed", self.name, label))?;
+        Ok(self.other_versions.remove(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(version: Option<&str>, app_image_path: &str) -> InstalledVersion {
+        InstalledVersion {
+            version: version.map(|version| version.to_string()),
+            app_image_path: PathBuf::from(app_image_path),
+            sha256: None,
+            installed_at: 0,
+            signature: None,
+        }
+    }
+
+    #[test]
+    fn test_switch_versions() {
+        let mut record = InstallRecord {
+            id: "org.example.Tool".to_string(),
+            name: "Tool".to_string(),
+            version: Some("2.0".to_string()),
+            scope: InstallScope::User,
+            app_image_path: PathBuf::from("/apps/org.example.Tool-2.0.AppImage"),
+            sha256: None,
+            desktop_entries: Vec::new(),
+            icon_paths: Vec::new(),
+            metainfo_path: None,
+            mime_packages: Vec::new(),
+            source_path: None,
+            installed_at: 0,
+            signature: None,
+            no_sandbox: false,
+            portable: false,
+            other_versions: vec![
+                version(Some("1.0"), "/apps/org.example.Tool-1.0.AppImage"),
+                version(None, "/apps/Tool.AppImage"),
+            ],
+        };
+        let labels = |record: &InstallRecord| -> Vec<String> {
+            record
+                .versions()
+                .iter()
+                .map(|version| version.label())
+                .collect()
+        };
+        assert_eq!(labels(&record), vec!["2.0", "1.0", "Tool.AppImage"]);
+
+        record.activate_version("1.0").unwrap();
+        assert_eq!(record.version.as_deref(), Some("1.0"));
+        assert_eq!(
+            record.app_image_path,
+            PathBuf::from("/apps/org.example.Tool-1.0.AppImage")
+        );
+        assert_eq!(labels(&record), vec!["1.0", "Tool.AppImage", "2.0"]);
+        assert!(record.activate_version("3.0").is_err());
+
+        assert!(record.remove_version("1.0").is_err());
+        let removed = record.remove_version("Tool.AppImage").unwrap();
+        assert_eq!(removed.app_image_path, PathBuf::from("/apps/Tool.AppImage"));
+        assert_eq!(labels(&record), vec!["1.0", "2.0"]);
+    }
+}
@@ -0,0 +1,85 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::xdg_helpers::{cache_home, data_home, state_home};
+
+/// Who an AppImage is installed for, which decides where its files are written
+#[derive(
+    serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum InstallScope {
+    /// Only the current user: files go under the home directory and no privileges are needed
+    #[default]
+    User,
+    /// All the users of the system: files go under `/usr/share` and root privileges are needed
+    System,
+}
+
+impl InstallScope {
+    pub const ALL: [InstallScope; 2] = [InstallScope::User, InstallScope::System];
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim() {
+            "user" => Some(Self::User),
+            "system" => Some(Self::System),
+            _ => None,
+        }
+    }
+
+    /// Whether installing in this scope needs root privileges
+    pub fn needs_privileges(&self) -> bool {
+        matches!(self, Self::System)
+    }
+
+    /// The base directory of the desktop entries, icons and MIME database:
+    /// `$XDG_DATA_HOME` for the user, `/usr/share` for the system
+    pub fn data_dir(&self) -> Result<PathBuf, &'static str> {
+        match self {
+            Self::User => data_home().ok_or("Failed to get user data directory"),
+            Self::System => Ok(PathBuf::from("/usr/share")),
+        }
+    }
+
+    /// The directory of the AppHub state, such as the journals of the pending transactions:
+    /// `$XDG_STATE_HOME/app-hub` for the user, `/var/lib/app-hub` for the system
+    pub fn state_dir(&self) -> Result<PathBuf, &'static str> {
+        match self {
+            Self::User => state_home()
+                .map(|state_home| state_home.join("app-hub"))
+                .ok_or("Failed to get user state directory"),
+            Self::System => Ok(PathBuf::from("/var/lib/app-hub")),
+        }
+    }
+
+    /// The staging directory of the AppImages downloaded before their installation:
+    /// `$XDG_CACHE_HOME/app-hub/downloads` for the user, `/var/cache/app-hub/downloads` for the system
+    pub fn download_dir(&self) -> Result<PathBuf, &'static str> {
+        match self {
+            Self::User => cache_home()
+                .map(|cache_home| cache_home.join("app-hub").join("downloads"))
+                .ok_or("Failed to get user cache directory"),
+            Self::System => Ok(PathBuf::from("/var/cache/app-hub/downloads")),
+        }
+    }
+
+    /// The default directory of the installed AppImages
+    pub fn default_installation_dir(&self) -> Result<PathBuf, &'static str> {
+        match self {
+            Self::User => dirs::home_dir()
+                .map(|home_dir| home_dir.join("Applications"))
+                .ok_or("Failed to get home directory"),
+            Self::System => Ok(PathBuf::from("/opt/AppImages")),
+        }
+    }
+}
+
+/// Format the scope as accepted by `parse` and the backend `--scope` argument
+impl fmt::Display for InstallScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::User => write!(f, "user"),
+            Self::System => write!(f, "system"),
+        }
+    }
+}
@@ -1,11 +1,43 @@
 use std::path::PathBuf;
 
+use crate::models::app_stream_metadata::AppStreamMetadata;
+use crate::models::desktop_action::DesktopAction;
+use crate::models::exec_command::ExecCommand;
+use crate::models::install_scope::InstallScope;
+use crate::models::installed_version::InstalledVersion;
+use crate::models::localized_string::LocalizedString;
+use crate::models::signature_status::SignatureStatus;
+use crate::models::update_info::UpdateInfo;
+
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct InstalledApp {
+    /// Stable identifier of the app in the install database
+    pub id: String,
     pub name: String,
+    /// The name with its translations
+    pub localized_name: LocalizedString,
+    pub generic_name: Option<LocalizedString>,
+    pub comment: Option<LocalizedString>,
     pub icon_path: Option<PathBuf>,
+    /// Path of the installed AppImage
     pub app_path: String,
+    /// Command used to launch the app
+    pub exec: ExecCommand,
     pub version: Option<String>,
+    /// The versions installed side by side, which the desktop entry does not launch
+    pub other_versions: Vec<InstalledVersion>,
+    /// The portable home and config directories holding the data of the app
+    pub portable_dirs: Vec<PathBuf>,
     pub categories: Option<String>,
+    pub update_info: Option<UpdateInfo>,
+    pub signature: Option<SignatureStatus>,
+    /// AppStream metadata shipped in the AppImage
+    pub metadata: Option<AppStreamMetadata>,
+    /// Additional actions, such as opening a new window
+    pub actions: Vec<DesktopAction>,
+    /// MIME types the app can open
+    pub mime_types: Vec<String>,
+    /// Whether the app is installed for the current user or for all users
+    pub scope: InstallScope,
 }
@@ -0,0 +1,36 @@
+use std::path::PathBuf;
+
+use crate::app_id_helpers::version_label;
+use crate::models::signature_status::SignatureStatus;
+
+/// A version of an installed app, kept installed next to the version its desktop entry launches
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct InstalledVersion {
+    pub version: Option<String>,
+    /// Path of the installed AppImage
+    pub app_image_path: PathBuf,
+    /// SHA-256 digest of the installed AppImage, hex encoded
+    pub sha256: Option<String>,
+    /// Installation time, in seconds since the Unix epoch
+    pub installed_at: u64,
+    pub signature: Option<SignatureStatus>,
+}
+
+impl InstalledVersion {
+    /// How the version is named to the user and in the backend `--app-version` argument:
+    /// its version, else the start of its hash, else the name of its AppImage
+    pub fn label(&self) -> String {
+        let label = version_label(
+            self.version.as_deref(),
+            self.sha256.as_deref().unwrap_or_default(),
+        );
+        if !label.is_empty() {
+            return label;
+        }
+        self.app_image_path
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+
+/// A localestring value of a desktop entry, with all its translations
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalizedString {
+    /// The untranslated value
+    pub default: String,
+    /// Translations by desktop entry locale, such as `pt_BR` or `sr@latin`
+    pub translations: BTreeMap<String, String>,
+}
+
+impl LocalizedString {
+    pub fn new(default: String) -> Self {
+        Self {
+            default,
+            translations: BTreeMap::new(),
+        }
+    }
+
+    /// The best translation for the given languages, in order of preference.
+    /// Languages are BCP 47 tags (`de-AT`) or POSIX locales (`de_AT.UTF-8@euro`).
+    /// Each language is matched following the Desktop Entry specification:
+    /// `lang_COUNTRY@MODIFIER`, `lang_COUNTRY`, `lang@MODIFIER`, `lang`.
+    /// The untranslated value is used when no language matches.
+    pub fn get(&self, languages: &[String]) -> &str {
+        languages
+            .iter()
+            .filter_map(|language| DesktopLocale::parse(language))
+            .flat_map(|locale| locale.candidates())
+            .find_map(|candidate| self.translations.get(&candidate))
+            .unwrap_or(&self.default)
+    }
+}
+
+/// A locale in the form used by desktop entries: `lang_COUNTRY@MODIFIER`
+#[derive(Debug, PartialEq, Eq)]
+struct DesktopLocale {
+    lang: String,
+    country: Option<String>,
+    modifier: Option<String>,
+}
+
+impl DesktopLocale {
+    fn parse(language: &str) -> Option<Self> {
+        // POSIX locale: lang_COUNTRY.ENCODING@MODIFIER
+        if language.contains('_') || language.contains('@') || language.contains('.') {
+            let (rest, modifier) = match language.split_once('@') {
+                Some((rest, modifier)) => (rest, Some(modifier.to_string())),
+                None => (language, None),
+            };
+            let rest = rest.split('.').next().unwrap_or(rest);
+            let (lang, country) = match rest.split_once('_') {
+                Some((lang, country)) => (lang, Some(country.to_string())),
+                None => (rest, None),
+            };
+            return Self::new(lang, country, modifier);
+        }
+
+        // BCP 47 tag: lang-Script-REGION-variants
+        let mut subtags = language.split('-');
+        let lang = subtags.next()?;
+        let mut country = None;
+        let mut modifier = None;
+        for subtag in subtags {
+            if subtag.len() == 4 && subtag.chars().all(|c| c.is_ascii_alphabetic()) {
+                modifier = script_modifier(subtag).map(|modifier| modifier.to_string());
+            } else if (subtag.len() == 2 && subtag.chars().all(|c| c.is_ascii_alphabetic()))
+                || (subtag.len() == 3 && subtag.chars().all(|c| c.is_ascii_digit()))
+            {
+                country = Some(subtag.to_uppercase());
+            }
+        }
+        Self::new(lang, country, modifier)
+    }
+
+    fn new(lang: &str, country: Option<String>, modifier: Option<String>) -> Option<Self> {
+        if lang.is_empty() || lang == "C" || lang == "POSIX" {
+            return None;
+        }
+        Some(Self {
+            lang: lang.to_lowercase(),
+            country,
+            modifier,
+        })
+    }
+
+    /// Locales to look up, from the most to the least specific
+    fn candidates(&self) -> Vec<String> {
+        let mut candidates = Vec::new();
+        if let (Some(country), Some(modifier)) = (&self.country, &self.modifier) {
+            candidates.push(format!("{}_{}@{}", self.lang, country, modifier));
+        }
+        if let Some(country) = &self.country {
+            candidates.push(format!("{}_{}", self.lang, country));
+        }
+        if let Some(modifier) = &self.modifier {
+            candidates.push(format!("{}@{}", self.lang, modifier));
+        }
+        candidates.push(self.lang.clone());
+        candidates
+    }
+}
+
+/// The locale modifier used by desktop entries for a script subtag
+fn script_modifier(script: &str) -> Option<&'static str> {
+    match script.to_lowercase().as_str() {
+        "latn" => Some("latin"),
+        "cyrl" => Some("cyrillic"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn languages(languages: &[&str]) -> Vec<String> {
+        languages
+            .iter()
+            .map(|language| language.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_fallback_chain() {
+        let mut name = LocalizedString::new("Text Editor".to_string());
+        for (locale, value) in [
+            ("de", "Texteditor"),
+            ("pt_BR", "Editor de texto"),
+            ("sr", "Уређивач текста"),
+            ("sr@latin", "Uređivač teksta"),
+        ] {
+            name.translations
+                .insert(locale.to_string(), value.to_string());
+        }
+
+        assert_eq!(name.get(&languages(&["de-AT"])), "Texteditor");
+        assert_eq!(name.get(&languages(&["de_AT.UTF-8@euro"])), "Texteditor");
+        assert_eq!(name.get(&languages(&["pt-BR"])), "Editor de texto");
+        assert_eq!(name.get(&languages(&["pt-PT"])), "Text Editor");
+        assert_eq!(name.get(&languages(&["sr-Latn-RS"])), "Uređivač teksta");
+        assert_eq!(name.get(&languages(&["sr_RS@latin"])), "Uređivač teksta");
+        assert_eq!(name.get(&languages(&["sr-RS"])), "Уређивач текста");
+        assert_eq!(name.get(&languages(&["fr-FR", "de"])), "Texteditor");
+        assert_eq!(name.get(&languages(&["C"])), "Text Editor");
+        assert_eq!(name.get(&[]), "Text Editor");
+    }
+}
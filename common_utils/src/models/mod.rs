@@ -1 +1,13 @@
-pub mod installed_app;
\ No newline at end of file
+pub mod app_identity;
+pub mod app_stream_metadata;
+pub mod desktop_action;
+pub mod exec_command;
+pub mod existing_app_policy;
+pub mod install_outcome;
+pub mod install_record;
+pub mod install_scope;
+pub mod installed_app;
+pub mod installed_version;
+pub mod localized_string;
+pub mod signature_status;
+pub mod update_info;
@@ -0,0 +1,60 @@
+use std::fmt;
+
+/// Result of the verification of the signature embedded in an AppImage
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", tag = "status")]
+pub enum SignatureStatus {
+    /// Valid signature made by a key of the AppHub keyring
+    Trusted { fingerprint: String },
+    /// Valid signature made by the key embedded in the AppImage, which is not in the AppHub keyring
+    UnknownKey { fingerprint: String },
+    /// The AppImage carries no signature
+    Unsigned,
+    /// The signature does not match the AppImage content or cannot be checked
+    Invalid,
+}
+
+impl SignatureStatus {
+    /// Parse the value stored in the `X-AppHub-Signature` desktop entry key
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().split_once(';') {
+            Some(("trusted", fingerprint)) => Some(Self::Trusted {
+                fingerprint: fingerprint.to_string(),
+            }),
+            Some(("unknown-key", fingerprint)) => Some(Self::UnknownKey {
+                fingerprint: fingerprint.to_string(),
+            }),
+            Some(_) => None,
+            None => match raw.trim() {
+                "unsigned" => Some(Self::Unsigned),
+                "invalid" => Some(Self::Invalid),
+                _ => None,
+            },
+        }
+    }
+
+    /// Whether the AppImage carries a signature matching its content
+    pub fn is_signed(&self) -> bool {
+        matches!(self, Self::Trusted { .. } | Self::UnknownKey { .. })
+    }
+
+    /// Fingerprint of the signing key, if the signature is valid
+    pub fn fingerprint(&self) -> Option<&str> {
+        match self {
+            Self::Trusted { fingerprint } | Self::UnknownKey { fingerprint } => Some(fingerprint),
+            Self::Unsigned | Self::Invalid => None,
+        }
+    }
+}
+
+/// Format the status as stored in the `X-AppHub-Signature` desktop entry key
+impl fmt::Display for SignatureStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Trusted { fingerprint } => write!(f, "trusted;{}", fingerprint),
+            Self::UnknownKey { fingerprint } => write!(f, "unknown-key;{}", fingerprint),
+            Self::Unsigned => write!(f, "unsigned"),
+            Self::Invalid => write!(f, "invalid"),
+        }
+    }
+}
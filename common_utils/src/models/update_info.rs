@@ -0,0 +1,130 @@
+use std::fmt;
+
+/// Update information embedded in an AppImage, as defined by the AppImage specification.
+/// It tells where newer versions of the AppImage can be found.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", tag = "transport")]
+pub enum UpdateInfo {
+    /// `zsync|<zsync file URL>`
+    Zsync { url: String },
+    /// `gh-releases-zsync|<user>|<repo>|<tag>|<zsync file name>`
+    GhReleasesZsync {
+        user: String,
+        repo: String,
+        tag: String,
+        filename: String,
+    },
+    /// `pling-v1-zsync|<product id>|<zsync file name>`
+    PlingV1Zsync { product_id: String, filename: String },
+    /// Any other transport, kept as the raw string
+    Unknown { raw: String },
+}
+
+impl UpdateInfo {
+    /// Parse an update information string.
+    /// Returns `None` if the string is empty (the AppImage does not update itself).
+    pub fn parse(raw: &str) -> Option<Self> {
+        let raw = raw.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+        if raw.is_empty() {
+            return None;
+        }
+
+        let parts: Vec<&str> = raw.split('|').collect();
+        let update_info = match parts.as_slice() {
+            ["zsync", url] => Self::Zsync {
+                url: url.to_string(),
+            },
+            ["gh-releases-zsync", user, repo, tag, filename] => Self::GhReleasesZsync {
+                user: user.to_string(),
+                repo: repo.to_string(),
+                tag: tag.to_string(),
+                filename: filename.to_string(),
+            },
+            ["pling-v1-zsync", product_id, filename] => Self::PlingV1Zsync {
+                product_id: product_id.to_string(),
+                filename: filename.to_string(),
+            },
+            _ => Self::Unknown {
+                raw: raw.to_string(),
+            },
+        };
+        Some(update_info)
+    }
+
+    /// Short human readable description of where updates come from
+    pub fn source(&self) -> String {
+        match self {
+            Self::Zsync { url } => url.clone(),
+            Self::GhReleasesZsync {
+                user, repo, tag, ..
+            } => format!("GitHub {}/{} ({})", user, repo, tag),
+            Self::PlingV1Zsync { product_id, .. } => format!("Pling {}", product_id),
+            Self::Unknown { raw } => raw.clone(),
+        }
+    }
+}
+
+/// Format the update information back to the string stored in the AppImage
+impl fmt::Display for UpdateInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Zsync { url } => write!(f, "zsync|{}", url),
+            Self::GhReleasesZsync {
+                user,
+                repo,
+                tag,
+                filename,
+            } => write!(f, "gh-releases-zsync|{}|{}|{}|{}", user, repo, tag, filename),
+            Self::PlingV1Zsync {
+                product_id,
+                filename,
+            } => write!(f, "pling-v1-zsync|{}|{}", product_id, filename),
+            Self::Unknown { raw } => write!(f, "{}", raw),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_transports() {
+        assert_eq!(
+            UpdateInfo::parse("zsync|https://example.com/App-latest.AppImage.zsync"),
+            Some(UpdateInfo::Zsync {
+                url: "https://example.com/App-latest.AppImage.zsync".to_string()
+            })
+        );
+        assert_eq!(
+            UpdateInfo::parse("gh-releases-zsync|probonopd|Pext|latest|Pext-*x86_64.AppImage.zsync\0\0\0"),
+            Some(UpdateInfo::GhReleasesZsync {
+                user: "probonopd".to_string(),
+                repo: "Pext".to_string(),
+                tag: "latest".to_string(),
+                filename: "Pext-*x86_64.AppImage.zsync".to_string(),
+            })
+        );
+        assert_eq!(
+            UpdateInfo::parse("pling-v1-zsync|1234|App-*.zsync"),
+            Some(UpdateInfo::PlingV1Zsync {
+                product_id: "1234".to_string(),
+                filename: "App-*.zsync".to_string(),
+            })
+        );
+        assert_eq!(
+            UpdateInfo::parse("bintray-zsync|a|b|c|d"),
+            Some(UpdateInfo::Unknown {
+                raw: "bintray-zsync|a|b|c|d".to_string()
+            })
+        );
+        assert_eq!(UpdateInfo::parse("\0\0\0\0"), None);
+
+        // the installed desktop entry keeps the value without the padding of the ELF section
+        let update_info = UpdateInfo::parse("gh-releases-zsync|user|repo|latest|App-*.zsync\0\0");
+        assert_eq!(
+            update_info.unwrap().to_string(),
+            "gh-releases-zsync|user|repo|latest|App-*.zsync"
+        );
+    }
+}
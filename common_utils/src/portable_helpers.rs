@@ -0,0 +1,127 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use log::{info, warn};
+
+use crate::file_system_helpers::copy_dir_all;
+
+/// Suffixes of the sidecar directories the AppImage runtime uses as `$HOME` and `$XDG_CONFIG_HOME`
+const PORTABLE_SUFFIXES: [&str; 2] = ["home", "config"];
+
+/// The portable home and config directories of an AppImage: `<AppImage>.home` and `<AppImage>.config`
+pub fn portable_dirs(app_image_path: &Path) -> Vec<PathBuf> {
+    PORTABLE_SUFFIXES
+        .iter()
+        .map(|suffix| {
+            let mut dir = app_image_path.as_os_str().to_owned();
+            dir.push(".");
+            dir.push(suffix);
+            PathBuf::from(dir)
+        })
+        .collect()
+}
+
+/// The portable directories of an AppImage which exist
+pub fn existing_portable_dirs(app_image_path: &Path) -> Vec<PathBuf> {
+    portable_dirs(app_image_path)
+        .into_iter()
+        .filter(|dir| dir.is_dir())
+        .collect()
+}
+
+/// Create the portable directories of an AppImage, only readable by their owner,
+/// so the app keeps its data next to it instead of in the home directory
+pub fn create_portable_dirs(app_image_path: &Path) -> Result<(), String> {
+    for dir in portable_dirs(app_image_path) {
+        if dir.is_dir() {
+            continue;
+        }
+        fs::create_dir(&dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))
+            .map_err(|e| format!("Failed to set permissions of {:?}: {}", dir, e))?;
+        info!("Created portable directory {:?}", dir);
+    }
+    Ok(())
+}
+
+/// Move the portable directories of an AppImage along with it, when they exist.
+/// They are renamed when possible, so their links and permissions are kept
+pub fn move_portable_dirs(from: &Path, to: &Path) -> Result<(), String> {
+    for (from_dir, to_dir) in portable_dirs(from).into_iter().zip(portable_dirs(to)) {
+        if !from_dir.is_dir() {
+            continue;
+        }
+        if to_dir.exists() {
+            return Err(format!("Portable directory already exists: {:?}", to_dir));
+        }
+        if let Err(e) = fs::rename(&from_dir, &to_dir) {
+            // another file system, such as a USB drive
+            warn!("Failed to rename {:?}, copying it: {}", from_dir, e);
+            copy_dir_all(&from_dir, &to_dir)
+                .map_err(|e| format!("Failed to copy {:?}: {}", from_dir, e))?;
+            fs::remove_dir_all(&from_dir)
+                .map_err(|e| format!("Failed to remove {:?}: {}", from_dir, e))?;
+        }
+        info!("Moved portable directory {:?} to {:?}", from_dir, to_dir);
+    }
+    Ok(())
+}
+
+/// Remove the portable directories of an AppImage, with the data the app stored in them.
+/// Returns the removed directories
+pub fn remove_portable_dirs(app_image_path: &Path) -> Result<Vec<PathBuf>, String> {
+    let dirs = existing_portable_dirs(app_image_path);
+    for dir in &dirs {
+        fs::remove_dir_all(dir).map_err(|e| format!("Failed to remove {:?}: {}", dir, e))?;
+        info!("Removed portable directory {:?}", dir);
+    }
+    Ok(dirs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_portable_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_image_path = dir.path().join("apphub-org.example.App.AppImage");
+        assert_eq!(
+            portable_dirs(&app_image_path),
+            vec![
+                dir.path().join("apphub-org.example.App.AppImage.home"),
+                dir.path().join("apphub-org.example.App.AppImage.config"),
+            ]
+        );
+        assert!(existing_portable_dirs(&app_image_path).is_empty());
+
+        create_portable_dirs(&app_image_path).unwrap();
+        let home = dir.path().join("apphub-org.example.App.AppImage.home");
+        assert_eq!(
+            fs::metadata(&home).unwrap().permissions().mode() & 0o777,
+            0o700
+        );
+        fs::write(home.join("settings.ini"), "theme=dark").unwrap();
+        // creating them again keeps the data
+        create_portable_dirs(&app_image_path).unwrap();
+
+        let moved_path = dir.path().join("moved").join("App.AppImage");
+        fs::create_dir(moved_path.parent().unwrap()).unwrap();
+        move_portable_dirs(&app_image_path, &moved_path).unwrap();
+        assert!(existing_portable_dirs(&app_image_path).is_empty());
+        assert_eq!(
+            fs::read_to_string(portable_dirs(&moved_path)[0].join("settings.ini")).unwrap(),
+            "theme=dark"
+        );
+
+        create_portable_dirs(&app_image_path).unwrap();
+        assert!(move_portable_dirs(&app_image_path, &moved_path).is_err());
+
+        assert_eq!(
+            remove_portable_dirs(&moved_path).unwrap(),
+            portable_dirs(&moved_path)
+        );
+        assert!(existing_portable_dirs(&moved_path).is_empty());
+    }
+}
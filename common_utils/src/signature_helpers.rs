@@ -0,0 +1,416 @@
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use log::{debug, info};
+use sha2::{Digest, Sha256};
+
+use crate::app_image_reader::AppImageReader;
+use crate::elf::ElfSection;
+use crate::models::signature_status::SignatureStatus;
+
+/// ELF section holding the ASCII armored signature of the AppImage digest
+pub const SIGNATURE_SECTION: &str = ".sha256_sig";
+
+/// ELF section holding the ASCII armored public key of the signer
+pub const KEY_SECTION: &str = ".sig_key";
+
+/// System wide keyring, managed by the administrator
+const SYSTEM_KEYRING_DIR: &str = "/etc/apphub/keyring";
+
+/// Extensions of the public key files read from the keyring directories
+const KEY_FILE_EXTENSIONS: [&str; 4] = ["asc", "gpg", "key", "pub"];
+
+/// Directories holding the public keys AppHub trusts
+pub fn keyring_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![PathBuf::from(SYSTEM_KEYRING_DIR)];
+    if let Some(data_dir) = dirs::data_dir() {
+        dirs.push(data_dir.join("apphub").join("keyring"));
+    }
+    dirs
+}
+
+/// Compute the hex encoded SHA-256 digest of an AppImage,
+/// with the content of the signature and key sections replaced by zeros
+pub fn app_image_digest(path: &Path, zeroed_sections: &[&ElfSection]) -> Result<String, String> {
+    let mut file = File::open(path).map_err(|e| format!("Failed to open AppImage: {}", e))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut position = 0u64;
+
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .map_err(|e| format!("Failed to read AppImage: {}", e))?;
+        if read == 0 {
+            break;
+        }
+        let chunk = &mut buffer[..read];
+        let chunk_end = position + read as u64;
+
+        for section in zeroed_sections {
+            let start = section.offset.max(position);
+            let end = (section.offset + section.size).min(chunk_end);
+            if start < end {
+                chunk[(start - position) as usize..(end - position) as usize].fill(0);
+            }
+        }
+
+        hasher.update(&chunk[..]);
+        position = chunk_end;
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Verify the signature embedded in an AppImage.
+/// The signature is checked first against the keys found in `keyring_dirs`,
+/// then against the key embedded in the AppImage.
+pub fn verify_app_image_signature(
+    reader: &AppImageReader,
+    keyring_dirs: &[PathBuf],
+) -> Result<SignatureStatus, String> {
+    let signature = match reader.read_section(SIGNATURE_SECTION)? {
+        Some(signature) => trim_section(signature),
+        None => Vec::new(),
+    };
+    if signature.is_empty() {
+        info!("The AppImage is not signed");
+        return Ok(SignatureStatus::Unsigned);
+    }
+
+    let zeroed_sections: Vec<&ElfSection> = [SIGNATURE_SECTION, KEY_SECTION]
+        .iter()
+        .filter_map(|name| reader.elf().section(name))
+        .collect();
+    let digest = app_image_digest(reader.path(), &zeroed_sections)?;
+    debug!("AppImage digest: {}", digest);
+
+    // Use a throwaway GnuPG home, so the keyring of the user is never involved
+    let work_dir =
+        tempfile::tempdir().map_err(|e| format!("Failed to create temporary directory: {}", e))?;
+    let gpg_home = work_dir.path().join("gnupg");
+    let digest_path = work_dir.path().join("digest");
+    let signature_path = work_dir.path().join("digest.sig");
+    fs::create_dir(&gpg_home)
+        .and_then(|_| fs::write(&digest_path, &digest))
+        .and_then(|_| fs::write(&signature_path, &signature))
+        .map_err(|e| format!("Failed to prepare signature verification: {}", e))?;
+    set_private_permissions(&gpg_home);
+
+    for key_path in keyring_dirs.iter().flat_map(|dir| find_key_files(dir)) {
+        debug!("Importing trusted key {:?}", key_path);
+        gpg_import(&gpg_home, &key_path)?;
+    }
+
+    match gpg_verify(&gpg_home, &signature_path, &digest_path)? {
+        Verification::Valid(fingerprint) => {
+            info!("Signature made by trusted key {}", fingerprint);
+            return Ok(SignatureStatus::Trusted { fingerprint });
+        }
+        Verification::Bad => {
+            info!("The AppImage signature does not match its content");
+            return Ok(SignatureStatus::Invalid);
+        }
+        Verification::MissingKey => {}
+    }
+
+    // The signing key is not trusted, check the signature with the embedded key
+    let key = match reader.read_section(KEY_SECTION)? {
+        Some(key) => trim_section(key),
+        None => Vec::new(),
+    };
+    if key.is_empty() {
+        info!("The AppImage is signed, but carries no key to check the signature");
+        return Ok(SignatureStatus::Invalid);
+    }
+    let key_path = work_dir.path().join("embedded.asc");
+    fs::write(&key_path, &key).map_err(|e| format!("Failed to write embedded key: {}", e))?;
+    gpg_import(&gpg_home, &key_path)?;
+
+    match gpg_verify(&gpg_home, &signature_path, &digest_path)? {
+        Verification::Valid(fingerprint) => {
+            info!("Signature made by unknown key {}", fingerprint);
+            Ok(SignatureStatus::UnknownKey { fingerprint })
+        }
+        Verification::Bad | Verification::MissingKey => {
+            info!("The AppImage signature does not match the embedded key");
+            Ok(SignatureStatus::Invalid)
+        }
+    }
+}
+
+enum Verification {
+    Valid(String),
+    Bad,
+    MissingKey,
+}
+
+fn gpg_import(gpg_home: &Path, key_path: &Path) -> Result<(), String> {
+    let output = Command::new("gpg")
+        .arg("--homedir")
+        .arg(gpg_home)
+        .args(["--batch", "--quiet", "--import"])
+        .arg(key_path)
+        .output()
+        .map_err(|e| format!("Failed to run gpg: {}", e))?;
+    if !output.status.success() {
+        debug!(
+            "Failed to import key {:?}: {}",
+            key_path,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(())
+}
+
+fn gpg_verify(
+    gpg_home: &Path,
+    signature_path: &Path,
+    digest_path: &Path,
+) -> Result<Verification, String> {
+    let output = Command::new("gpg")
+        .arg("--homedir")
+        .arg(gpg_home)
+        .args(["--batch", "--status-fd", "1", "--verify"])
+        .arg(signature_path)
+        .arg(digest_path)
+        .output()
+        .map_err(|e| format!("Failed to run gpg: {}", e))?;
+
+    let status = String::from_utf8_lossy(&output.stdout);
+    debug!("gpg status: {}", status);
+    Ok(parse_gpg_status(&status))
+}
+
+/// Interpret the machine readable output of `gpg --status-fd`
+fn parse_gpg_status(status: &str) -> Verification {
+    let mut missing_key = false;
+    for line in status.lines() {
+        let mut fields = line.split_whitespace();
+        if fields.next() != Some("[GNUPG:]") {
+            continue;
+        }
+        match fields.next() {
+            Some("VALIDSIG") => {
+                if let Some(fingerprint) = fields.next() {
+                    return Verification::Valid(fingerprint.to_string());
+                }
+            }
+            Some("BADSIG") => return Verification::Bad,
+            Some("NO_PUBKEY") | Some("ERRSIG") => missing_key = true,
+            _ => {}
+        }
+    }
+    if missing_key {
+        Verification::MissingKey
+    } else {
+        Verification::Bad
+    }
+}
+
+fn find_key_files(dir: &Path) -> Vec<PathBuf> {
+    let mut keys: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.is_file()
+                    && path
+                        .extension()
+                        .and_then(|ext| ext.to_str())
+                        .is_some_and(|ext| KEY_FILE_EXTENSIONS.contains(&ext))
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    keys.sort();
+    keys
+}
+
+/// Sections are allocated with a fixed size and padded with NUL bytes
+fn trim_section(mut content: Vec<u8>) -> Vec<u8> {
+    let end = content
+        .iter()
+        .rposition(|&b| b != 0 && !b.is_ascii_whitespace())
+        .map(|position| position + 1)
+        .unwrap_or(0);
+    content.truncate(end);
+    content
+}
+
+fn set_private_permissions(path: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+    if let Err(e) = fs::set_permissions(path, fs::Permissions::from_mode(0o700)) {
+        debug!("Failed to restrict permissions of {:?}: {}", path, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{build_app_image, TestNode};
+
+    #[test]
+    fn test_digest_zeroes_signature_sections() {
+        let dir = tempfile::tempdir().unwrap();
+        let signed_path = dir.path().join("Signed.AppImage");
+        let zeroed_path = dir.path().join("Zeroed.AppImage");
+        let nodes = [("test.desktop", TestNode::File(b"[Desktop Entry]\n"))];
+        fs::write(
+            &signed_path,
+            build_app_image(
+                &[(SIGNATURE_SECTION, b"signature"), (KEY_SECTION, b"key")],
+                &nodes,
+            ),
+        )
+        .unwrap();
+        fs::write(
+            &zeroed_path,
+            build_app_image(
+                &[(SIGNATURE_SECTION, &[0; 9]), (KEY_SECTION, &[0; 3])],
+                &nodes,
+            ),
+        )
+        .unwrap();
+
+        let reader = AppImageReader::open(&signed_path).unwrap();
+        let sections: Vec<&ElfSection> = [SIGNATURE_SECTION, KEY_SECTION]
+            .iter()
+            .filter_map(|name| reader.elf().section(name))
+            .collect();
+        let expected = hex::encode(Sha256::digest(fs::read(&zeroed_path).unwrap()));
+        assert_eq!(app_image_digest(&signed_path, &sections).unwrap(), expected);
+        assert_ne!(app_image_digest(&signed_path, &[]).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_unsigned_app_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Test.AppImage");
+        fs::write(
+            &path,
+            build_app_image(
+                &[(SIGNATURE_SECTION, &[0; 64])],
+                &[("test.desktop", TestNode::File(b""))],
+            ),
+        )
+        .unwrap();
+
+        let reader = AppImageReader::open(&path).unwrap();
+        assert_eq!(
+            verify_app_image_signature(&reader, &[]).unwrap(),
+            SignatureStatus::Unsigned
+        );
+    }
+
+    fn gpg(home: &Path, args: &[&str]) -> Vec<u8> {
+        let output = Command::new("gpg")
+            .arg("--homedir")
+            .arg(home)
+            .args(["--batch", "--yes", "--passphrase", ""])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        output.stdout
+    }
+
+    #[test]
+    fn test_verify_signed_app_image() {
+        if Command::new("gpg").arg("--version").output().is_err() {
+            eprintln!("gpg not available, skipping");
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let signer_home = dir.path().join("signer");
+        fs::create_dir(&signer_home).unwrap();
+        set_private_permissions(&signer_home);
+        gpg(
+            &signer_home,
+            &[
+                "--quick-gen-key",
+                "AppHub Test <test@example.com>",
+                "ed25519",
+                "sign",
+                "never",
+            ],
+        );
+        let key = gpg(&signer_home, &["--armor", "--export"]);
+
+        // Sign the AppImage the way appimagetool does: reserve the sections,
+        // sign the digest and write the signature and the key in place
+        let path = dir.path().join("Test.AppImage");
+        fs::write(
+            &path,
+            build_app_image(
+                &[(SIGNATURE_SECTION, &[0; 1024]), (KEY_SECTION, &[0; 4096])],
+                &[("test.desktop", TestNode::File(b"[Desktop Entry]\n"))],
+            ),
+        )
+        .unwrap();
+        let reader = AppImageReader::open(&path).unwrap();
+        let signature_section = reader.elf().section(SIGNATURE_SECTION).unwrap().clone();
+        let key_section = reader.elf().section(KEY_SECTION).unwrap().clone();
+        let digest_path = dir.path().join("digest");
+        fs::write(
+            &digest_path,
+            app_image_digest(&path, &[&signature_section, &key_section]).unwrap(),
+        )
+        .unwrap();
+        let signature = gpg(
+            &signer_home,
+            &[
+                "--armor",
+                "--detach-sign",
+                "--output",
+                "-",
+                digest_path.to_str().unwrap(),
+            ],
+        );
+        let mut content = fs::read(&path).unwrap();
+        let offset = signature_section.offset as usize;
+        content[offset..offset + signature.len()].copy_from_slice(&signature);
+        let offset = key_section.offset as usize;
+        content[offset..offset + key.len()].copy_from_slice(&key);
+        fs::write(&path, &content).unwrap();
+
+        let reader = AppImageReader::open(&path).unwrap();
+        let status = verify_app_image_signature(&reader, &[]).unwrap();
+        assert!(matches!(status, SignatureStatus::UnknownKey { .. }));
+
+        let keyring = dir.path().join("keyring");
+        fs::create_dir(&keyring).unwrap();
+        fs::write(keyring.join("test.asc"), &key).unwrap();
+        let status = verify_app_image_signature(&reader, std::slice::from_ref(&keyring)).unwrap();
+        assert!(matches!(status, SignatureStatus::Trusted { .. }));
+
+        // Any change outside of the signature sections invalidates the signature
+        let last = content.len() - 1;
+        content[last] ^= 0xff;
+        fs::write(&path, &content).unwrap();
+        let status = verify_app_image_signature(&reader, &[keyring]).unwrap();
+        assert_eq!(status, SignatureStatus::Invalid);
+    }
+
+    #[test]
+    fn test_parse_gpg_status() {
+        let valid = "[GNUPG:] NEWSIG\n[GNUPG:] GOODSIG 0123 Test\n\
+                     [GNUPG:] VALIDSIG ABCDEF0123 2024-01-01 1704067200 0 4 0 22 8 00 ABCDEF0123\n";
+        assert!(matches!(parse_gpg_status(valid), Verification::Valid(fpr) if fpr == "ABCDEF0123"));
+        let missing = "[GNUPG:] ERRSIG 0123 22 8 00 1704067200 9 -\n[GNUPG:] NO_PUBKEY 0123\n";
+        assert!(matches!(
+            parse_gpg_status(missing),
+            Verification::MissingKey
+        ));
+        assert!(matches!(
+            parse_gpg_status("[GNUPG:] BADSIG 0123 Test\n"),
+            Verification::Bad
+        ));
+    }
+}
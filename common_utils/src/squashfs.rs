@@ -0,0 +1,796 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use log::debug;
+
+use crate::image_fs::{is_valid_name, normalize, EntryKind, ImageEntry, ImageFs, MAX_FILE_SIZE};
+
+const SQUASHFS_MAGIC: u32 = 0x7371_7368;
+const SUPERBLOCK_SIZE: usize = 96;
+const METADATA_BLOCK_SIZE: usize = 8192;
+const METADATA_UNCOMPRESSED: u16 = 0x8000;
+const DATA_UNCOMPRESSED: u32 = 1 << 24;
+const NO_FRAGMENT: u32 = 0xFFFF_FFFF;
+const FRAGMENT_ENTRIES_PER_BLOCK: u64 = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compression {
+    Gzip,
+    Lzma,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    fn from_id(id: u16) -> Result<Self, String> {
+        match id {
+            1 => Ok(Self::Gzip),
+            2 => Ok(Self::Lzma),
+            4 => Ok(Self::Xz),
+            6 => Ok(Self::Zstd),
+            3 => Err("Unsupported squashfs compression: lzo".to_string()),
+            5 => Err("Unsupported squashfs compression: lz4".to_string()),
+            other => Err(format!("Unknown squashfs compression id: {}", other)),
+        }
+    }
+
+    fn decompress(&self, data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+        let mut out = Vec::with_capacity(limit);
+        let result = match self {
+            Self::Gzip => flate2::read::ZlibDecoder::new(data)
+                .take(limit as u64 + 1)
+                .read_to_end(&mut out)
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+            Self::Lzma => {
+                let mut writer = LimitedWriter::new(&mut out, limit);
+                lzma_rs::lzma_decompress(&mut &data[..], &mut writer).map_err(|e| e.to_string())
+            }
+            Self::Xz => {
+                let mut writer = LimitedWriter::new(&mut out, limit);
+                lzma_rs::xz_decompress(&mut &data[..], &mut writer).map_err(|e| e.to_string())
+            }
+            Self::Zstd => match ruzstd::StreamingDecoder::new(&mut &data[..]) {
+                Ok(decoder) => decoder
+                    .take(limit as u64 + 1)
+                    .read_to_end(&mut out)
+                    .map(|_| ())
+                    .map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            },
+        };
+        result.map_err(|e| format!("Failed to decompress squashfs block: {}", e))?;
+        if out.len() > limit {
+            return Err("Decompressed squashfs block is larger than expected".to_string());
+        }
+        Ok(out)
+    }
+}
+
+/// Writer refusing more than `limit + 1` bytes, so a decompression bomb stops early
+/// and the extra byte tells the block is larger than expected
+struct LimitedWriter<'a> {
+    out: &'a mut Vec<u8>,
+    limit: usize,
+}
+
+impl<'a> LimitedWriter<'a> {
+    fn new(out: &'a mut Vec<u8>, limit: usize) -> Self {
+        Self { out, limit }
+    }
+}
+
+impl Write for LimitedWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let available = (self.limit + 1).saturating_sub(self.out.len());
+        if available == 0 {
+            return Err(io::Error::other(
+                "Decompressed squashfs block is larger than expected",
+            ));
+        }
+        let len = buf.len().min(available);
+        self.out.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SuperBlock {
+    block_size: u32,
+    fragment_entry_count: u32,
+    compression: Compression,
+    root_inode: u64,
+    bytes_used: u64,
+    id_table_start: u64,
+    xattr_id_table_start: u64,
+    inode_table_start: u64,
+    directory_table_start: u64,
+    fragment_table_start: u64,
+    export_table_start: u64,
+}
+
+#[derive(Debug, Clone)]
+enum Inode {
+    Directory {
+        block_index: u32,
+        block_offset: u16,
+        file_size: u32,
+    },
+    File {
+        blocks_start: u64,
+        file_size: u64,
+        fragment_index: u32,
+        fragment_offset: u32,
+        block_sizes: Vec<u32>,
+    },
+    Symlink {
+        target: String,
+    },
+    Other,
+}
+
+/// A decompressed metadata table (inodes or directories).
+/// Metadata references address blocks by their compressed offset from the table start,
+/// so every block start is mapped to its position in the decompressed buffer.
+struct MetadataTable {
+    data: Vec<u8>,
+    block_positions: HashMap<u64, usize>,
+}
+
+impl MetadataTable {
+    fn position(&self, block: u64, offset: u16) -> Result<usize, String> {
+        self.block_positions
+            .get(&block)
+            .map(|position| position + offset as usize)
+            .filter(|position| *position <= self.data.len())
+            .ok_or_else(|| format!("Invalid squashfs metadata reference: {}:{}", block, offset))
+    }
+}
+
+/// Read-only squashfs 4.0 image reader.
+/// The image can start at any offset of the underlying reader, like the payload of an AppImage.
+pub struct SquashFs<R: Read + Seek> {
+    reader: R,
+    base_offset: u64,
+    super_block: SuperBlock,
+    inodes: MetadataTable,
+    fragments: Vec<(u64, u32)>,
+    entries: Vec<ImageEntry>,
+    index: HashMap<String, Inode>,
+}
+
+impl<R: Read + Seek> SquashFs<R> {
+    /// Open the squashfs image found at `base_offset` of the reader and index all its entries
+    pub fn open(mut reader: R, base_offset: u64) -> Result<Self, String> {
+        let super_block = read_super_block(&mut reader, base_offset)?;
+        debug!("Squashfs superblock: {:?}", super_block);
+
+        let inodes = read_metadata_table(
+            &mut reader,
+            base_offset,
+            super_block.inode_table_start,
+            super_block.directory_table_start,
+            super_block.compression,
+        )?;
+
+        // The directory table ends where the first of the following tables starts
+        let directory_table_end = [
+            super_block.fragment_table_start,
+            super_block.export_table_start,
+            super_block.id_table_start,
+            super_block.xattr_id_table_start,
+            super_block.bytes_used,
+        ]
+        .into_iter()
+        .filter(|start| *start > super_block.directory_table_start)
+        .min()
+        .unwrap_or(super_block.bytes_used);
+        let directories = read_metadata_table(
+            &mut reader,
+            base_offset,
+            super_block.directory_table_start,
+            directory_table_end,
+            super_block.compression,
+        )?;
+
+        let fragments = read_fragment_table(&mut reader, base_offset, &super_block)?;
+
+        let mut squashfs = Self {
+            reader,
+            base_offset,
+            super_block,
+            inodes,
+            fragments,
+            entries: Vec::new(),
+            index: HashMap::new(),
+        };
+        squashfs.build_index(&directories)?;
+
+        Ok(squashfs)
+    }
+
+    fn read_data_block(&mut self, start: u64, raw_size: u32) -> Result<Vec<u8>, String> {
+        let size = (raw_size & !DATA_UNCOMPRESSED) as usize;
+        let mut block = vec![0u8; size];
+        self.reader
+            .seek(SeekFrom::Start(self.base_offset + start))
+            .and_then(|_| self.reader.read_exact(&mut block))
+            .map_err(|e| format!("Failed to read squashfs data block: {}", e))?;
+        if raw_size & DATA_UNCOMPRESSED != 0 {
+            Ok(block)
+        } else {
+            self.super_block
+                .compression
+                .decompress(&block, self.super_block.block_size as usize)
+        }
+    }
+
+    /// Walk the whole directory tree and index every entry by path
+    fn build_index(&mut self, directories: &MetadataTable) -> Result<(), String> {
+        let root = self.read_inode(self.super_block.root_inode)?;
+        let mut pending = vec![(String::new(), root)];
+        // a directory entry pointing back at an ancestor would loop forever
+        let mut visited = HashSet::from([self.super_block.root_inode]);
+
+        while let Some((dir_path, dir_inode)) = pending.pop() {
+            let Inode::Directory {
+                block_index,
+                block_offset,
+                file_size,
+            } = dir_inode
+            else {
+                continue;
+            };
+
+            for (name, inode_ref) in
+                read_directory(directories, block_index, block_offset, file_size)?
+            {
+                if !is_valid_name(&name) {
+                    return Err(format!("Invalid file name in squashfs: {:?}", name));
+                }
+                let path = if dir_path.is_empty() {
+                    name
+                } else {
+                    format!("{}/{}", dir_path, name)
+                };
+                let inode = self.read_inode(inode_ref)?;
+                let size = match &inode {
+                    Inode::File { file_size, .. } => *file_size,
+                    Inode::Symlink { target } => target.len() as u64,
+                    _ => 0,
+                };
+                self.entries.push(ImageEntry {
+                    path: path.clone(),
+                    kind: inode_kind(&inode),
+                    size,
+                });
+                if let Inode::Directory { .. } = inode {
+                    if !visited.insert(inode_ref) {
+                        return Err(format!("Directory loop in squashfs: {}", path));
+                    }
+                    pending.push((path.clone(), inode.clone()));
+                }
+                self.index.insert(path, inode);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_inode(&self, inode_ref: u64) -> Result<Inode, String> {
+        let position = self
+            .inodes
+            .position(inode_ref >> 16, (inode_ref & 0xFFFF) as u16)?;
+        let mut cursor = ByteCursor::new(&self.inodes.data, position);
+        let inode_type = cursor.u16()?;
+        // Skip permissions, uid, gid, mtime and inode number
+        cursor.skip(14)?;
+
+        let block_size = self.super_block.block_size as u64;
+        let block_count = |file_size: u64, fragment_index: u32| {
+            if fragment_index == NO_FRAGMENT {
+                file_size.div_ceil(block_size)
+            } else {
+                file_size / block_size
+            }
+        };
+
+        let inode = match inode_type {
+            // Basic directory
+            1 => {
+                let block_index = cursor.u32()?;
+                cursor.skip(4)?;
+                let file_size = cursor.u16()? as u32;
+                let block_offset = cursor.u16()?;
+                Inode::Directory {
+                    block_index,
+                    block_offset,
+                    file_size,
+                }
+            }
+            // Extended directory
+            8 => {
+                cursor.skip(4)?;
+                let file_size = cursor.u32()?;
+                let block_index = cursor.u32()?;
+                cursor.skip(6)?;
+                let block_offset = cursor.u16()?;
+                Inode::Directory {
+                    block_index,
+                    block_offset,
+                    file_size,
+                }
+            }
+            // Basic file
+            2 => {
+                let blocks_start = cursor.u32()? as u64;
+                let fragment_index = cursor.u32()?;
+                let fragment_offset = cursor.u32()?;
+                let file_size = cursor.u32()? as u64;
+                let block_sizes = cursor.u32_list(block_count(file_size, fragment_index))?;
+                Inode::File {
+                    blocks_start,
+                    file_size,
+                    fragment_index,
+                    fragment_offset,
+                    block_sizes,
+                }
+            }
+            // Extended file
+            9 => {
+                let blocks_start = cursor.u64()?;
+                let file_size = cursor.u64()?;
+                cursor.skip(12)?;
+                let fragment_index = cursor.u32()?;
+                let fragment_offset = cursor.u32()?;
+                cursor.skip(4)?;
+                let block_sizes = cursor.u32_list(block_count(file_size, fragment_index))?;
+                Inode::File {
+                    blocks_start,
+                    file_size,
+                    fragment_index,
+                    fragment_offset,
+                    block_sizes,
+                }
+            }
+            // Basic and extended symlink
+            3 | 10 => {
+                cursor.skip(4)?;
+                let target_size = cursor.u32()? as usize;
+                let target = String::from_utf8_lossy(cursor.bytes(target_size)?).to_string();
+                Inode::Symlink { target }
+            }
+            4..=7 | 11..=14 => Inode::Other,
+            other => return Err(format!("Unknown squashfs inode type: {}", other)),
+        };
+        Ok(inode)
+    }
+}
+
+impl<R: Read + Seek> ImageFs for SquashFs<R> {
+    fn entries(&self) -> &[ImageEntry] {
+        &self.entries
+    }
+
+    fn kind(&self, path: &str) -> Option<EntryKind> {
+        self.index.get(normalize(path).as_str()).map(inode_kind)
+    }
+
+    fn read_link(&self, path: &str) -> Result<String, String> {
+        match self.index.get(normalize(path).as_str()) {
+            Some(Inode::Symlink { target }) => Ok(target.clone()),
+            Some(_) => Err(format!("Not a symlink: {}", path)),
+            None => Err(format!("File not found in squashfs: {}", path)),
+        }
+    }
+
+    fn read_regular_file(&mut self, path: &str) -> Result<Vec<u8>, String> {
+        let Some(Inode::File {
+            blocks_start,
+            file_size,
+            fragment_index,
+            fragment_offset,
+            block_sizes,
+        }) = self.index.get(normalize(path).as_str()).cloned()
+        else {
+            return Err(format!("Not a regular file: {}", path));
+        };
+        // sparse blocks cost nothing in the image, only the size bounds them
+        if file_size > MAX_FILE_SIZE {
+            return Err(format!(
+                "Squashfs file too large: {} ({} bytes)",
+                path, file_size
+            ));
+        }
+
+        let block_size = self.super_block.block_size as u64;
+        // the size in the inode is not trusted, the blocks and the image bound the content
+        let capacity = file_size
+            .min((block_sizes.len() as u64 + 1) * block_size)
+            .min(self.super_block.bytes_used);
+        let mut content = Vec::with_capacity(capacity as usize);
+        let mut position = blocks_start;
+        for raw_size in block_sizes {
+            let expected = block_size.min(file_size - content.len() as u64) as usize;
+            let size = raw_size & !DATA_UNCOMPRESSED;
+            if size == 0 {
+                // Sparse block
+                content.resize(content.len() + expected, 0);
+                continue;
+            }
+            let block = self.read_data_block(position, raw_size)?;
+            position += size as u64;
+            content.extend_from_slice(&block[..expected.min(block.len())]);
+        }
+
+        if fragment_index != NO_FRAGMENT {
+            let &(start, raw_size) = self
+                .fragments
+                .get(fragment_index as usize)
+                .ok_or_else(|| format!("Invalid squashfs fragment index: {}", fragment_index))?;
+            let fragment = self.read_data_block(start, raw_size)?;
+            let begin = fragment_offset as usize;
+            let end = (file_size - content.len() as u64)
+                .checked_add(begin as u64)
+                .filter(|end| *end <= fragment.len() as u64)
+                .map(|end| end as usize);
+            let Some(end) = end else {
+                return Err(format!("Invalid squashfs fragment for file: {}", path));
+            };
+            content.extend_from_slice(&fragment[begin..end]);
+        }
+
+        if content.len() as u64 != file_size {
+            return Err(format!("Truncated squashfs file: {}", path));
+        }
+        Ok(content)
+    }
+}
+
+fn inode_kind(inode: &Inode) -> EntryKind {
+    match inode {
+        Inode::Directory { .. } => EntryKind::Directory,
+        Inode::File { .. } => EntryKind::File,
+        Inode::Symlink { .. } => EntryKind::Symlink,
+        Inode::Other => EntryKind::Other,
+    }
+}
+
+fn read_super_block<R: Read + Seek>(
+    reader: &mut R,
+    base_offset: u64,
+) -> Result<SuperBlock, String> {
+    let mut raw = [0u8; SUPERBLOCK_SIZE];
+    reader
+        .seek(SeekFrom::Start(base_offset))
+        .and_then(|_| reader.read_exact(&mut raw))
+        .map_err(|e| format!("Failed to read squashfs superblock: {}", e))?;
+    let mut cursor = ByteCursor::new(&raw, 0);
+
+    if cursor.u32()? != SQUASHFS_MAGIC {
+        return Err("Squashfs magic not found".to_string());
+    }
+    let _inode_count = cursor.u32()?;
+    let _modification_time = cursor.u32()?;
+    let block_size = cursor.u32()?;
+    let fragment_entry_count = cursor.u32()?;
+    let compression = Compression::from_id(cursor.u16()?)?;
+    let _block_log = cursor.u16()?;
+    let _flags = cursor.u16()?;
+    let _id_count = cursor.u16()?;
+    let version_major = cursor.u16()?;
+    let version_minor = cursor.u16()?;
+    if (version_major, version_minor) != (4, 0) {
+        return Err(format!(
+            "Unsupported squashfs version: {}.{}",
+            version_major, version_minor
+        ));
+    }
+    if block_size == 0 || block_size > 1024 * 1024 {
+        return Err(format!("Invalid squashfs block size: {}", block_size));
+    }
+
+    Ok(SuperBlock {
+        block_size,
+        fragment_entry_count,
+        compression,
+        root_inode: cursor.u64()?,
+        bytes_used: cursor.u64()?,
+        id_table_start: cursor.u64()?,
+        xattr_id_table_start: cursor.u64()?,
+        inode_table_start: cursor.u64()?,
+        directory_table_start: cursor.u64()?,
+        fragment_table_start: cursor.u64()?,
+        export_table_start: cursor.u64()?,
+    })
+}
+
+/// Read a single metadata block, returning its decompressed content and its size on disk
+fn read_metadata_block<R: Read + Seek>(
+    reader: &mut R,
+    position: u64,
+    compression: Compression,
+) -> Result<(Vec<u8>, u64), String> {
+    let mut header = [0u8; 2];
+    reader
+        .seek(SeekFrom::Start(position))
+        .and_then(|_| reader.read_exact(&mut header))
+        .map_err(|e| format!("Failed to read squashfs metadata header: {}", e))?;
+    let header = u16::from_le_bytes(header);
+    let size = (header & !METADATA_UNCOMPRESSED) as usize;
+    if size == 0 || size > METADATA_BLOCK_SIZE {
+        return Err(format!("Invalid squashfs metadata block size: {}", size));
+    }
+
+    let mut block = vec![0u8; size];
+    reader
+        .read_exact(&mut block)
+        .map_err(|e| format!("Failed to read squashfs metadata block: {}", e))?;
+    let data = if header & METADATA_UNCOMPRESSED != 0 {
+        block
+    } else {
+        compression.decompress(&block, METADATA_BLOCK_SIZE)?
+    };
+    Ok((data, 2 + size as u64))
+}
+
+fn read_metadata_table<R: Read + Seek>(
+    reader: &mut R,
+    base_offset: u64,
+    start: u64,
+    end: u64,
+    compression: Compression,
+) -> Result<MetadataTable, String> {
+    let mut table = MetadataTable {
+        data: Vec::new(),
+        block_positions: HashMap::new(),
+    };
+    let mut position = start;
+    while position < end {
+        let (data, disk_size) = read_metadata_block(reader, base_offset + position, compression)?;
+        table
+            .block_positions
+            .insert(position - start, table.data.len());
+        table.data.extend_from_slice(&data);
+        position += disk_size;
+    }
+    Ok(table)
+}
+
+fn read_fragment_table<R: Read + Seek>(
+    reader: &mut R,
+    base_offset: u64,
+    super_block: &SuperBlock,
+) -> Result<Vec<(u64, u32)>, String> {
+    let count = super_block.fragment_entry_count as u64;
+    if count == 0 {
+        return Ok(Vec::new());
+    }
+
+    let block_count = count.div_ceil(FRAGMENT_ENTRIES_PER_BLOCK);
+    let mut lookup = vec![0u8; block_count as usize * 8];
+    reader
+        .seek(SeekFrom::Start(
+            base_offset + super_block.fragment_table_start,
+        ))
+        .and_then(|_| reader.read_exact(&mut lookup))
+        .map_err(|e| format!("Failed to read squashfs fragment table: {}", e))?;
+
+    let mut fragments = Vec::with_capacity(count as usize);
+    for pointer in lookup.chunks_exact(8) {
+        let mut raw = [0u8; 8];
+        raw.copy_from_slice(pointer);
+        let (data, _) = read_metadata_block(
+            reader,
+            base_offset + u64::from_le_bytes(raw),
+            super_block.compression,
+        )?;
+        let mut cursor = ByteCursor::new(&data, 0);
+        while fragments.len() < count as usize && cursor.remaining() >= 16 {
+            let start = cursor.u64()?;
+            let size = cursor.u32()?;
+            cursor.skip(4)?;
+            fragments.push((start, size));
+        }
+    }
+    Ok(fragments)
+}
+
+/// Read the entries of a directory as (name, inode reference) pairs
+fn read_directory(
+    directories: &MetadataTable,
+    block_index: u32,
+    block_offset: u16,
+    file_size: u32,
+) -> Result<Vec<(String, u64)>, String> {
+    let start = directories.position(block_index as u64, block_offset)?;
+    // The stored size accounts for the implicit "." and ".." entries
+    let end = start + (file_size as usize).saturating_sub(3);
+    if end > directories.data.len() {
+        return Err("Invalid squashfs directory size".to_string());
+    }
+
+    let mut cursor = ByteCursor::new(&directories.data[..end], start);
+    let mut entries = Vec::new();
+    while cursor.remaining() > 0 {
+        let count = cursor.u32()? as usize + 1;
+        let inode_block = cursor.u32()? as u64;
+        let _inode_number = cursor.u32()?;
+        for _ in 0..count {
+            let offset = cursor.u16()? as u64;
+            let _inode_offset = cursor.u16()?;
+            let _entry_type = cursor.u16()?;
+            let name_size = cursor.u16()? as usize + 1;
+            let name = String::from_utf8_lossy(cursor.bytes(name_size)?).to_string();
+            entries.push((name, (inode_block << 16) | offset));
+        }
+    }
+    Ok(entries)
+}
+
+/// Little endian cursor over a byte slice
+struct ByteCursor<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteCursor<'a> {
+    fn new(data: &'a [u8], position: usize) -> Self {
+        Self { data, position }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.position)
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.remaining() < len {
+            return Err("Unexpected end of squashfs metadata".to_string());
+        }
+        let bytes = &self.data[self.position..self.position + len];
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), String> {
+        self.bytes(len).map(|_| ())
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        let mut raw = [0u8; 8];
+        raw.copy_from_slice(self.bytes(8)?);
+        Ok(u64::from_le_bytes(raw))
+    }
+
+    fn u32_list(&mut self, count: u64) -> Result<Vec<u32>, String> {
+        (0..count).map(|_| self.u32()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_fs::ImageFs;
+    use crate::test_support::{build_squashfs, TestNode};
+    use std::io::Cursor;
+
+    fn sample_image() -> Vec<u8> {
+        build_squashfs(&[
+            (
+                "app.desktop",
+                TestNode::File(b"[Desktop Entry]\nName=App\n"),
+            ),
+            (".DirIcon", TestNode::Symlink("usr/share/icons/app.png")),
+            ("usr/share/icons/app.png", TestNode::File(b"\x89PNG fake")),
+            ("usr/share/empty", TestNode::File(b"")),
+            ("usr/bin/link", TestNode::Symlink("../share/icons/app.png")),
+        ])
+    }
+
+    #[test]
+    fn test_lists_all_entries() {
+        let squashfs = SquashFs::open(Cursor::new(sample_image()), 0).unwrap();
+        let mut paths: Vec<&str> = squashfs.entries().iter().map(|e| e.path.as_str()).collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![
+                ".DirIcon",
+                "app.desktop",
+                "usr",
+                "usr/bin",
+                "usr/bin/link",
+                "usr/share",
+                "usr/share/empty",
+                "usr/share/icons",
+                "usr/share/icons/app.png"
+            ]
+        );
+        assert_eq!(squashfs.kind("usr/share"), Some(EntryKind::Directory));
+        assert_eq!(squashfs.kind(".DirIcon"), Some(EntryKind::Symlink));
+    }
+
+    #[test]
+    fn test_reads_files_and_follows_symlinks() {
+        let mut squashfs = SquashFs::open(Cursor::new(sample_image()), 0).unwrap();
+        assert_eq!(
+            squashfs.read_file("/app.desktop").unwrap(),
+            b"[Desktop Entry]\nName=App\n"
+        );
+        assert_eq!(squashfs.read_file(".DirIcon").unwrap(), b"\x89PNG fake");
+        assert_eq!(squashfs.read_file("usr/bin/link").unwrap(), b"\x89PNG fake");
+        assert_eq!(squashfs.read_file("usr/share/empty").unwrap(), b"");
+        assert!(squashfs.read_file("usr/share").is_err());
+        assert!(squashfs.read_file("missing").is_err());
+    }
+
+    #[test]
+    fn test_opens_image_at_offset() {
+        let mut data = vec![0u8; 1000];
+        data.extend(sample_image());
+        let mut squashfs = SquashFs::open(Cursor::new(data), 1000).unwrap();
+        assert_eq!(
+            squashfs.read_link(".DirIcon").unwrap(),
+            "usr/share/icons/app.png"
+        );
+        assert_eq!(squashfs.read_file(".DirIcon").unwrap(), b"\x89PNG fake");
+    }
+
+    #[test]
+    fn test_rejects_oversized_sparse_files() {
+        let mut squashfs = SquashFs::open(Cursor::new(sample_image()), 0).unwrap();
+        let block_count = (MAX_FILE_SIZE / squashfs.super_block.block_size as u64 + 1) as usize;
+        if let Some(Inode::File {
+            file_size,
+            block_sizes,
+            fragment_index,
+            ..
+        }) = squashfs.index.get_mut("usr/share/empty")
+        {
+            *file_size = MAX_FILE_SIZE + 1;
+            *block_sizes = vec![0; block_count];
+            *fragment_index = NO_FRAGMENT;
+        }
+        assert!(squashfs.read_file("usr/share/empty").is_err());
+    }
+
+    #[test]
+    fn test_gzip_decompression() {
+        use std::io::Write;
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(b"compressed metadata").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let data = Compression::Gzip.decompress(&compressed, 8192).unwrap();
+        assert_eq!(data, b"compressed metadata");
+        assert!(Compression::Gzip.decompress(&compressed, 4).is_err());
+    }
+
+    #[test]
+    fn test_xz_decompression_is_capped() {
+        let content = vec![0u8; 64 * 1024];
+        let mut compressed = Vec::new();
+        lzma_rs::xz_compress(&mut &content[..], &mut compressed).unwrap();
+
+        let data = Compression::Xz
+            .decompress(&compressed, content.len())
+            .unwrap();
+        assert_eq!(data, content);
+        assert!(Compression::Xz.decompress(&compressed, 1024).is_err());
+    }
+}
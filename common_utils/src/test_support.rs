@@ -0,0 +1,391 @@
+//! Builders for the small ELF and squashfs images used by the unit tests
+
+use std::collections::BTreeMap;
+
+pub enum TestNode {
+    File(&'static [u8]),
+    /// A file stored compressed when the image format supports it (zisofs)
+    Compressed(&'static [u8]),
+    Symlink(&'static str),
+}
+
+enum Kind<'a> {
+    Dir(BTreeMap<String, usize>),
+    File(&'a [u8], bool),
+    Symlink(&'a str),
+}
+
+/// Build the directory tree of the given nodes; the root directory is at index 0
+fn build_tree<'a>(nodes: &[(&str, TestNode)]) -> Vec<Kind<'a>> {
+    let mut tree: Vec<Kind> = vec![Kind::Dir(BTreeMap::new())];
+    for (path, node) in nodes {
+        let components: Vec<&str> = path.split('/').collect();
+        let mut current = 0;
+        for (depth, component) in components.iter().enumerate() {
+            let is_leaf = depth == components.len() - 1;
+            let existing = match &tree[current] {
+                Kind::Dir(children) => children.get(*component).copied(),
+                _ => panic!("parent is not a directory"),
+            };
+            current = match existing {
+                Some(index) => index,
+                None => {
+                    tree.push(match (is_leaf, node) {
+                        (false, _) => Kind::Dir(BTreeMap::new()),
+                        (true, TestNode::File(content)) => Kind::File(content, false),
+                        (true, TestNode::Compressed(content)) => Kind::File(content, true),
+                        (true, TestNode::Symlink(target)) => Kind::Symlink(target),
+                    });
+                    let index = tree.len() - 1;
+                    if let Kind::Dir(children) = &mut tree[current] {
+                        children.insert(component.to_string(), index);
+                    }
+                    index
+                }
+            };
+        }
+    }
+    tree
+}
+
+/// Build a minimal little endian ELF64 file with the given AppImage magic and named sections.
+/// The section header table is the last thing in the file, like in the AppImage runtime.
+pub fn build_elf(magic: [u8; 3], sections: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut data = vec![0u8; 64];
+
+    // Section contents
+    let mut headers = vec![(0u32, 0u64, 0u64)];
+    let mut names = vec![0u8];
+    for (name, content) in sections {
+        headers.push((names.len() as u32, data.len() as u64, content.len() as u64));
+        names.extend_from_slice(name.as_bytes());
+        names.push(0);
+        data.extend_from_slice(content);
+    }
+    let shstrtab_name = names.len() as u32;
+    names.extend_from_slice(b".shstrtab\0");
+    headers.push((shstrtab_name, data.len() as u64, names.len() as u64));
+    data.extend_from_slice(&names);
+    let last = headers.len() - 1;
+
+    let sh_offset = data.len() as u64;
+    for (name, offset, size) in &headers {
+        let mut header = [0u8; 64];
+        header[0x00..0x04].copy_from_slice(&name.to_le_bytes());
+        header[0x18..0x20].copy_from_slice(&offset.to_le_bytes());
+        header[0x20..0x28].copy_from_slice(&size.to_le_bytes());
+        data.extend_from_slice(&header);
+    }
+
+    data[0..4].copy_from_slice(b"\x7fELF");
+    data[4] = 2;
+    data[5] = 1;
+    data[6] = 1;
+    data[8..11].copy_from_slice(&magic);
+    data[0x10..0x12].copy_from_slice(&2u16.to_le_bytes());
+    data[0x12..0x14].copy_from_slice(&0x3eu16.to_le_bytes());
+    data[0x28..0x30].copy_from_slice(&sh_offset.to_le_bytes());
+    data[0x34..0x36].copy_from_slice(&64u16.to_le_bytes());
+    data[0x3a..0x3c].copy_from_slice(&64u16.to_le_bytes());
+    data[0x3c..0x3e].copy_from_slice(&(headers.len() as u16).to_le_bytes());
+    data[0x3e..0x40].copy_from_slice(&(last as u16).to_le_bytes());
+    data
+}
+
+/// Build an uncompressed squashfs 4.0 image holding the given files and symlinks.
+/// Parent directories are created implicitly.
+pub fn build_squashfs(nodes: &[(&str, TestNode)]) -> Vec<u8> {
+    const BLOCK_SIZE: u32 = 4096;
+
+    let tree = build_tree(nodes);
+
+    // File data follows the superblock, one uncompressed block per file
+    let mut image = vec![0u8; 96];
+    let mut data_positions = vec![0u64; tree.len()];
+    for (index, node) in tree.iter().enumerate() {
+        if let Kind::File(content, _) = node {
+            assert!(content.len() <= BLOCK_SIZE as usize);
+            data_positions[index] = image.len() as u64;
+            image.extend_from_slice(content);
+        }
+    }
+
+    // Inode offsets only depend on inode sizes
+    let inode_size = |node: &Kind| match node {
+        Kind::Dir(_) => 32,
+        Kind::File(content, _) => 32 + if content.is_empty() { 0 } else { 4 },
+        Kind::Symlink(target) => 24 + target.len(),
+    };
+    let mut inode_offsets = Vec::new();
+    let mut total = 0;
+    for node in &tree {
+        inode_offsets.push(total);
+        total += inode_size(node);
+    }
+
+    // Directory listings reference child inode offsets
+    let mut listings = vec![Vec::new(); tree.len()];
+    for (index, node) in tree.iter().enumerate() {
+        if let Kind::Dir(children) = node {
+            if children.is_empty() {
+                continue;
+            }
+            let listing = &mut listings[index];
+            listing.extend_from_slice(&(children.len() as u32 - 1).to_le_bytes());
+            listing.extend_from_slice(&0u32.to_le_bytes());
+            listing.extend_from_slice(&1u32.to_le_bytes());
+            for (name, child) in children {
+                let entry_type: u16 = match tree[*child] {
+                    Kind::Dir(_) => 1,
+                    Kind::File(..) => 2,
+                    Kind::Symlink(_) => 3,
+                };
+                listing.extend_from_slice(&(inode_offsets[*child] as u16).to_le_bytes());
+                listing.extend_from_slice(&(*child as i16).to_le_bytes());
+                listing.extend_from_slice(&entry_type.to_le_bytes());
+                listing.extend_from_slice(&(name.len() as u16 - 1).to_le_bytes());
+                listing.extend_from_slice(name.as_bytes());
+            }
+        }
+    }
+    let mut listing_offsets = Vec::new();
+    let mut directory_table = Vec::new();
+    for listing in &listings {
+        listing_offsets.push(directory_table.len());
+        directory_table.extend_from_slice(listing);
+    }
+
+    let mut inode_table = Vec::new();
+    for (index, node) in tree.iter().enumerate() {
+        let inode_type: u16 = match node {
+            Kind::Dir(_) => 1,
+            Kind::File(..) => 2,
+            Kind::Symlink(_) => 3,
+        };
+        inode_table.extend_from_slice(&inode_type.to_le_bytes());
+        inode_table.extend_from_slice(&0o755u16.to_le_bytes());
+        inode_table.extend_from_slice(&[0u8; 8]);
+        inode_table.extend_from_slice(&(index as u32 + 1).to_le_bytes());
+        match node {
+            Kind::Dir(_) => {
+                inode_table.extend_from_slice(&0u32.to_le_bytes());
+                inode_table.extend_from_slice(&2u32.to_le_bytes());
+                inode_table.extend_from_slice(&(listings[index].len() as u16 + 3).to_le_bytes());
+                inode_table.extend_from_slice(&(listing_offsets[index] as u16).to_le_bytes());
+                inode_table.extend_from_slice(&1u32.to_le_bytes());
+            }
+            Kind::File(content, _) => {
+                inode_table.extend_from_slice(&(data_positions[index] as u32).to_le_bytes());
+                inode_table.extend_from_slice(&0xFFFF_FFFFu32.to_le_bytes());
+                inode_table.extend_from_slice(&0u32.to_le_bytes());
+                inode_table.extend_from_slice(&(content.len() as u32).to_le_bytes());
+                if !content.is_empty() {
+                    inode_table.extend_from_slice(&(content.len() as u32 | 1 << 24).to_le_bytes());
+                }
+            }
+            Kind::Symlink(target) => {
+                inode_table.extend_from_slice(&1u32.to_le_bytes());
+                inode_table.extend_from_slice(&(target.len() as u32).to_le_bytes());
+                inode_table.extend_from_slice(target.as_bytes());
+            }
+        }
+    }
+    assert!(inode_table.len() < 8192 && directory_table.len() < 8192);
+
+    let push_metadata_block = |image: &mut Vec<u8>, block: &[u8]| {
+        image.extend_from_slice(&(block.len() as u16 | 0x8000).to_le_bytes());
+        image.extend_from_slice(block);
+    };
+    let inode_table_start = image.len() as u64;
+    push_metadata_block(&mut image, &inode_table);
+    let directory_table_start = image.len() as u64;
+    push_metadata_block(&mut image, &directory_table);
+    let id_block_start = image.len() as u64;
+    push_metadata_block(&mut image, &0u32.to_le_bytes());
+    let id_table_start = image.len() as u64;
+    image.extend_from_slice(&id_block_start.to_le_bytes());
+
+    let bytes_used = image.len() as u64;
+    let mut super_block = Vec::new();
+    super_block.extend_from_slice(&0x7371_7368u32.to_le_bytes());
+    super_block.extend_from_slice(&(tree.len() as u32).to_le_bytes());
+    super_block.extend_from_slice(&0u32.to_le_bytes());
+    super_block.extend_from_slice(&BLOCK_SIZE.to_le_bytes());
+    super_block.extend_from_slice(&0u32.to_le_bytes());
+    super_block.extend_from_slice(&1u16.to_le_bytes());
+    super_block.extend_from_slice(&12u16.to_le_bytes());
+    super_block.extend_from_slice(&0x0013u16.to_le_bytes());
+    super_block.extend_from_slice(&1u16.to_le_bytes());
+    super_block.extend_from_slice(&4u16.to_le_bytes());
+    super_block.extend_from_slice(&0u16.to_le_bytes());
+    super_block.extend_from_slice(&(inode_offsets[0] as u64).to_le_bytes());
+    super_block.extend_from_slice(&bytes_used.to_le_bytes());
+    super_block.extend_from_slice(&id_table_start.to_le_bytes());
+    super_block.extend_from_slice(&u64::MAX.to_le_bytes());
+    super_block.extend_from_slice(&inode_table_start.to_le_bytes());
+    super_block.extend_from_slice(&directory_table_start.to_le_bytes());
+    super_block.extend_from_slice(&u64::MAX.to_le_bytes());
+    super_block.extend_from_slice(&u64::MAX.to_le_bytes());
+    image[..96].copy_from_slice(&super_block);
+    image
+}
+
+/// Build a type-2 AppImage: an ELF runtime followed by a squashfs payload
+pub fn build_app_image(sections: &[(&str, &[u8])], nodes: &[(&str, TestNode)]) -> Vec<u8> {
+    let mut image = build_elf(*b"AI\x02", sections);
+    image.extend(build_squashfs(nodes));
+    image
+}
+
+/// Build a type-1 AppImage: an ISO 9660 image with Rock Ridge names and symlinks,
+/// with the ELF runtime in its system area
+pub fn build_iso9660(sections: &[(&str, &[u8])], nodes: &[(&str, TestNode)]) -> Vec<u8> {
+    const SECTOR: usize = 2048;
+    let tree = build_tree(nodes);
+
+    // Volume descriptors are at sectors 16 and 17, everything else follows
+    let mut next_sector = 18;
+    let mut extents = vec![(0usize, 0usize); tree.len()];
+    let mut contents: Vec<Vec<u8>> = vec![Vec::new(); tree.len()];
+    for (index, node) in tree.iter().enumerate() {
+        if let Kind::File(content, compressed) = node {
+            contents[index] = if *compressed {
+                zisofs_compress(content)
+            } else {
+                content.to_vec()
+            };
+        }
+    }
+    for (index, node) in tree.iter().enumerate() {
+        let size = match node {
+            Kind::Dir(_) => SECTOR,
+            Kind::File(..) => contents[index].len(),
+            Kind::Symlink(_) => 0,
+        };
+        extents[index] = (next_sector, size);
+        next_sector += size.div_ceil(SECTOR).max(1);
+    }
+
+    let mut image = vec![0u8; next_sector * SECTOR];
+    let elf = build_elf(*b"AI\x01", sections);
+    image[..elf.len()].copy_from_slice(&elf);
+
+    let record = |index: usize, iso_name: &[u8], name: Option<&str>| {
+        let (extent, size) = extents[index];
+        let mut system_use = Vec::new();
+        if let Some(name) = name {
+            system_use.extend_from_slice(&[b'N', b'M', 5 + name.len() as u8, 1, 0]);
+            system_use.extend_from_slice(name.as_bytes());
+            match &tree[index] {
+                Kind::Symlink(target) => {
+                    let mut px = vec![b'P', b'X', 44, 1];
+                    px.extend_from_slice(&0o120777u32.to_le_bytes());
+                    px.extend_from_slice(&0o120777u32.to_be_bytes());
+                    px.resize(44, 0);
+                    system_use.extend_from_slice(&px);
+
+                    let mut components = Vec::new();
+                    for component in target.split('/') {
+                        match component {
+                            ".." => components.extend_from_slice(&[0x04, 0]),
+                            component => {
+                                components.extend_from_slice(&[0, component.len() as u8]);
+                                components.extend_from_slice(component.as_bytes());
+                            }
+                        }
+                    }
+                    system_use.extend_from_slice(&[b'S', b'L', 5 + components.len() as u8, 1, 0]);
+                    system_use.extend_from_slice(&components);
+                }
+                Kind::File(content, true) => {
+                    system_use.extend_from_slice(&[b'Z', b'F', 16, 1, b'p', b'z', 4, 15]);
+                    system_use.extend_from_slice(&(content.len() as u32).to_le_bytes());
+                    system_use.extend_from_slice(&(content.len() as u32).to_be_bytes());
+                }
+                _ => {}
+            }
+        }
+        let padding = 1 - iso_name.len() % 2;
+        let mut record = vec![0u8; 33];
+        record[2..6].copy_from_slice(&(extent as u32).to_le_bytes());
+        record[10..14].copy_from_slice(&(size as u32).to_le_bytes());
+        record[25] = if matches!(tree[index], Kind::Dir(_)) {
+            2
+        } else {
+            0
+        };
+        record[32] = iso_name.len() as u8;
+        record.extend_from_slice(iso_name);
+        record.resize(record.len() + padding, 0);
+        record.extend_from_slice(&system_use);
+        if record.len() % 2 == 1 {
+            record.push(0);
+        }
+        record[0] = record.len() as u8;
+        record
+    };
+
+    let mut parents = vec![0usize; tree.len()];
+    for (index, node) in tree.iter().enumerate() {
+        if let Kind::Dir(children) = node {
+            for child in children.values() {
+                parents[*child] = index;
+            }
+        }
+    }
+
+    for (index, node) in tree.iter().enumerate() {
+        match node {
+            Kind::Dir(children) => {
+                let mut listing = record(index, &[0], None);
+                listing.extend(record(parents[index], &[1], None));
+                for (name, child) in children {
+                    listing.extend(record(
+                        *child,
+                        format!("F{};1", child).as_bytes(),
+                        Some(name),
+                    ));
+                }
+                assert!(listing.len() <= SECTOR);
+                let start = extents[index].0 * SECTOR;
+                image[start..start + listing.len()].copy_from_slice(&listing);
+            }
+            Kind::File(..) => {
+                let start = extents[index].0 * SECTOR;
+                image[start..start + contents[index].len()].copy_from_slice(&contents[index]);
+            }
+            Kind::Symlink(_) => {}
+        }
+    }
+
+    let descriptor = 16 * SECTOR;
+    image[descriptor] = 1;
+    image[descriptor + 1..descriptor + 6].copy_from_slice(b"CD001");
+    image[descriptor + 6] = 1;
+    image[descriptor + 128..descriptor + 130].copy_from_slice(&(SECTOR as u16).to_le_bytes());
+    image[descriptor + 130..descriptor + 132].copy_from_slice(&(SECTOR as u16).to_be_bytes());
+    let root = record(0, &[0], None);
+    image[descriptor + 156..descriptor + 156 + root.len()].copy_from_slice(&root);
+
+    let terminator = 17 * SECTOR;
+    image[terminator] = 255;
+    image[terminator + 1..terminator + 6].copy_from_slice(b"CD001");
+    image
+}
+
+/// Compress a file with zisofs: header, block pointers and a single zlib block
+fn zisofs_compress(content: &[u8]) -> Vec<u8> {
+    use std::io::Write;
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(content).unwrap();
+    let block = encoder.finish().unwrap();
+
+    let mut data = vec![0x37, 0xE4, 0x53, 0x96, 0xC9, 0xDB, 0xD6, 0x07];
+    data.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    data.extend_from_slice(&[4, 15, 0, 0]);
+    let blocks_start = data.len() as u32 + 8;
+    data.extend_from_slice(&blocks_start.to_le_bytes());
+    data.extend_from_slice(&(blocks_start + block.len() as u32).to_le_bytes());
+    data.extend_from_slice(&block);
+    data
+}
@@ -0,0 +1,144 @@
+use std::cmp::Ordering;
+
+use crate::desktop_file::{DesktopFile, DESKTOP_ENTRY_GROUP};
+use crate::models::app_stream_metadata::AppStreamMetadata;
+
+/// Key of the desktop entry holding the version of the AppImage, as set by appimagetool
+const APP_IMAGE_VERSION_KEY: &str = "X-AppImage-Version";
+
+/// A run of digits or of letters of a version string
+#[derive(Debug, PartialEq, Eq)]
+enum VersionSegment<'a> {
+    Number(&'a str),
+    Text(&'a str),
+}
+
+/// The version of the app packaged in an AppImage:
+/// the `X-AppImage-Version` of its desktop entry, or the latest release of its AppStream metadata
+pub fn app_version(
+    desktop_file: &DesktopFile,
+    metadata: Option<&AppStreamMetadata>,
+) -> Option<String> {
+    desktop_file
+        .get(DESKTOP_ENTRY_GROUP, APP_IMAGE_VERSION_KEY)
+        .filter(|version| !version.trim().is_empty())
+        .or_else(|| {
+            metadata?
+                .releases
+                .iter()
+                .map(|release| release.version.clone())
+                .max_by(|a, b| compare_versions(a, b))
+        })
+}
+
+/// Compare two version strings, such as `1.10.2` and `1.9`.
+/// Numbers are compared by value and a version followed by letters is a pre-release,
+/// so `1.0rc1` comes before `1.0` which comes before `1.0.1`
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let a = version_segments(a);
+    let b = version_segments(b);
+    for index in 0..a.len().max(b.len()) {
+        let ordering = match (a.get(index), b.get(index)) {
+            (Some(VersionSegment::Number(a)), Some(VersionSegment::Number(b))) => {
+                let a = a.trim_start_matches('0');
+                let b = b.trim_start_matches('0');
+                a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+            }
+            (Some(VersionSegment::Text(a)), Some(VersionSegment::Text(b))) => {
+                a.to_lowercase().cmp(&b.to_lowercase())
+            }
+            (Some(VersionSegment::Number(_)), Some(VersionSegment::Text(_))) => Ordering::Greater,
+            (Some(VersionSegment::Text(_)), Some(VersionSegment::Number(_))) => Ordering::Less,
+            (Some(VersionSegment::Number(_)), None) => Ordering::Greater,
+            (Some(VersionSegment::Text(_)), None) => Ordering::Less,
+            (None, Some(VersionSegment::Number(_))) => Ordering::Less,
+            (None, Some(VersionSegment::Text(_))) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+/// Split a version into its numbers and words, dropping the separators and a leading `v`
+fn version_segments(version: &str) -> Vec<VersionSegment<'_>> {
+    let version = version.trim();
+    let version = version
+        .strip_prefix(['v', 'V'])
+        .filter(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
+        .unwrap_or(version);
+
+    let mut segments = Vec::new();
+    let mut start = None;
+    for (index, c) in version.char_indices() {
+        if let Some(segment_start) = start {
+            let is_number = version[segment_start..].starts_with(|c: char| c.is_ascii_digit());
+            if c.is_ascii_alphanumeric() && c.is_ascii_digit() == is_number {
+                continue;
+            }
+            segments.push(version_segment(&version[segment_start..index]));
+            start = None;
+        }
+        if c.is_ascii_alphanumeric() {
+            start = Some(index);
+        }
+    }
+    if let Some(segment_start) = start {
+        segments.push(version_segment(&version[segment_start..]));
+    }
+    segments
+}
+
+fn version_segment(segment: &str) -> VersionSegment<'_> {
+    if segment.starts_with(|c: char| c.is_ascii_digit()) {
+        VersionSegment::Number(segment)
+    } else {
+        VersionSegment::Text(segment)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("1.10.2", "1.9"), Ordering::Greater);
+        assert_eq!(compare_versions("v2.0", "2.0"), Ordering::Equal);
+        assert_eq!(compare_versions("1.0", "1.00"), Ordering::Equal);
+        assert_eq!(compare_versions("1.0rc1", "1.0"), Ordering::Less);
+        assert_eq!(compare_versions("1.0-beta", "1.0-alpha"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0", "1.0.1"), Ordering::Less);
+        assert_eq!(compare_versions("2024.10", "2024.9.30"), Ordering::Greater);
+        assert_eq!(
+            compare_versions("99999999999999999999.1", "99999999999999999999.0"),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn test_app_version() {
+        let metadata = AppStreamMetadata::parse(
+            "<component><id>org.example.App</id><releases>\
+             <release version=\"1.2\"/><release version=\"1.10\"/>\
+             </releases></component>",
+        )
+        .unwrap();
+
+        let desktop_file = DesktopFile::parse("[Desktop Entry]\nName=App\n");
+        assert_eq!(
+            app_version(&desktop_file, Some(&metadata)),
+            Some("1.10".to_string())
+        );
+        assert_eq!(app_version(&desktop_file, None), None);
+
+        let desktop_file =
+            DesktopFile::parse("[Desktop Entry]\nName=App\nX-AppImage-Version=2.0.1\n");
+        assert_eq!(
+            app_version(&desktop_file, Some(&metadata)),
+            Some("2.0.1".to_string())
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use crate::models::install_scope::InstallScope;
+
+/// Data directories searched when `$XDG_DATA_DIRS` is unset or empty
+const DEFAULT_DATA_DIRS: [&str; 2] = ["/usr/local/share", "/usr/share"];
+
+/// The user data directory: `$XDG_DATA_HOME`, or `~/.local/share` when unset
+pub fn data_home() -> Option<PathBuf> {
+    resolve_home_dir(
+        env::var("XDG_DATA_HOME").ok().as_deref(),
+        dirs::home_dir(),
+        ".local/share",
+    )
+}
+
+/// The user state directory: `$XDG_STATE_HOME`, or `~/.local/state` when unset
+pub fn state_home() -> Option<PathBuf> {
+    resolve_home_dir(
+        env::var("XDG_STATE_HOME").ok().as_deref(),
+        dirs::home_dir(),
+        ".local/state",
+    )
+}
+
+/// The user cache directory: `$XDG_CACHE_HOME`, or `~/.cache` when unset
+pub fn cache_home() -> Option<PathBuf> {
+    resolve_home_dir(
+        env::var("XDG_CACHE_HOME").ok().as_deref(),
+        dirs::home_dir(),
+        ".cache",
+    )
+}
+
+/// The system data directories from `$XDG_DATA_DIRS`, in order of preference
+pub fn data_dirs() -> Vec<PathBuf> {
+    parse_data_dirs(env::var("XDG_DATA_DIRS").ok().as_deref())
+}
+
+/// Directories of the desktop entries of the scope, in order of preference.
+/// Entries in earlier directories shadow the ones with the same desktop file ID in later directories.
+pub fn applications_search_path(scope: InstallScope) -> Vec<PathBuf> {
+    let mut search_path: Vec<PathBuf> = Vec::new();
+    match scope {
+        InstallScope::User => search_path.extend(data_home().map(|dir| dir.join("applications"))),
+        InstallScope::System => {
+            if let Ok(path) = env::var("KDE_INSTALL_APPDIR") {
+                search_path.push(PathBuf::from(path));
+            }
+            search_path.extend(data_dirs().iter().map(|dir| dir.join("applications")));
+        }
+    }
+
+    let mut unique: Vec<PathBuf> = Vec::new();
+    for dir in search_path {
+        if !unique.contains(&dir) {
+            unique.push(dir);
+        }
+    }
+    unique
+}
+
+/// Resolve a user base directory from its variable, or its default relative to the home directory.
+/// Relative values are invalid and ignored as the specification requires
+fn resolve_home_dir(
+    value: Option<&str>,
+    home_dir: Option<PathBuf>,
+    default: &str,
+) -> Option<PathBuf> {
+    match value {
+        Some(value) if Path::new(value).is_absolute() => Some(PathBuf::from(value)),
+        _ => home_dir.map(|home_dir| home_dir.join(default)),
+    }
+}
+
+/// Split a `:` separated list of data directories, keeping the absolute ones
+fn parse_data_dirs(xdg_data_dirs: Option<&str>) -> Vec<PathBuf> {
+    let dirs: Vec<PathBuf> = xdg_data_dirs
+        .unwrap_or_default()
+        .split(':')
+        .filter(|dir| Path::new(dir).is_absolute())
+        .map(PathBuf::from)
+        .collect();
+
+    if dirs.is_empty() {
+        DEFAULT_DATA_DIRS.iter().map(PathBuf::from).collect()
+    } else {
+        dirs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_home_dir() {
+        let home_dir = Some(PathBuf::from("/home/user"));
+        assert_eq!(
+            resolve_home_dir(Some("/data"), home_dir.clone(), ".local/share"),
+            Some(PathBuf::from("/data"))
+        );
+        assert_eq!(
+            resolve_home_dir(Some("data"), home_dir.clone(), ".local/share"),
+            Some(PathBuf::from("/home/user/.local/share"))
+        );
+        assert_eq!(
+            resolve_home_dir(None, home_dir, ".local/state"),
+            Some(PathBuf::from("/home/user/.local/state"))
+        );
+        assert_eq!(resolve_home_dir(None, None, ".local/share"), None);
+    }
+
+    #[test]
+    fn test_parse_data_dirs() {
+        assert_eq!(
+            parse_data_dirs(Some("/opt/share::relative:/usr/share")),
+            vec![PathBuf::from("/opt/share"), PathBuf::from("/usr/share")]
+        );
+        assert_eq!(
+            parse_data_dirs(Some("")),
+            vec![
+                PathBuf::from("/usr/local/share"),
+                PathBuf::from("/usr/share")
+            ]
+        );
+        assert_eq!(parse_data_dirs(None), parse_data_dirs(Some("")));
+    }
+}
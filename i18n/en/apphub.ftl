@@ -6,12 +6,67 @@ page-id = Page { $num }
 
 install-from-file = Install from file
 choose-file = Choose file
+drop-files = or drop one or more AppImages here
 installing = Installing app...
 installation-completed = Installation completed!
+install = Install
+cancel = Cancel
+reading-app-image = Reading the AppImage...
+signature-trusted = Signed by a trusted key ({ $fingerprint })
+signature-unknown-key = Signed by a key which is not in the keyring ({ $fingerprint })
+signature-unsigned = This AppImage is not signed
+signature-invalid = The signature of this AppImage is not valid
+signature-error = Unable to verify the signature: { $error }
+desktop-file-warnings = The desktop entry has problems, they will be fixed during the installation
+desktop-file-invalid = The desktop entry is invalid, this AppImage cannot be installed
+desktop-file-error = Unable to read the desktop entry: { $error }
+install-for-all-users = Install for all users
+already-installed = { $name } { $installed } is installed, this AppImage is version { $version }
+unknown-version = unknown
+upgrade = Upgrade
+downgrade = Downgrade
+reinstall = Reinstall
+replace = Replace
+install-side-by-side = Install side by side
+portable-data = Keep the app data in portable directories next to the AppImage
+install-from-url = Or download it from a URL
+app-image-url = https://example.org/App.AppImage
+expected-sha256 = Expected SHA-256 checksum (optional)
+download = Download
+downloading = Downloading { $url }
+download-failed = Download failed: { $error }
+batch-selected = { $count } AppImages selected
+install-all = Install all
+install-all-downgrade = Install all, replacing newer versions
+install-all-side-by-side = Install all next to the installed versions
+batch-installed = { $name } installed
+batch-failed = { $name } was not installed: { $error }
+batch-not-run = the installation did not start
+batch-excluded = its desktop entry is invalid
 
 installed-list = Installed app list
 uninstall = Uninstall
 run-app = Run
+update-info = Updates from { $source }
+no-update-info = No update information
+details = Details
+app-versions = Versions installed side by side
+active-version = Launched version: { $version }
+make-default = Make default
+remove-version = Remove
+portable-data-prompt = This app keeps its data in portable directories. Delete them too?
+keep-portable-data = Keep data
+delete-portable-data = Delete data
+no-app-details = This app does not provide AppStream metadata
+app-license = License: { $license }
+app-homepage = Homepage: { $homepage }
+app-screenshots = { $count ->
+    [one] 1 screenshot
+   *[other] { $count } screenshots
+}
+app-releases = Releases
+app-actions = Actions
+installed-for-all-users = Installed for all users
 
 settings = Settings
 installation-dir = Installation dir
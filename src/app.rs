@@ -5,17 +5,23 @@ use crate::core::nav::NavPage;
 use crate::pages::install_from_file::InstallFromFileMessage;
 use crate::pages::installed_list::InstalledListMessage;
 use crate::{fl, pages};
+use common_utils::install_transaction::{recover_transactions, transactions_dir};
+use common_utils::models::install_scope::InstallScope;
 use cosmic::app::{Command, Core};
 use cosmic::cosmic_config::{self, CosmicConfigEntry};
 use cosmic::dialog::ashpd::url::Url;
 use cosmic::dialog::file_chooser;
 use cosmic::iced::{Alignment, Length, Subscription};
 use cosmic::widget::{self, menu, nav_bar};
-use cosmic::{cosmic_theme, theme, Application, ApplicationExt, Element};
+use cosmic::{cosmic_theme, dbus_activation, theme, Application, ApplicationExt, Element};
 use futures_util::SinkExt;
 use std::collections::HashMap;
+use std::path::Path;
 use std::sync::Arc;
 
+/// Action of the running instance opening the files given to a second launch
+const OPEN_ACTION: &str = "open";
+
 const REPOSITORY: &str = "https://github.com/cosmic-utils/app-hub";
 const APP_ICON: &[u8] =
     include_bytes!("../res/icons/hicolor/scalable/apps/com.francescogaglione.apphub.png");
@@ -48,7 +54,7 @@ pub enum Message {
     UpdateConfig(Config),
 
     InstallFromFile(pages::install_from_file::InstallFromFileMessage),
-    ChooseFile(Url),
+    ChooseFiles(Vec<Url>),
     InstallationSuccessful,
     Cancelled,
     OpenError(Arc<file_chooser::Error>),
@@ -67,7 +73,7 @@ impl Application for AppModel {
     type Executor = cosmic::executor::Default;
 
     /// Data that your application receives to its init method.
-    type Flags = ();
+    type Flags = Flags;
 
     /// Messages which the application and its widgets will emit.
     type Message = Message;
@@ -84,7 +90,16 @@ impl Application for AppModel {
     }
 
     /// Initializes the application with any given flags and startup commands.
-    fn init(core: Core, _flags: Self::Flags) -> (Self, Command<Self::Message>) {
+    fn init(core: Core, flags: Self::Flags) -> (Self, Command<Self::Message>) {
+        // Roll back the installations for the current user interrupted by a crash,
+        // the backend recovers the ones for all users
+        if let Ok(journal_dir) = transactions_dir(InstallScope::User) {
+            let recovered = recover_transactions(&journal_dir);
+            if recovered > 0 {
+                log::info!("rolled back {} interrupted installations", recovered);
+            }
+        }
+
         // Create a nav bar with three page items.
         let mut nav = nav_bar::Model::default();
 
@@ -125,12 +140,27 @@ impl Application for AppModel {
             settings: pages::settings::Settings::default(),
         };
 
-        // Create a startup command that sets the window title.
-        let command = app.update_title();
+        // Create a startup command that sets the window title, and preview the given files.
+        let command = if flags.open.is_empty() {
+            app.update_title()
+        } else {
+            app.open(flags.open)
+        };
 
         (app, command)
     }
 
+    /// Preview the files given to a second launch of AppHub.
+    fn dbus_activation(&mut self, msg: dbus_activation::Message) -> Command<Self::Message> {
+        match msg.msg {
+            dbus_activation::Details::Open { url } => self.open(url),
+            dbus_activation::Details::ActivateAction { action, args } if action == OPEN_ACTION => {
+                self.open(Flags::from_forwarded_args(args).open)
+            }
+            _ => Command::none(),
+        }
+    }
+
     /// Elements to pack at the start of the header bar.
     fn header_start(&self) -> Vec<Element<Self::Message>> {
         let menu_bar = menu::bar(vec![menu::Tree::with_children(
@@ -196,6 +226,10 @@ impl Application for AppModel {
                     futures_util::future::pending().await
                 },
             ),
+            // Download the AppImage requested in the install page, accept the dropped ones.
+            self.install_from_file
+                .subscription()
+                .map(Message::InstallFromFile),
             // Watch for application configuration changes.
             self.core()
                 .watch_config::<Config>(Self::APP_ID)
@@ -254,9 +288,9 @@ impl Application for AppModel {
             Message::Settings(message) => {
                 commands.push(self.settings.update(message).map(cosmic::app::Message::App))
             }
-            Message::ChooseFile(message) => commands.push(
+            Message::ChooseFiles(message) => commands.push(
                 self.install_from_file
-                    .update(InstallFromFileMessage::FileSelected(message))
+                    .update(InstallFromFileMessage::FilesSelected(message))
                     .map(cosmic::app::Message::App),
             ),
             Message::Cancelled => commands.push(
@@ -301,6 +335,25 @@ impl Application for AppModel {
 }
 
 impl AppModel {
+    /// Show the install page with a preview of the files, or the download of the URL.
+    pub fn open(&mut self, urls: Vec<Url>) -> Command<Message> {
+        log::info!("opening {:?}", urls);
+        let install_page = self
+            .nav
+            .iter()
+            .find(|id| self.nav.data::<NavPage>(*id) == Some(&NavPage::InstallFromFile));
+        if let Some(id) = install_page {
+            self.nav.activate(id);
+        }
+
+        Command::batch(vec![
+            self.update_title(),
+            self.install_from_file
+                .update(InstallFromFileMessage::Open(urls))
+                .map(cosmic::app::Message::App),
+        ])
+    }
+
     /// The about page for this app.
     pub fn about(&self) -> Element<Message> {
         let cosmic_theme::Spacing { space_xxs, .. } = theme::active().cosmic().spacing;
@@ -335,6 +388,68 @@ impl AppModel {
     }
 }
 
+/// The files and URLs AppHub is launched with, such as the AppImage opened from a file manager.
+#[derive(Clone, Debug, Default)]
+pub struct Flags {
+    pub open: Vec<Url>,
+    /// `OPEN_ACTION` when there are files, to hand them to the running instance
+    action: Option<String>,
+}
+
+impl Flags {
+    /// Read the paths and URIs among the command line arguments, ignoring the options.
+    /// Relative paths are resolved against the working directory of this process,
+    /// so only absolute URIs are handed to a running instance.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Self {
+        let working_dir = std::env::current_dir().ok();
+        Self::parse(args, working_dir.as_deref())
+    }
+
+    /// Read the URIs handed over by another launch of AppHub.
+    /// Relative paths are ignored, the working directory they belong to is unknown.
+    pub fn from_forwarded_args(args: impl IntoIterator<Item = String>) -> Self {
+        Self::parse(args, None)
+    }
+
+    fn parse(args: impl IntoIterator<Item = String>, working_dir: Option<&Path>) -> Self {
+        let open: Vec<Url> = args
+            .into_iter()
+            .filter(|arg| !arg.starts_with('-'))
+            .filter_map(|arg| match Url::parse(&arg) {
+                Ok(url) if ["file", "http", "https"].contains(&url.scheme()) => Some(url),
+                Ok(url) => {
+                    log::warn!("unsupported URI: {}", url);
+                    None
+                }
+                Err(_) if Path::new(&arg).is_absolute() => Url::from_file_path(&arg).ok(),
+                Err(_) => match working_dir {
+                    Some(working_dir) => Url::from_file_path(working_dir.join(&arg)).ok(),
+                    None => {
+                        log::warn!("ignoring relative path: {}", arg);
+                        None
+                    }
+                },
+            })
+            .collect();
+        let action = (!open.is_empty()).then(|| OPEN_ACTION.to_string());
+        Self { open, action }
+    }
+}
+
+impl cosmic::app::CosmicFlags for Flags {
+    type SubCommand = String;
+    type Args = Vec<String>;
+
+    fn action(&self) -> Option<&String> {
+        self.action.as_ref()
+    }
+
+    /// The absolute URIs of the files and URLs, forwarded with `OPEN_ACTION`
+    fn args(&self) -> Vec<&str> {
+        self.open.iter().map(Url::as_str).collect()
+    }
+}
+
 /// The context page to display in the context drawer.
 #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
 pub enum ContextPage {
@@ -2,6 +2,7 @@
 
 use std::{any::TypeId, path::PathBuf};
 
+use common_utils::models::install_scope::InstallScope;
 use cosmic::Application;
 use cosmic::{
     cosmic_config::{self, cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry},
@@ -13,21 +14,28 @@ pub const CONFIG_VERSION: u64 = 1;
 
 #[derive(Clone, CosmicConfigEntry, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Config {
+    /// Directory of the AppImages installed for the current user
     pub installation_dir: PathBuf,
 }
 
 impl Default for Config {
     fn default() -> Self {
-        let installation_dir = PathBuf::from(format!(
-            "{}/AppImages/",
-            dirs::home_dir().unwrap().to_string_lossy()
-        ));
+        let installation_dir = InstallScope::User.default_installation_dir().unwrap();
 
         Self { installation_dir }
     }
 }
 
 impl Config {
+    /// Directory of the AppImages installed in the given scope,
+    /// AppImages for all users never go to a home directory
+    pub fn installation_dir_for(&self, scope: InstallScope) -> PathBuf {
+        match scope {
+            InstallScope::User => self.installation_dir.clone(),
+            InstallScope::System => InstallScope::System.default_installation_dir().unwrap(),
+        }
+    }
+
     pub fn load() -> (Option<cosmic_config::Config>, Self) {
         match cosmic_config::Config::new(crate::app::AppModel::APP_ID, CONFIG_VERSION) {
             Ok(config_handler) => {
@@ -0,0 +1,23 @@
+use std::process::Command;
+
+use common_utils::models::install_scope::InstallScope;
+
+/// Command running the AppHub backend next to the current executable.
+/// Installations for all users go through pkexec, the ones for the current user need no privileges.
+pub fn backend_command(scope: InstallScope) -> Result<Command, String> {
+    let current_exe = std::env::current_exe().map_err(|_| "unable to get current exe")?;
+    let backend = current_exe
+        .parent()
+        .ok_or("unable to get current exe directory")?
+        .join("app_hub_backend");
+
+    let mut command = if scope.needs_privileges() {
+        let mut command = Command::new("pkexec");
+        command.arg(backend);
+        command
+    } else {
+        Command::new(backend)
+    };
+    command.arg("--scope").arg(scope.to_string());
+    Ok(command)
+}
@@ -1 +1,2 @@
-pub mod nav;
\ No newline at end of file
+pub mod backend;
+pub mod nav;
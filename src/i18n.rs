@@ -5,7 +5,7 @@
 use i18n_embed::{
     fluent::{fluent_language_loader, FluentLanguageLoader},
     unic_langid::LanguageIdentifier,
-    DefaultLocalizer, LanguageLoader, Localizer,
+    DefaultLocalizer, DesktopLanguageRequester, LanguageLoader, Localizer,
 };
 use once_cell::sync::Lazy;
 use rust_embed::RustEmbed;
@@ -37,6 +37,15 @@ pub static LANGUAGE_LOADER: Lazy<FluentLanguageLoader> = Lazy::new(|| {
     loader
 });
 
+/// The languages requested by the desktop, in order of preference, as BCP 47 tags.
+/// Used to pick the translations of the desktop entries of installed apps.
+pub static REQUESTED_LANGUAGES: Lazy<Vec<String>> = Lazy::new(|| {
+    DesktopLanguageRequester::requested_languages()
+        .iter()
+        .map(|language| language.to_string())
+        .collect()
+});
+
 /// Request a localized string by ID from the i18n/ directory.
 #[macro_export]
 macro_rules! fl {
@@ -17,6 +17,9 @@ fn main() -> cosmic::iced::Result {
     // Settings for configuring the application window and iced runtime.
     let settings = cosmic::app::Settings::default();
 
-    // Starts the application's event loop with `()` as the application's flags.
-    cosmic::app::run::<app::AppModel>(settings, ())
+    // The AppImages to preview, when AppHub opens them from a file manager.
+    let flags = app::Flags::from_args(std::env::args().skip(1));
+
+    // Starts the application's event loop, or hands the files to the running instance.
+    cosmic::app::run_single_instance::<app::AppModel>(settings, flags)
 }
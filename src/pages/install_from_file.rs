@@ -1,33 +1,146 @@
 use cosmic::dialog::ashpd::url::Url;
 use cosmic::dialog::file_chooser::{self, FileFilter};
-use cosmic::iced::Length;
+use cosmic::iced::{Length, Subscription};
 use cosmic::widget::vertical_space;
 use cosmic::{
     iced::{self},
     widget::{self, column},
     Command, Element,
 };
+use common_utils::app_image_reader::AppImageReader;
+use common_utils::app_images_helpers::identify_app_image;
+use common_utils::desktop_file_validator::{has_errors, validate_desktop_file, ValidationIssue};
+use common_utils::download_helpers::{
+    download_app_image, is_download_url, remove_download, DownloadOptions,
+};
+use common_utils::install_database::InstallDatabase;
+use common_utils::models::app_identity::AppIdentity;
+use common_utils::models::existing_app_policy::ExistingAppPolicy;
+use common_utils::models::install_outcome::InstallOutcome;
+use common_utils::models::install_record::InstallRecord;
+use common_utils::models::install_scope::InstallScope;
+use common_utils::models::signature_status::SignatureStatus;
+use common_utils::signature_helpers::{keyring_dirs, verify_app_image_signature};
+use common_utils::version_helpers::compare_versions;
+use futures_util::{SinkExt, StreamExt};
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::sync::Arc;
+use std::thread;
 
 use crate::config::Config;
+use crate::core::backend::backend_command;
 use crate::{app, fl};
 
 #[derive(Debug, Clone)]
 pub enum InstallFromFileMessage {
     ChooseFile,
     FileSelected(Url),
+    /// What was read from the selected AppImage, off the UI thread
+    Previewed(PathBuf, Box<Preview>),
+    /// Several files chosen at once, installed as a batch
+    FilesSelected(Vec<Url>),
+    FileDropped(PathBuf),
+    /// Install the batch, with what to do for the apps which are already installed
+    InstallBatch(ExistingAppPolicy),
+    /// Outcome of one AppImage of the running batch, as reported by the backend
+    BatchOutcome(InstallOutcome),
+    BatchFinished,
+    /// Files or URLs AppHub was launched with
+    Open(Vec<Url>),
+    Install(ExistingAppPolicy),
+    ClearSelection,
     InstallationSuccessful,
     Cancelled,
     OpenError(Arc<file_chooser::Error>),
     NoSandboxSelected(bool),
+    SystemWideSelected(bool),
+    PortableSelected(bool),
+    UrlChanged(String),
+    ExpectedSha256Changed(String),
+    Download,
+    DownloadProgress(u64, Option<u64>),
+    DownloadFinished(Result<PathBuf, String>),
+}
+
+/// What is known about an AppImage before it is installed
+#[derive(Debug, Clone)]
+pub struct Preview {
+    signature: Result<SignatureStatus, String>,
+    validation: Result<Vec<ValidationIssue>, String>,
+    identity: Option<AppIdentity>,
+    installed: Vec<InstallRecord>,
+}
+
+impl Preview {
+    fn failed(error: String) -> Self {
+        Self {
+            signature: Err(error.clone()),
+            validation: Err(error),
+            identity: None,
+            installed: Vec::new(),
+        }
+    }
+
+    /// Whether the desktop entry allows the installation, the AppImages with errors are not installed
+    fn installable(&self) -> bool {
+        matches!(&self.validation, Ok(issues) if !has_errors(issues))
+    }
+}
+
+/// AppImages being installed, a batch or the selected one, which runs the installation subscription
+#[derive(Debug, Clone)]
+struct BatchInstallation {
+    file_paths: Vec<PathBuf>,
+    /// Downloaded AppImages, removed once the installation is over whatever its outcome
+    downloads: Vec<PathBuf>,
+    /// AppImages of the batch left out, because their desktop entry has errors
+    excluded: Vec<PathBuf>,
+    scope: InstallScope,
+    installation_dir: PathBuf,
+    no_sandbox: bool,
+    portable: bool,
+    existing: ExistingAppPolicy,
 }
 
 pub struct InstallFromFile {
     loading: bool,
     successful: bool,
     no_sandbox: bool,
+    /// Install for all users instead of the current user only
+    system_wide: bool,
+    /// Create portable home and config directories next to the installed AppImage
+    portable: bool,
+    selected_file: Option<PathBuf>,
+    signature: Option<Result<SignatureStatus, String>>,
+    /// Problems found in the desktop entry of the selected AppImage
+    validation: Option<Result<Vec<ValidationIssue>, String>>,
+    /// The app packaged in the selected AppImage
+    identity: Option<AppIdentity>,
+    /// The installed versions of that app, at most one per scope
+    installed: Vec<InstallRecord>,
+    /// URL of the AppImage to download, as typed
+    url: String,
+    /// Checksum the downloaded AppImage must match, as typed
+    expected_sha256: String,
+    /// The URL being downloaded, which runs the download subscription
+    downloading: Option<String>,
+    /// Downloaded and total size of the running download
+    download_progress: (u64, Option<u64>),
+    download_error: Option<String>,
+    /// The selected file was downloaded, and is removed once installed
+    downloaded: bool,
+    /// AppImages installed together, under a single authorization
+    batch: Vec<PathBuf>,
+    /// What was read from each AppImage of the batch
+    batch_previews: HashMap<PathBuf, Preview>,
+    /// Outcome of each AppImage of the last batch
+    batch_outcomes: Vec<InstallOutcome>,
+    /// The batch being installed
+    installing_batch: Option<BatchInstallation>,
 }
 
 impl Default for InstallFromFile {
@@ -38,6 +151,23 @@ impl Default for InstallFromFile {
             loading: false,
             successful: false,
             no_sandbox: false,
+            system_wide: false,
+            portable: false,
+            selected_file: None,
+            signature: None,
+            validation: None,
+            identity: None,
+            installed: Vec::new(),
+            url: String::new(),
+            expected_sha256: String::new(),
+            downloading: None,
+            download_progress: (0, None),
+            download_error: None,
+            downloaded: false,
+            batch: Vec::new(),
+            batch_previews: HashMap::new(),
+            batch_outcomes: Vec::new(),
+            installing_batch: None,
         }
     }
 }
@@ -51,12 +181,248 @@ impl InstallFromFile {
                 .align_y(iced::alignment::Vertical::Center),
         );
 
-        if self.loading {
+        if let Some(url) = &self.downloading {
+            // Progress of the download, the preview follows once it completes
+            let (downloaded, total) = self.download_progress;
+            col = col.push(widget::vertical_space(Length::from(30)));
+            col = col.push(
+                widget::container(widget::text::text(fl!("downloading", url = url.clone())))
+                    .width(iced::Length::Fill)
+                    .align_x(iced::alignment::Horizontal::Center),
+            );
+            let (value, range) = match total {
+                Some(total) if total > 0 => (downloaded as f32, 0.0..=total as f32),
+                _ => (0.0, 0.0..=1.0),
+            };
+            col = col.push(
+                widget::container(widget::progress_bar(range, value).width(Length::from(400)))
+                    .width(iced::Length::Fill)
+                    .align_x(iced::alignment::Horizontal::Center),
+            );
+        } else if self.loading {
             col = col.push(
                 widget::container(cosmic::widget::text::text(fl!("installing")))
                     .width(iced::Length::Fill)
                     .align_x(iced::alignment::Horizontal::Center),
             );
+        } else if !self.batch.is_empty() {
+            // The AppImages of the batch, installed with the default options once all are read.
+            // The ones whose desktop entry has errors are left out
+            let count = self.batch.len();
+            let mut files = column::<InstallFromFileMessage>()
+                .spacing(10)
+                .push(widget::text::title4(fl!("batch-selected", count = count)));
+            for path in &self.batch {
+                let mut file = column::<InstallFromFileMessage>()
+                    .spacing(5)
+                    .push(widget::text::heading(file_name(path)));
+                file = match self.batch_previews.get(path) {
+                    Some(preview) => file
+                        .push(widget::text::text(signature_text(&preview.signature)))
+                        .push(validation_findings(&preview.validation)),
+                    None => file.push(widget::text::text(fl!("reading-app-image"))),
+                };
+                files = files.push(file);
+            }
+            let installable = self
+                .batch
+                .iter()
+                .all(|path| self.batch_previews.contains_key(path))
+                && self
+                    .batch
+                    .iter()
+                    .filter_map(|path| self.batch_previews.get(path))
+                    .any(Preview::installable);
+            let mut side_by_side_button =
+                cosmic::widget::button::text(fl!("install-all-side-by-side"));
+            let mut downgrade_button = cosmic::widget::button::text(fl!("install-all-downgrade"));
+            let mut install_button = cosmic::widget::button::text(fl!("install-all"))
+                .style(widget::button::Style::Suggested);
+            if installable {
+                side_by_side_button = side_by_side_button.on_press(
+                    InstallFromFileMessage::InstallBatch(ExistingAppPolicy::SideBySide),
+                );
+                downgrade_button = downgrade_button.on_press(InstallFromFileMessage::InstallBatch(
+                    ExistingAppPolicy::Downgrade,
+                ));
+                install_button = install_button.on_press(InstallFromFileMessage::InstallBatch(
+                    ExistingAppPolicy::Upgrade,
+                ));
+            }
+            col = col.push(widget::vertical_space(Length::from(30)));
+            col = col.push(
+                widget::container(files)
+                    .width(iced::Length::Fill)
+                    .align_x(iced::alignment::Horizontal::Center),
+            );
+            col = col.push(widget::vertical_space(Length::from(30)));
+            col = col.push(
+                widget::container(
+                    widget::row()
+                        .spacing(10)
+                        .push(
+                            cosmic::widget::button::text(fl!("cancel"))
+                                .on_press(InstallFromFileMessage::ClearSelection),
+                        )
+                        .push(side_by_side_button)
+                        .push(downgrade_button)
+                        .push(install_button),
+                )
+                .width(iced::Length::Fill)
+                .align_x(iced::alignment::Horizontal::Center),
+            );
+            col = col.push(widget::vertical_space(Length::from(30)));
+            col = col.push(
+                widget::container(cosmic::widget::checkbox(
+                    "No sandbox",
+                    self.no_sandbox,
+                    InstallFromFileMessage::NoSandboxSelected,
+                ))
+                .width(iced::Length::Fill)
+                .align_x(iced::alignment::Horizontal::Center),
+            );
+            col = col.push(
+                widget::container(cosmic::widget::checkbox(
+                    fl!("install-for-all-users"),
+                    self.system_wide,
+                    InstallFromFileMessage::SystemWideSelected,
+                ))
+                .width(iced::Length::Fill)
+                .align_x(iced::alignment::Horizontal::Center),
+            );
+            if !self.system_wide {
+                col = col.push(
+                    widget::container(cosmic::widget::checkbox(
+                        fl!("portable-data"),
+                        self.portable,
+                        InstallFromFileMessage::PortableSelected,
+                    ))
+                    .width(iced::Length::Fill)
+                    .align_x(iced::alignment::Horizontal::Center),
+                );
+            }
+        } else if let Some(selected_file) = &self.selected_file {
+            // Show what is known about the AppImage before anything is written
+            let file_name = file_name(selected_file);
+            let signature = match &self.signature {
+                Some(signature) => signature_text(signature),
+                None => fl!("reading-app-image"),
+            };
+
+            col = col.push(widget::vertical_space(Length::from(30)));
+            col = col.push(
+                widget::container(widget::text::title4(file_name))
+                    .width(iced::Length::Fill)
+                    .align_x(iced::alignment::Horizontal::Center),
+            );
+            col = col.push(
+                widget::container(widget::text::text(signature))
+                    .width(iced::Length::Fill)
+                    .align_x(iced::alignment::Horizontal::Center),
+            );
+
+            // Findings of the desktop entry validation; errors prevent the installation,
+            // which waits for the AppImage to be read
+            let installable = matches!(&self.validation, Some(Ok(issues)) if !has_errors(issues));
+            let findings = match &self.validation {
+                Some(validation) => validation_findings(validation),
+                None => column::<InstallFromFileMessage>(),
+            };
+            col = col.push(widget::vertical_space(Length::from(10)));
+            col = col.push(
+                widget::container(findings)
+                    .width(iced::Length::Fill)
+                    .align_x(iced::alignment::Horizontal::Center),
+            );
+
+            // An app which is already installed is upgraded, downgraded or kept next to the new one
+            let scope = self.scope();
+            let installed = self.installed.iter().find(|record| record.scope == scope);
+            let version = self
+                .identity
+                .as_ref()
+                .and_then(|identity| identity.version.clone());
+            let (install_label, policy) = match installed {
+                None => (fl!("install"), ExistingAppPolicy::Upgrade),
+                Some(record) => {
+                    let installed_version =
+                        record.version.clone().unwrap_or(fl!("unknown-version"));
+                    col = col.push(
+                        widget::container(widget::text::text(fl!(
+                            "already-installed",
+                            name = record.name.clone(),
+                            installed = installed_version,
+                            version = version.clone().unwrap_or(fl!("unknown-version"))
+                        )))
+                        .width(iced::Length::Fill)
+                        .align_x(iced::alignment::Horizontal::Center),
+                    );
+                    match (&record.version, &version) {
+                        (Some(installed_version), Some(version)) => {
+                            match compare_versions(version, installed_version) {
+                                Ordering::Greater => (fl!("upgrade"), ExistingAppPolicy::Upgrade),
+                                Ordering::Equal => (fl!("reinstall"), ExistingAppPolicy::Upgrade),
+                                Ordering::Less => (fl!("downgrade"), ExistingAppPolicy::Downgrade),
+                            }
+                        }
+                        _ => (fl!("replace"), ExistingAppPolicy::Upgrade),
+                    }
+                }
+            };
+
+            let mut install_button =
+                cosmic::widget::button::text(install_label).style(widget::button::Style::Suggested);
+            let mut side_by_side_button = cosmic::widget::button::text(fl!("install-side-by-side"));
+            if installable {
+                install_button = install_button.on_press(InstallFromFileMessage::Install(policy));
+                side_by_side_button = side_by_side_button.on_press(
+                    InstallFromFileMessage::Install(ExistingAppPolicy::SideBySide),
+                );
+            }
+            let mut buttons = widget::row().spacing(10).push(
+                cosmic::widget::button::text(fl!("cancel"))
+                    .on_press(InstallFromFileMessage::ClearSelection),
+            );
+            if installed.is_some() {
+                buttons = buttons.push(side_by_side_button);
+            }
+            buttons = buttons.push(install_button);
+            col = col.push(widget::vertical_space(Length::from(30)));
+            col = col.push(
+                widget::container(buttons)
+                    .width(iced::Length::Fill)
+                    .align_x(iced::alignment::Horizontal::Center),
+            );
+            col = col.push(widget::vertical_space(Length::from(30)));
+            col = col.push(
+                widget::container(cosmic::widget::checkbox(
+                    "No sandbox",
+                    self.no_sandbox,
+                    InstallFromFileMessage::NoSandboxSelected,
+                ))
+                .width(iced::Length::Fill)
+                .align_x(iced::alignment::Horizontal::Center),
+            );
+            col = col.push(
+                widget::container(cosmic::widget::checkbox(
+                    fl!("install-for-all-users"),
+                    self.system_wide,
+                    InstallFromFileMessage::SystemWideSelected,
+                ))
+                .width(iced::Length::Fill)
+                .align_x(iced::alignment::Horizontal::Center),
+            );
+            if !self.system_wide {
+                col = col.push(
+                    widget::container(cosmic::widget::checkbox(
+                        fl!("portable-data"),
+                        self.portable,
+                        InstallFromFileMessage::PortableSelected,
+                    ))
+                    .width(iced::Length::Fill)
+                    .align_x(iced::alignment::Horizontal::Center),
+                );
+            }
         } else {
             col = col.push(widget::vertical_space(Length::from(30)));
             col = col.push(
@@ -68,6 +434,11 @@ impl InstallFromFile {
                 .width(iced::Length::Fill)
                 .align_x(iced::alignment::Horizontal::Center),
             );
+            col = col.push(
+                widget::container(widget::text::caption(fl!("drop-files")))
+                    .width(iced::Length::Fill)
+                    .align_x(iced::alignment::Horizontal::Center),
+            );
             col = col.push(widget::vertical_space(Length::from(30)));
             col = col.push(
                 widget::container(cosmic::widget::checkbox(
@@ -78,6 +449,45 @@ impl InstallFromFile {
                 .width(iced::Length::Fill)
                 .align_x(iced::alignment::Horizontal::Center),
             );
+
+            // The AppImage can also be downloaded, then previewed like a chosen file
+            let mut download_button = cosmic::widget::button::text(fl!("download"));
+            if is_download_url(self.url.trim()) {
+                download_button = download_button.on_press(InstallFromFileMessage::Download);
+            }
+            col = col.push(widget::vertical_space(Length::from(30)));
+            col = col.push(
+                widget::container(
+                    column::<InstallFromFileMessage>()
+                        .spacing(10)
+                        .width(Length::from(400))
+                        .push(widget::text::heading(fl!("install-from-url")))
+                        .push(
+                            cosmic::widget::text_input(fl!("app-image-url"), self.url.clone())
+                                .on_input(InstallFromFileMessage::UrlChanged),
+                        )
+                        .push(
+                            cosmic::widget::text_input(
+                                fl!("expected-sha256"),
+                                self.expected_sha256.clone(),
+                            )
+                            .on_input(InstallFromFileMessage::ExpectedSha256Changed),
+                        )
+                        .push(download_button),
+                )
+                .width(iced::Length::Fill)
+                .align_x(iced::alignment::Horizontal::Center),
+            );
+            if let Some(error) = &self.download_error {
+                col = col.push(
+                    widget::container(widget::text::text(fl!(
+                        "download-failed",
+                        error = error.clone()
+                    )))
+                    .width(iced::Length::Fill)
+                    .align_x(iced::alignment::Horizontal::Center),
+                );
+            }
         }
 
         if self.successful {
@@ -87,6 +497,21 @@ impl InstallFromFile {
                     .align_x(iced::alignment::Horizontal::Center),
             );
         }
+        if !self.batch_outcomes.is_empty() {
+            let mut outcomes = column::<InstallFromFileMessage>().spacing(5);
+            for outcome in &self.batch_outcomes {
+                let name = file_name(&outcome.file_path);
+                outcomes = outcomes.push(widget::text::text(match &outcome.error {
+                    None => fl!("batch-installed", name = name),
+                    Some(error) => fl!("batch-failed", name = name, error = error.clone()),
+                }));
+            }
+            col = col.push(vertical_space(Length::from(30))).push(
+                widget::container(outcomes)
+                    .width(iced::Length::Fill)
+                    .align_x(iced::alignment::Horizontal::Center),
+            );
+        }
 
         widget::container(col)
             .width(iced::Length::Fill)
@@ -96,6 +521,116 @@ impl InstallFromFile {
             .into()
     }
 
+    /// Listen to the files dropped on the window, and run the requested download or batch
+    pub fn subscription(&self) -> Subscription<InstallFromFileMessage> {
+        let dropped_files = cosmic::iced::event::listen_with(|event, _status| match event {
+            cosmic::iced::Event::Window(_, cosmic::iced::window::Event::FileDropped(path)) => {
+                Some(InstallFromFileMessage::FileDropped(path))
+            }
+            _ => None,
+        });
+        Subscription::batch(vec![
+            dropped_files,
+            self.download_subscription(),
+            self.batch_subscription(),
+        ])
+    }
+
+    /// Run the requested download on a thread, reporting its progress to the page
+    fn download_subscription(&self) -> Subscription<InstallFromFileMessage> {
+        let Some(url) = self.downloading.clone() else {
+            return Subscription::none();
+        };
+        let expected_sha256 = self.expected_sha256.trim().to_string();
+        let options = DownloadOptions {
+            sha256: (!expected_sha256.is_empty()).then_some(expected_sha256),
+            ..DownloadOptions::default()
+        };
+
+        cosmic::iced::subscription::channel(url.clone(), 16, move |mut output| async move {
+            let (sender, mut receiver) = cosmic::iced::futures::channel::mpsc::unbounded();
+            thread::spawn(move || {
+                // the AppImages are downloaded for the current user, even the ones installed for all users
+                let result = InstallScope::User
+                    .download_dir()
+                    .map_err(|error| error.to_string())
+                    .and_then(|download_dir| {
+                        download_app_image(&url, &download_dir, &options, |downloaded, total| {
+                            let _ = sender.unbounded_send(
+                                InstallFromFileMessage::DownloadProgress(downloaded, total),
+                            );
+                        })
+                    });
+                let _ = sender.unbounded_send(InstallFromFileMessage::DownloadFinished(result));
+            });
+            while let Some(message) = receiver.next().await {
+                let _ = output.send(message).await;
+            }
+
+            futures_util::future::pending().await
+        })
+    }
+
+    /// Run the backend installing the batch on a thread, reporting each outcome to the page
+    fn batch_subscription(&self) -> Subscription<InstallFromFileMessage> {
+        let Some(installation) = self.installing_batch.clone() else {
+            return Subscription::none();
+        };
+
+        cosmic::iced::subscription::channel(
+            installation.file_paths.clone(),
+            16,
+            move |mut output| async move {
+                let (sender, mut receiver) = cosmic::iced::futures::channel::mpsc::unbounded();
+                thread::spawn(move || {
+                    install_batch(&installation, |outcome| {
+                        let _ =
+                            sender.unbounded_send(InstallFromFileMessage::BatchOutcome(outcome));
+                    });
+                    let _ = sender.unbounded_send(InstallFromFileMessage::BatchFinished);
+                });
+                while let Some(message) = receiver.next().await {
+                    let _ = output.send(message).await;
+                }
+
+                futures_util::future::pending().await
+            },
+        )
+    }
+
+    /// Add an AppImage to the batch, once, and read it
+    fn add_to_batch(&mut self, path: PathBuf) -> Command<app::Message> {
+        if self.batch.contains(&path) {
+            return Command::none();
+        }
+        self.batch.push(path.clone());
+        preview(path)
+    }
+
+    /// Remove the selected file when it was downloaded, it is not needed anymore
+    fn discard_download(&mut self, path: &Path) {
+        if self.downloaded {
+            self.downloaded = false;
+            if let Err(error) = remove_download(path) {
+                log::error!("{}", error);
+            }
+        }
+    }
+
+    /// Whether portable directories are requested, only the installations for the current user have them
+    fn portable_for(&self, scope: InstallScope) -> bool {
+        self.portable && !scope.needs_privileges()
+    }
+
+    /// The scope the selected AppImage would be installed in
+    fn scope(&self) -> InstallScope {
+        if self.system_wide {
+            InstallScope::System
+        } else {
+            InstallScope::User
+        }
+    }
+
     pub fn update(&mut self, message: InstallFromFileMessage) -> Command<crate::app::Message> {
         let mut commands = vec![];
         match message {
@@ -118,12 +653,8 @@ impl InstallFromFile {
                         .title("Choose a file")
                         .filter(filter);
 
-                    match dialog.open_file().await {
-                        Ok(response) => {
-                            //InstallFromFileMessage::FileSelected(response.url().to_owned())
-
-                            app::Message::ChooseFile(response.url().clone())
-                        }
+                    match dialog.open_files().await {
+                        Ok(response) => app::Message::ChooseFiles(response.urls().to_vec()),
 
                         Err(file_chooser::Error::Cancelled) => app::Message::Cancelled,
 
@@ -132,88 +663,23 @@ impl InstallFromFile {
                 }));
             }
             InstallFromFileMessage::FileSelected(url) => {
-                log::info!("requested to install {}", url);
+                log::info!("selected file {}", url);
+                self.loading = false;
+                self.successful = false;
+                self.batch_outcomes.clear();
+                if let Some(path) = self.selected_file.take() {
+                    self.discard_download(&path);
+                }
+
+                self.signature = None;
+                self.validation = None;
+                self.identity = None;
+                self.installed.clear();
 
                 match url.to_file_path() {
                     Ok(path) => {
-                        let config = Config::load();
-                        let installation_dir = config.1.installation_dir;
-                        log::info!("Using installation dir : {:?}", installation_dir);
-                        //let result = install_app_image(path, installation_dir, false);
-                        //log::info!("result from install: {:?}", result);
-
-                        let current_exe =
-                            std::env::current_exe().map_err(|_| "unable to get current exe");
-                        match current_exe {
-                            Ok(current_exe) => {
-                                let cmd = std::process::Command::new("pkexec")
-                                    .arg(current_exe.parent().unwrap().join("app_hub_backend"))
-                                    .arg("--action")
-                                    .arg("install")
-                                    .arg("--file-path")
-                                    .arg(path)
-                                    .arg("--install-dir")
-                                    .arg(installation_dir)
-                                    .arg("--no-sandbox")
-                                    .arg(self.no_sandbox.to_string())
-                                    .stdout(Stdio::piped())
-                                    .stderr(Stdio::piped())
-                                    .spawn();
-
-                                match cmd {
-                                    Ok(mut child) => {
-                                        // Read stdout
-                                        if let Some(stdout) = child.stdout.take() {
-                                            let reader = BufReader::new(stdout);
-                                            for line in reader.lines() {
-                                                if let Ok(line) = line {
-                                                    log::debug!("app_hub_backend output: {}", line);
-                                                } else {
-                                                    log::error!("Failed to read line from stdout");
-                                                }
-                                            }
-                                        }
-
-                                        // Read stderr
-                                        if let Some(stderr) = child.stderr.take() {
-                                            let reader = BufReader::new(stderr);
-                                            for line in reader.lines() {
-                                                if let Ok(line) = line {
-                                                    log::error!("app_hub_backend error: {}", line);
-                                                } else {
-                                                    log::error!("Failed to read line from stderr");
-                                                }
-                                            }
-                                        }
-
-                                        let output = child
-                                            .wait_with_output()
-                                            .expect("Failed to wait on child");
-                                        log::debug!("output: {:?}", output);
-                                        if output.status.success() {
-                                            log::info!("Installation successful");
-                                            self.successful = true;
-                                            commands.push(Command::perform(async {}, |_| {
-                                                app::Message::InstallationSuccessful
-                                            }));
-                                            commands.push(Command::perform(async {}, |_| {
-                                                app::Message::LoadApps
-                                            }));
-                                        } else {
-                                            log::error!("Installation failed");
-                                            //return Err("Installation failed".to_string());
-                                        }
-                                    }
-                                    Err(error) => {
-                                        log::error!("error: {:?}", error);
-                                        //return Err("Failed to install AppImage".to_string());
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                log::error!("current_exe error: {:?}", e);
-                            }
-                        };
+                        self.selected_file = Some(path.clone());
+                        commands.push(preview(path));
                     }
                     Err(e) => {
                         log::error!("error converting url to file path: {:?}", e);
@@ -221,6 +687,222 @@ impl InstallFromFile {
                     }
                 };
             }
+            InstallFromFileMessage::Previewed(path, preview) => {
+                // another file may have been selected in the meantime
+                if self.selected_file.as_ref() == Some(&path) {
+                    log::info!("signature status: {:?}", preview.signature);
+                    log::info!("desktop file validation: {:?}", preview.validation);
+                    self.signature = Some(preview.signature);
+                    self.validation = Some(preview.validation);
+                    self.identity = preview.identity;
+                    self.installed = preview.installed;
+                } else if self.batch.contains(&path) {
+                    log::info!(
+                        "desktop file validation of {:?}: {:?}",
+                        path,
+                        preview.validation
+                    );
+                    self.batch_previews.insert(path, *preview);
+                }
+            }
+            InstallFromFileMessage::FilesSelected(urls) => {
+                log::info!("selected {} files", urls.len());
+                self.loading = false;
+                if let [url] = urls.as_slice() {
+                    commands.push(self.update(InstallFromFileMessage::FileSelected(url.clone())));
+                } else {
+                    self.successful = false;
+                    self.batch_outcomes.clear();
+                    for url in urls {
+                        match url.to_file_path() {
+                            Ok(path) => commands.push(self.add_to_batch(path)),
+                            Err(e) => log::error!("error converting url to file path: {:?}", e),
+                        }
+                    }
+                }
+            }
+            InstallFromFileMessage::Open(urls) => {
+                if self.loading || self.downloading.is_some() {
+                    log::warn!("ignoring the files opened during an installation");
+                    return Command::none();
+                }
+                // the opened files replace the ones previewed
+                commands.push(self.update(InstallFromFileMessage::ClearSelection));
+                let (files, urls): (Vec<Url>, Vec<Url>) =
+                    urls.into_iter().partition(|url| url.scheme() == "file");
+                if !files.is_empty() {
+                    commands.push(self.update(InstallFromFileMessage::FilesSelected(files)));
+                } else if let Some(url) = urls.first() {
+                    self.url = url.to_string();
+                    commands.push(self.update(InstallFromFileMessage::Download));
+                }
+            }
+            InstallFromFileMessage::FileDropped(path) => {
+                log::info!("dropped file {:?}", path);
+                if self.loading || self.downloading.is_some() {
+                    log::warn!("ignoring the file dropped during an installation");
+                } else if self.batch.is_empty() && self.selected_file.is_none() {
+                    // a single file is previewed, the next dropped ones turn it into a batch
+                    match Url::from_file_path(&path) {
+                        Ok(url) => {
+                            commands.push(self.update(InstallFromFileMessage::FileSelected(url)))
+                        }
+                        Err(_) => log::error!("invalid dropped file path: {:?}", path),
+                    }
+                } else {
+                    self.batch_outcomes.clear();
+                    if let Some(selected_file) = self.selected_file.take() {
+                        // the selected file keeps its preview, or gets it once it is read
+                        if let (Some(signature), Some(validation)) =
+                            (self.signature.take(), self.validation.take())
+                        {
+                            let preview = Preview {
+                                signature,
+                                validation,
+                                identity: self.identity.take(),
+                                installed: std::mem::take(&mut self.installed),
+                            };
+                            self.batch_previews.insert(selected_file.clone(), preview);
+                        }
+                        self.identity = None;
+                        self.installed.clear();
+                        if !self.batch.contains(&selected_file) {
+                            self.batch.push(selected_file);
+                        }
+                    }
+                    commands.push(self.add_to_batch(path));
+                }
+            }
+            InstallFromFileMessage::InstallBatch(policy) => {
+                let previews = std::mem::take(&mut self.batch_previews);
+                let (file_paths, excluded): (Vec<PathBuf>, Vec<PathBuf>) =
+                    std::mem::take(&mut self.batch)
+                        .into_iter()
+                        .partition(|path| previews.get(path).is_some_and(Preview::installable));
+                for path in &excluded {
+                    log::warn!("not installing {:?}, its desktop entry has errors", path);
+                }
+                let scope = self.scope();
+                let installation_dir = Config::load().1.installation_dir_for(scope);
+                log::info!(
+                    "requested to install {} AppImages in {:?} ({})",
+                    file_paths.len(),
+                    installation_dir,
+                    policy
+                );
+                self.batch_outcomes.clear();
+                if file_paths.is_empty() {
+                    return Command::none();
+                }
+                self.loading = true;
+                self.installing_batch = Some(BatchInstallation {
+                    file_paths,
+                    downloads: Vec::new(),
+                    excluded,
+                    scope,
+                    installation_dir,
+                    no_sandbox: self.no_sandbox,
+                    portable: self.portable_for(scope),
+                    existing: policy,
+                });
+            }
+            InstallFromFileMessage::BatchOutcome(outcome) => {
+                self.batch_outcomes.push(outcome);
+            }
+            InstallFromFileMessage::BatchFinished => {
+                // stops the subscription
+                self.loading = false;
+                let Some(installation) = self.installing_batch.take() else {
+                    return Command::none();
+                };
+                for download in &installation.downloads {
+                    if let Err(error) = remove_download(download) {
+                        log::error!("{}", error);
+                    }
+                }
+                if self.batch_outcomes.is_empty() {
+                    // the backend did not start, for example the authorization was refused
+                    let error = fl!("batch-not-run");
+                    self.batch_outcomes = installation
+                        .file_paths
+                        .into_iter()
+                        .map(|file_path| InstallOutcome {
+                            file_path,
+                            error: Some(error.clone()),
+                        })
+                        .collect();
+                }
+                let error = fl!("batch-excluded");
+                self.batch_outcomes
+                    .extend(
+                        installation
+                            .excluded
+                            .into_iter()
+                            .map(|file_path| InstallOutcome {
+                                file_path,
+                                error: Some(error.clone()),
+                            }),
+                    );
+                self.successful = self.batch_outcomes.iter().all(InstallOutcome::is_success);
+                if self.successful {
+                    commands.push(Command::perform(async {}, |_| {
+                        app::Message::InstallationSuccessful
+                    }));
+                }
+                if self.batch_outcomes.iter().any(InstallOutcome::is_success) {
+                    commands.push(Command::perform(async {}, |_| app::Message::LoadApps));
+                }
+            }
+            InstallFromFileMessage::ClearSelection => {
+                self.batch.clear();
+                self.batch_previews.clear();
+                self.batch_outcomes.clear();
+                if let Some(path) = self.selected_file.take() {
+                    self.discard_download(&path);
+                }
+                self.signature = None;
+                self.validation = None;
+                self.identity = None;
+                self.installed.clear();
+            }
+            InstallFromFileMessage::Install(policy) => {
+                self.signature = None;
+                self.validation = None;
+                self.identity = None;
+                self.installed.clear();
+                let downloaded = std::mem::take(&mut self.downloaded);
+                match self.selected_file.take() {
+                    Some(path) => {
+                        log::info!("requested to install {:?} ({})", path, policy);
+                        let scope = self.scope();
+                        let config = Config::load();
+                        let installation_dir = config.1.installation_dir_for(scope);
+                        log::info!("Using installation dir : {:?}", installation_dir);
+
+                        // installed by the backend like a batch of one AppImage
+                        self.loading = true;
+                        self.successful = false;
+                        self.batch_outcomes.clear();
+                        self.installing_batch = Some(BatchInstallation {
+                            downloads: if downloaded {
+                                vec![path.clone()]
+                            } else {
+                                Vec::new()
+                            },
+                            file_paths: vec![path],
+                            excluded: Vec::new(),
+                            scope,
+                            installation_dir,
+                            no_sandbox: self.no_sandbox,
+                            portable: self.portable_for(scope),
+                            existing: policy,
+                        });
+                    }
+                    None => {
+                        log::error!("no file selected");
+                    }
+                };
+            }
             InstallFromFileMessage::Cancelled => {
                 self.loading = false;
             }
@@ -235,7 +917,216 @@ impl InstallFromFile {
                 self.no_sandbox = selected;
                 log::info!("no sandbox: {}", self.no_sandbox);
             }
+            InstallFromFileMessage::SystemWideSelected(selected) => {
+                self.system_wide = selected;
+                log::info!("install for all users: {}", self.system_wide);
+            }
+            InstallFromFileMessage::PortableSelected(selected) => {
+                self.portable = selected;
+                log::info!("portable: {}", self.portable);
+            }
+            InstallFromFileMessage::UrlChanged(url) => {
+                self.url = url;
+            }
+            InstallFromFileMessage::ExpectedSha256Changed(expected_sha256) => {
+                self.expected_sha256 = expected_sha256;
+            }
+            InstallFromFileMessage::Download => {
+                let url = self.url.trim().to_string();
+                log::info!("downloading {}", url);
+                self.successful = false;
+                self.download_error = None;
+                self.download_progress = (0, None);
+                self.downloading = Some(url);
+            }
+            InstallFromFileMessage::DownloadProgress(downloaded, total) => {
+                self.download_progress = (downloaded, total);
+            }
+            InstallFromFileMessage::DownloadFinished(result) => {
+                // stops the subscription
+                self.downloading = None;
+                match result.and_then(|path| {
+                    Url::from_file_path(&path)
+                        .map_err(|_| format!("Invalid download path: {:?}", path))
+                }) {
+                    Ok(url) => {
+                        commands.push(self.update(InstallFromFileMessage::FileSelected(url)));
+                        self.downloaded = true;
+                        self.url.clear();
+                        self.expected_sha256.clear();
+                    }
+                    Err(error) => {
+                        log::error!("download failed: {}", error);
+                        self.download_error = Some(error);
+                    }
+                }
+            }
         }
         Command::batch(commands)
     }
 }
+
+/// Install a batch of AppImages with the backend, under a single authorization.
+/// The backend reports the outcome of each AppImage on its own line, as soon as it is installed
+fn install_batch(installation: &BatchInstallation, mut report: impl FnMut(InstallOutcome)) {
+    let mut backend = match backend_command(installation.scope) {
+        Ok(backend) => backend,
+        Err(e) => {
+            log::error!("backend command error: {:?}", e);
+            return;
+        }
+    };
+    let cmd = backend
+        .arg("--action")
+        .arg("install-batch")
+        .arg("--install-dir")
+        .arg(&installation.installation_dir)
+        .arg("--no-sandbox")
+        .arg(installation.no_sandbox.to_string())
+        .arg("--portable")
+        .arg(installation.portable.to_string())
+        .arg("--existing")
+        .arg(installation.existing.to_string())
+        .arg("--file-paths")
+        .args(&installation.file_paths)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+
+    match cmd {
+        Ok(mut child) => {
+            // the log of a whole batch can fill the pipe, so it is read on its own thread
+            if let Some(stderr) = child.stderr.take() {
+                let lines = BufReader::new(stderr).lines();
+                thread::spawn(move || {
+                    for line in lines.map_while(Result::ok) {
+                        log::debug!("app_hub_backend error: {}", line);
+                    }
+                });
+            }
+
+            // one line per AppImage
+            if let Some(stdout) = child.stdout.take() {
+                let lines = BufReader::new(stdout).lines();
+                for line in lines.map_while(Result::ok) {
+                    log::debug!("app_hub_backend output: {}", line);
+                    if let Some(outcome) = InstallOutcome::parse(&line) {
+                        report(outcome);
+                    }
+                }
+            }
+
+            match child.wait() {
+                Ok(status) => log::debug!("status: {:?}", status),
+                Err(error) => log::error!("failed to wait for the backend: {}", error),
+            }
+        }
+        Err(error) => {
+            log::error!("error: {:?}", error);
+        }
+    }
+}
+
+/// The name of a file, as shown in the page
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// The signature status of an AppImage, as shown in the page
+fn signature_text(signature: &Result<SignatureStatus, String>) -> String {
+    match signature {
+        Ok(SignatureStatus::Trusted { fingerprint }) => {
+            fl!("signature-trusted", fingerprint = fingerprint.clone())
+        }
+        Ok(SignatureStatus::UnknownKey { fingerprint }) => {
+            fl!("signature-unknown-key", fingerprint = fingerprint.clone())
+        }
+        Ok(SignatureStatus::Unsigned) => fl!("signature-unsigned"),
+        Ok(SignatureStatus::Invalid) => fl!("signature-invalid"),
+        Err(error) => fl!("signature-error", error = error.clone()),
+    }
+}
+
+/// The findings of the desktop entry validation of an AppImage, empty when there are none
+fn validation_findings<'a>(
+    validation: &Result<Vec<ValidationIssue>, String>,
+) -> widget::Column<'a, InstallFromFileMessage> {
+    let mut findings = column::<InstallFromFileMessage>().spacing(5);
+    match validation {
+        Ok(issues) if !issues.is_empty() => {
+            findings = findings.push(widget::text::heading(if has_errors(issues) {
+                fl!("desktop-file-invalid")
+            } else {
+                fl!("desktop-file-warnings")
+            }));
+            for issue in issues {
+                findings = findings.push(widget::text::caption(issue.to_string()));
+            }
+        }
+        Err(error) => {
+            findings = findings.push(widget::text::text(fl!(
+                "desktop-file-error",
+                error = error.clone()
+            )));
+        }
+        _ => {}
+    }
+    findings
+}
+
+/// Read the AppImage off the UI thread, reading and hashing the whole file takes a while
+fn preview(path: PathBuf) -> Command<app::Message> {
+    cosmic::command::future(async move {
+        let preview_path = path.clone();
+        let preview = tokio::task::spawn_blocking(move || preview_app_image(&preview_path))
+            .await
+            .unwrap_or_else(|error| Preview::failed(error.to_string()));
+        app::Message::InstallFromFile(InstallFromFileMessage::Previewed(path, Box::new(preview)))
+    })
+}
+
+/// Verify the signature and the desktop entry of an AppImage, and find the installed versions
+/// of its app, without installing anything yet
+fn preview_app_image(path: &Path) -> Preview {
+    let mut reader = match AppImageReader::open(path) {
+        Ok(reader) => reader,
+        Err(error) => return Preview::failed(error),
+    };
+    let signature = verify_app_image_signature(&reader, &keyring_dirs());
+    let validation = reader
+        .desktop_file()
+        .map(|desktop_file| validate_desktop_file(&desktop_file));
+    let identity = match identify_app_image(&mut reader) {
+        Ok(identity) => Some(identity),
+        Err(error) => {
+            log::error!("failed to identify the app: {}", error);
+            None
+        }
+    };
+    let installed = identity
+        .as_ref()
+        .map(find_installed_versions)
+        .unwrap_or_default();
+    Preview {
+        signature,
+        validation,
+        identity,
+        installed,
+    }
+}
+
+/// The installed versions of an app, in the databases of the current user and of all users
+fn find_installed_versions(identity: &AppIdentity) -> Vec<InstallRecord> {
+    InstallScope::ALL
+        .iter()
+        .filter_map(|scope| match InstallDatabase::open(*scope) {
+            Ok(database) => database.find_installed(identity).cloned(),
+            Err(error) => {
+                log::error!("failed to open the {} database: {}", scope, error);
+                None
+            }
+        })
+        .collect()
+}
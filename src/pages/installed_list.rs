@@ -1,24 +1,42 @@
 use std::io::{BufRead, BufReader};
 
-use common_utils::{app_images_helpers::read_all_app, models::installed_app::InstalledApp};
+use common_utils::{
+    app_images_helpers::read_all_app,
+    models::{
+        desktop_action::DesktopAction, exec_command::ExecCommand, install_scope::InstallScope,
+        installed_app::InstalledApp,
+    },
+};
 use cosmic::{
     iced::{self, Length, Padding},
     widget::{self, column},
     Command, Element,
 };
 
-use crate::{app, fl};
+use crate::{app, core::backend::backend_command, fl, i18n::REQUESTED_LANGUAGES};
 
 #[derive(Debug, Clone)]
 pub enum InstalledListMessage {
     UninstallApp(InstalledApp),
+    /// Uninstall the app, deleting its portable home and config directories or not
+    ConfirmUninstall(InstalledApp, bool),
+    CancelUninstall,
     UninstallationComplete,
     LoadApps,
     RunApp(InstalledApp),
+    RunAction(DesktopAction),
+    ToggleDetails(String),
+    /// Make a version installed side by side the one the desktop entry launches
+    ActivateVersion(InstalledApp, String),
+    RemoveVersion(InstalledApp, String),
 }
 
 pub struct InstalledList {
     installed_apps: Vec<InstalledApp>,
+    /// Id of the app whose details are shown
+    expanded_app: Option<String>,
+    /// Id of the app whose uninstallation waits for the choice to keep its portable data
+    uninstall_prompt: Option<String>,
 }
 
 impl Default for InstalledList {
@@ -31,6 +49,8 @@ impl Default for InstalledList {
             } else {
                 Vec::new()
             },
+            expanded_app: None,
+            uninstall_prompt: None,
         }
     }
 }
@@ -52,6 +72,16 @@ impl InstalledList {
         col = col.push(widget::vertical_space(Length::from(20)));
 
         for app in &self.installed_apps {
+            let app_icon: Element<'a, InstalledListMessage> = match &app.icon_path {
+                Some(icon_path) => widget::image::Image::new(icon_path.clone())
+                    .width(Length::from(50))
+                    .into(),
+                // the desktop entry of the app names no icon
+                None => widget::icon::from_name("application-x-executable")
+                    .size(50)
+                    .icon()
+                    .into(),
+            };
             col = col.push(
                 widget::container(
                     widget::row()
@@ -59,18 +89,22 @@ impl InstalledList {
                             widget::column()
                                 .push(
                                     widget::row()
-                                        .push(
-                                            widget::image::Image::new(
-                                                app.icon_path.clone().unwrap(),
-                                            )
-                                            .width(Length::from(50)),
-                                        )
+                                        .push(app_icon)
                                         .push(widget::horizontal_space(Length::from(10)))
                                         .push(
-                                            widget::text::title3(&app.name)
-                                                .vertical_alignment(
-                                                    iced::alignment::Vertical::Center,
-                                                )
+                                            widget::column()
+                                                .push(widget::text::title3(
+                                                    app.localized_name.get(&REQUESTED_LANGUAGES),
+                                                ))
+                                                .push(widget::text::caption(
+                                                    match &app.update_info {
+                                                        Some(update_info) => fl!(
+                                                            "update-info",
+                                                            source = update_info.source()
+                                                        ),
+                                                        None => fl!("no-update-info"),
+                                                    },
+                                                ))
                                                 .height(Length::Fill),
                                         ),
                                 )
@@ -88,6 +122,13 @@ impl InstalledList {
                                                 .style(widget::button::Style::Destructive),
                                         )
                                         .push(widget::horizontal_space(Length::from(10)))
+                                        .push(
+                                            cosmic::widget::button::text(fl!("details"))
+                                                .on_press(InstalledListMessage::ToggleDetails(
+                                                    app.id.clone(),
+                                                )),
+                                        )
+                                        .push(widget::horizontal_space(Length::from(10)))
                                         .push(
                                             cosmic::widget::button::text(fl!("run-app"))
                                                 .on_press(InstalledListMessage::RunApp(app.clone()))
@@ -105,6 +146,33 @@ impl InstalledList {
                 .width(iced::Length::Fill)
                 .align_x(iced::alignment::Horizontal::Left),
             );
+            if self.uninstall_prompt.as_ref() == Some(&app.id) {
+                col = col.push(widget::vertical_space(Length::from(5)));
+                col = col.push(
+                    widget::container(Self::uninstall_prompt_view(app))
+                        .padding(Padding::from(10))
+                        .style(cosmic::theme::Container::Card)
+                        .width(iced::Length::Fill),
+                );
+            }
+            if !app.other_versions.is_empty() {
+                col = col.push(widget::vertical_space(Length::from(5)));
+                col = col.push(
+                    widget::container(Self::versions_view(app))
+                        .padding(Padding::from(10))
+                        .style(cosmic::theme::Container::Card)
+                        .width(iced::Length::Fill),
+                );
+            }
+            if self.expanded_app.as_ref() == Some(&app.id) {
+                col = col.push(widget::vertical_space(Length::from(5)));
+                col = col.push(
+                    widget::container(Self::details_view(app))
+                        .padding(Padding::from(10))
+                        .style(cosmic::theme::Container::Card)
+                        .width(iced::Length::Fill),
+                );
+            }
             col = col.push(widget::vertical_space(Length::from(10)));
         }
 
@@ -115,21 +183,164 @@ impl InstalledList {
             .into()
     }
 
+    /// Show the versions of an app installed side by side, under the version the desktop entry launches
+    fn versions_view<'a>(app: &'a InstalledApp) -> Element<'a, InstalledListMessage> {
+        let mut col = column::<InstalledListMessage>()
+            .spacing(5)
+            .push(widget::text::heading(fl!("app-versions")))
+            .push(widget::text::text(fl!(
+                "active-version",
+                version = app.version.clone().unwrap_or(fl!("unknown-version"))
+            )));
+        for version in &app.other_versions {
+            let label = version.label();
+            col = col.push(
+                widget::row()
+                    .spacing(10)
+                    .align_items(iced::Alignment::Center)
+                    .push(widget::text::text(label.clone()).width(Length::Fill))
+                    .push(cosmic::widget::button::text(fl!("make-default")).on_press(
+                        InstalledListMessage::ActivateVersion(app.clone(), label.clone()),
+                    ))
+                    .push(
+                        cosmic::widget::button::text(fl!("remove-version"))
+                            .on_press(InstalledListMessage::RemoveVersion(app.clone(), label))
+                            .style(widget::button::Style::Destructive),
+                    ),
+            );
+        }
+        col.into()
+    }
+
+    /// Ask whether the portable home and config directories of the app are deleted with it
+    fn uninstall_prompt_view<'a>(app: &'a InstalledApp) -> Element<'a, InstalledListMessage> {
+        let mut col = column::<InstalledListMessage>()
+            .spacing(5)
+            .push(widget::text::heading(fl!("portable-data-prompt")));
+        for dir in &app.portable_dirs {
+            col = col.push(widget::text::caption(dir.display().to_string()));
+        }
+        col.push(
+            widget::row()
+                .spacing(10)
+                .push(
+                    cosmic::widget::button::text(fl!("cancel"))
+                        .on_press(InstalledListMessage::CancelUninstall),
+                )
+                .push(
+                    cosmic::widget::button::text(fl!("keep-portable-data"))
+                        .on_press(InstalledListMessage::ConfirmUninstall(app.clone(), false)),
+                )
+                .push(
+                    cosmic::widget::button::text(fl!("delete-portable-data"))
+                        .on_press(InstalledListMessage::ConfirmUninstall(app.clone(), true))
+                        .style(widget::button::Style::Destructive),
+                ),
+        )
+        .into()
+    }
+
+    /// Show the desktop entry details, the desktop actions and the AppStream metadata of an installed app
+    fn details_view<'a>(app: &'a InstalledApp) -> Element<'a, InstalledListMessage> {
+        let mut col = column::<InstalledListMessage>().spacing(5);
+        if app.scope == InstallScope::System {
+            col = col.push(widget::text::caption(fl!("installed-for-all-users")));
+        }
+        if let Some(generic_name) = &app.generic_name {
+            col = col.push(widget::text::heading(
+                generic_name.get(&REQUESTED_LANGUAGES),
+            ));
+        }
+        if let Some(comment) = &app.comment {
+            col = col.push(widget::text::text(comment.get(&REQUESTED_LANGUAGES)));
+        }
+        let actions: Vec<&DesktopAction> = app
+            .actions
+            .iter()
+            .filter(|action| action.exec.is_some())
+            .collect();
+        if !actions.is_empty() {
+            let mut actions_row = widget::row::<InstalledListMessage>().spacing(10);
+            for action in actions {
+                actions_row = actions_row.push(
+                    cosmic::widget::button::text(&action.name)
+                        .on_press(InstalledListMessage::RunAction(action.clone())),
+                );
+            }
+            col = col
+                .push(widget::text::heading(fl!("app-actions")))
+                .push(actions_row);
+        }
+
+        let metadata = match &app.metadata {
+            Some(metadata) => metadata,
+            None => return col.push(widget::text::text(fl!("no-app-details"))).into(),
+        };
+
+        if let Some(summary) = &metadata.summary {
+            col = col.push(widget::text::title4(summary));
+        }
+        if let Some(description) = &metadata.description {
+            col = col.push(widget::text::text(description));
+        }
+        if let Some(license) = &metadata.project_license {
+            col = col.push(widget::text::caption(fl!("app-license", license = license.clone())));
+        }
+        if let Some(homepage) = &metadata.homepage {
+            col = col.push(widget::text::caption(fl!(
+                "app-homepage",
+                homepage = homepage.clone()
+            )));
+        }
+        if !metadata.screenshots.is_empty() {
+            col = col.push(widget::text::caption(fl!(
+                "app-screenshots",
+                count = metadata.screenshots.len()
+            )));
+        }
+        if !metadata.releases.is_empty() {
+            col = col.push(widget::text::heading(fl!("app-releases")));
+            for release in &metadata.releases {
+                let title = match &release.date {
+                    Some(date) => format!("{} ({})", release.version, date),
+                    None => release.version.clone(),
+                };
+                col = col.push(widget::text::body(title));
+                if let Some(description) = &release.description {
+                    col = col.push(widget::text::caption(description));
+                }
+            }
+        }
+        col.into()
+    }
+
     pub fn update(&mut self, message: InstalledListMessage) -> Command<crate::app::Message> {
         let mut commands = Vec::new();
         match message {
             InstalledListMessage::UninstallApp(app) => {
+                if app.portable_dirs.is_empty() {
+                    return self.update(InstalledListMessage::ConfirmUninstall(app, false));
+                }
+                // the data of the app is only deleted on request
+                self.uninstall_prompt = Some(app.id.clone());
+            }
+            InstalledListMessage::CancelUninstall => {
+                self.uninstall_prompt = None;
+            }
+            InstalledListMessage::ConfirmUninstall(app, remove_portable_data) => {
                 log::info!("uninstall app: {:?}", app);
+                self.uninstall_prompt = None;
 
-                let current_exe = std::env::current_exe().map_err(|_| "unable to get current exe");
-                match current_exe {
-                    Ok(current_exe) => {
-                        let cmd = std::process::Command::new("pkexec")
-                            .arg(current_exe.parent().unwrap().join("app_hub_backend"))
+                // only apps installed for all users need privileges to be removed
+                match backend_command(app.scope) {
+                    Ok(mut backend) => {
+                        let cmd = backend
                             .arg("--action")
                             .arg("uninstall")
-                            .arg("--uninstall-app-name")
-                            .arg(app.name)
+                            .arg("--app-id")
+                            .arg(app.id)
+                            .arg("--remove-portable-data")
+                            .arg(remove_portable_data.to_string())
                             .spawn();
                         match cmd {
                             Ok(mut res) => {
@@ -169,15 +380,70 @@ impl InstalledList {
                             }
                         }
                     }
-                    Err(_) => todo!(),
+                    Err(error) => {
+                        log::error!("backend command error: {:?}", error);
+                    }
+                }
+            }
+            InstalledListMessage::ActivateVersion(app, version) => {
+                log::info!("activate version {} of app {}", version, app.id);
+                let arguments = [
+                    "--action",
+                    "activate",
+                    "--app-id",
+                    &app.id,
+                    "--app-version",
+                    &version,
+                ];
+                if run_backend(app.scope, &arguments) {
+                    self.load_apps();
+                }
+            }
+            InstalledListMessage::RemoveVersion(app, version) => {
+                log::info!("remove version {} of app {}", version, app.id);
+                let arguments = [
+                    "--action",
+                    "remove-version",
+                    "--app-id",
+                    &app.id,
+                    "--app-version",
+                    &version,
+                ];
+                if run_backend(app.scope, &arguments) {
+                    self.load_apps();
                 }
             }
             InstalledListMessage::UninstallationComplete | InstalledListMessage::LoadApps => {
                 self.load_apps();
             }
+            InstalledListMessage::ToggleDetails(app_id) => {
+                if self.expanded_app.as_ref() == Some(&app_id) {
+                    self.expanded_app = None;
+                } else {
+                    self.expanded_app = Some(app_id);
+                }
+            }
+            InstalledListMessage::RunAction(action) => {
+                log::info!("running action: {:?}", action);
+                match ExecCommand::parse(action.exec.as_deref().unwrap_or_default()) {
+                    Ok(command) => {
+                        let arguments = command.launch_arguments();
+                        if let Err(error) = std::process::Command::new(&arguments[0])
+                            .args(&arguments[1..])
+                            .spawn()
+                        {
+                            log::error!("error: {:?}", error);
+                        }
+                    }
+                    Err(error) => log::error!("Invalid command of action {}: {}", action.id, error),
+                }
+            }
             InstalledListMessage::RunApp(installed_app) => {
                 log::info!("running app: {:?}", installed_app);
-                let cmd = std::process::Command::new(installed_app.app_path).spawn();
+                let arguments = installed_app.exec.launch_arguments();
+                let cmd = std::process::Command::new(&arguments[0])
+                    .args(&arguments[1..])
+                    .spawn();
                 match cmd {
                     Ok(mut res) => {
                         if let Some(stderr) = res.stderr.take() {
@@ -198,3 +464,27 @@ impl InstalledList {
         Command::batch(commands)
     }
 }
+
+/// Run the backend with the given arguments, with privileges for the apps installed for all users.
+/// Returns whether it succeeded
+fn run_backend(scope: InstallScope, arguments: &[&str]) -> bool {
+    let mut backend = match backend_command(scope) {
+        Ok(backend) => backend,
+        Err(error) => {
+            log::error!("backend command error: {:?}", error);
+            return false;
+        }
+    };
+    match backend.args(arguments).output() {
+        Ok(output) => {
+            for line in String::from_utf8_lossy(&output.stderr).lines() {
+                log::error!("app_hub_backend error: {}", line);
+            }
+            output.status.success()
+        }
+        Err(error) => {
+            log::error!("error: {:?}", error);
+            false
+        }
+    }
+}